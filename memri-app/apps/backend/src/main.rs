@@ -10,14 +10,21 @@ use axum::{
     http::StatusCode,
     middleware,
     response::sse::{Event, KeepAlive, Sse},
-    routing::get,
+    routing::{get, post},
     Json, Router,
 };
 use futures_util::{Stream, StreamExt};
-use memri_capture::{monitor::list_monitors, start_capture, CaptureConfig};
-use memri_config::AppConfig;
-use memri_ocr::{OcrEngine, WindowsOcr};
-use memri_storage::{CaptureWithWindows, ChatMessage, SqliteSink};
+use memri_capture::{
+    frame_source_from_app_config, monitor::list_monitors, start_capture, CaptureConfig,
+    CaptureEvent, CaptureHandle, CaptureStatus, DetectionSettings, OcrMetrics, Redactor, Schedule,
+    ScheduleSlot,
+};
+use memri_config::{AppConfig, DetectionConfig, OcrConfig};
+use memri_ocr::{
+    engine_from_name, CachedOcr, CompositeOcr, CompositeStrategy, OcrCacheStats, OcrCacheStore,
+    OcrEngine, OcrLayout, OcrPayload, OcrRect,
+};
+use memri_storage::{CaptureWithWindows, ChatMessage, SqliteSink, UsageTotal};
 use serde::Deserialize;
 use serde::Serialize;
 use tokio::signal;
@@ -25,7 +32,7 @@ use tokio::sync::broadcast;
 use tokio::task::JoinHandle;
 use tokio_stream::wrappers::BroadcastStream;
 use tower_http::cors::CorsLayer;
-use tracing::{error, info, Level};
+use tracing::{error, info, warn, Level};
 use tracing_subscriber::{fmt, EnvFilter};
 
 #[tokio::main]
@@ -35,42 +42,39 @@ async fn main() -> Result<()> {
     let app_config = AppConfig::from_env()?;
     info!(?app_config, "loaded configuration");
 
-    // Discover available monitors up front and reconcile config.
-    let available = list_monitors().await.unwrap_or_default();
-    if available.is_empty() {
-        error!("no monitors detected on this system; capture cannot start");
-        return Ok(());
-    }
-
     // Build the desired monitor list: use explicit monitor_ids if provided, otherwise the single monitor_id.
-    let mut requested = if app_config.monitor_ids.is_empty() {
+    let requested = if app_config.monitor_ids.is_empty() {
         vec![app_config.monitor_id]
     } else {
         app_config.monitor_ids.clone()
     };
 
-    // Filter to available monitors; fallback to first available if none match.
-    let available_ids: Vec<u32> = available.iter().map(|m| m.id()).collect();
-    requested.retain(|id| available_ids.contains(id));
-    if requested.is_empty() {
-        let fallback = available_ids[0];
-        error!(
-            "configured monitor(s) not found: {:?}; falling back to primary monitor {}",
-            if app_config.monitor_ids.is_empty() {
-                vec![app_config.monitor_id]
-            } else {
-                app_config.monitor_ids.clone()
-            },
-            fallback
-        );
-        requested.push(fallback);
+    // The capture loop follows connected monitors itself (falling back to the
+    // primary one when none of the requested ids is present); this is only a hint.
+    if app_config.capture_source != "replay" {
+        match list_monitors().await {
+            Ok(available) if !available.is_empty() => {
+                let available_ids: Vec<u32> = available.iter().map(|m| m.id()).collect();
+                info!("available monitors: {:?}", available_ids);
+            }
+            Ok(_) => warn!("no monitors detected yet; capture starts once one is connected"),
+            Err(err) => warn!("failed to list monitors: {err}"),
+        }
     }
 
-    info!("available monitors: {:?}", available_ids);
-    info!("using monitors: {:?}", requested);
+    info!(
+        "requested monitors: {:?} (capture all: {})",
+        requested, app_config.capture_all_monitors
+    );
     let (events_tx, _events_rx) = broadcast::channel::<String>(64);
     let storage = Arc::new(SqliteSink::from_app_config(&app_config).await?);
-    let ocr_engine: Arc<dyn OcrEngine> = Arc::new(WindowsOcr);
+    let ocr_engine = build_ocr_engine(&app_config.ocr)?;
+    let ocr_cache = build_ocr_cache(&app_config, ocr_engine.clone(), &storage);
+    let ocr_engine: Arc<dyn OcrEngine> = match &ocr_cache {
+        Some(cache) => cache.clone(),
+        None => ocr_engine,
+    };
+    info!(engine = ocr_engine.name(), "OCR engine ready");
     let anthropic = AnthropicClient::from_env();
     let api_key = env::var("MEMRI_API_KEY").ok();
 
@@ -79,24 +83,40 @@ async fn main() -> Result<()> {
         tx: events_tx.clone(),
     });
 
-    let mut capture_handles = Vec::new();
-    for monitor_id in requested {
-        let cfg = CaptureConfig::from_app_config(&app_config, monitor_id);
-        let handle = start_capture(cfg, ocr_engine.clone(), notifying_sink.clone()).await?;
-        capture_handles.push(handle);
-    }
+    // Settings handle shared with the API so threshold updates reach the running loop.
+    let detection = DetectionSettings::new(app_config.detection.clone());
+    let schedule = Schedule::from_config(&app_config.schedule)?;
+
+    // Capture loop events are re-published as JSON on the shared `/events` stream.
+    let (capture_events_tx, capture_events_rx) = broadcast::channel::<CaptureEvent>(64);
+    tokio::spawn(forward_capture_events(capture_events_rx, events_tx.clone()));
+
+    // One loop captures every requested monitor per tick.
+    let mut cfg = CaptureConfig::from_app_config(&app_config, requested);
+    cfg.detection = detection.clone();
+    let source = frame_source_from_app_config(&app_config)?;
+    let capture = start_capture(
+        cfg,
+        source,
+        ocr_engine.clone(),
+        notifying_sink.clone(),
+        capture_events_tx.clone(),
+    )
+    .await?;
 
     let api_task = start_api_server(
         storage.clone(),
         events_tx.clone(),
         anthropic.clone(),
+        detection,
+        capture.clone(),
+        schedule,
+        ocr_cache,
         api_key,
     );
 
     signal::ctrl_c().await?;
-    for handle in capture_handles {
-        handle.shutdown().await;
-    }
+    capture.shutdown().await;
     api_task.abort();
 
     Ok(())
@@ -118,12 +138,91 @@ struct AppState {
     storage: Arc<SqliteSink>,
     events_tx: broadcast::Sender<String>,
     anthropic: Option<AnthropicClient>,
+    detection: DetectionSettings,
+    capture: CaptureHandle,
+    schedule: Option<Schedule>,
+    ocr_cache: Option<Arc<CachedOcr>>,
+}
+
+/// The configured OCR engine; `composite` wraps each engine listed in
+/// `engines` with the configured strategy.
+fn build_ocr_engine(config: &OcrConfig) -> Result<Arc<dyn OcrEngine>> {
+    let data_path = config.tesseract_data_path.as_deref();
+    if config.engine.trim() != "composite" {
+        return engine_from_name(&config.engine, data_path);
+    }
+    let engines = config
+        .engines
+        .iter()
+        .map(|name| engine_from_name(name, data_path))
+        .collect::<Result<Vec<_>>>()?;
+    let strategy: CompositeStrategy = config.strategy.parse()?;
+    info!(engines = ?config.engines, ?strategy, "combining OCR engines");
+    Ok(Arc::new(CompositeOcr::new(
+        engines,
+        strategy,
+        config.min_confidence,
+    )?))
+}
+
+/// Wrap `engine` in the OCR result cache, unless both of its tiers are off.
+fn build_ocr_cache(
+    app_config: &AppConfig,
+    engine: Arc<dyn OcrEngine>,
+    storage: &Arc<SqliteSink>,
+) -> Option<Arc<CachedOcr>> {
+    let config = &app_config.ocr;
+    if config.cache_capacity == 0 && !config.cache_persistent {
+        return None;
+    }
+    let store: Option<Arc<dyn OcrCacheStore>> = config.cache_persistent.then(|| {
+        Arc::new(SqliteOcrCache {
+            storage: storage.clone(),
+            max_entries: config.cache_max_entries,
+            redactor: Redactor::new(&app_config.redaction),
+        }) as Arc<dyn OcrCacheStore>
+    });
+    Some(Arc::new(CachedOcr::new(
+        engine,
+        config.cache_capacity,
+        store,
+    )))
 }
 
+/// Persistent tier of the OCR cache, in the capture database. Results the
+/// redactor would change are never written, so no secret reaches the table.
+struct SqliteOcrCache {
+    storage: Arc<SqliteSink>,
+    max_entries: u64,
+    redactor: Redactor,
+}
+
+#[async_trait]
+impl OcrCacheStore for SqliteOcrCache {
+    async fn load(&self, key: &str) -> Result<Option<String>> {
+        self.storage.load_cached_ocr(key).await
+    }
+
+    async fn store(&self, key: &str, payload_json: &str) -> Result<()> {
+        self.storage
+            .store_cached_ocr(key, payload_json, self.max_entries)
+            .await
+    }
+
+    fn accepts(&self, payload: &OcrPayload) -> bool {
+        !self.redactor.matches(&payload.text, &payload.layout)
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
 fn start_api_server(
     storage: Arc<SqliteSink>,
     events_tx: broadcast::Sender<String>,
     anthropic: Option<AnthropicClient>,
+    detection: DetectionSettings,
+    capture: CaptureHandle,
+    schedule: Option<Schedule>,
+    ocr_cache: Option<Arc<CachedOcr>>,
     api_key: Option<String>,
 ) -> JoinHandle<()> {
     tokio::spawn(async move {
@@ -131,6 +230,10 @@ fn start_api_server(
             storage,
             events_tx,
             anthropic,
+            detection,
+            capture,
+            schedule,
+            ocr_cache,
         };
         let app = build_router(state, api_key);
 
@@ -157,7 +260,18 @@ fn build_router(state: AppState, api_key: Option<String>) -> Router {
         .route("/health", get(|| async { StatusCode::OK }))
         .route("/captures", get(list_captures))
         .route("/captures/images", get(get_capture_images))
+        .route("/captures/layouts", get(get_capture_layouts))
         .route("/events", get(capture_events))
+        .route("/detection", get(get_detection).put(update_detection))
+        .route("/capture/status", get(capture_status))
+        .route("/capture/pause", post(pause_capture))
+        .route("/capture/resume", post(resume_capture))
+        .route("/capture/snooze", post(snooze_capture))
+        .route("/capture/now", post(capture_now))
+        .route("/ocr/metrics", get(ocr_metrics))
+        .route("/schedule", get(get_schedule))
+        .route("/usage/apps", get(app_usage))
+        .route("/usage/titles", get(title_usage))
         .route("/chat", get(list_chat_messages).post(add_chat_message))
         .route("/assistant", get(list_chat_messages).post(run_assistant))
         .route(
@@ -231,6 +345,281 @@ async fn get_capture_images(
         .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
 }
 
+#[derive(Deserialize)]
+struct LayoutParams {
+    ids: String, // Comma-separated capture IDs
+    /// Search terms whose words are returned as `highlights`.
+    q: Option<String>,
+}
+
+#[derive(Serialize)]
+struct WindowLayoutResponse {
+    window_name: Option<String>,
+    app_name: Option<String>,
+    image_hash: Option<String>,
+    layout: OcrLayout,
+    /// Boxes of words matching `q`, in the pixels of `layout`.
+    highlights: Vec<OcrRect>,
+}
+
+/// OCR layouts of the windows of specific captures, keyed by capture ID, so
+/// the UI can outline search hits on the image. Windows stored before
+/// layouts were recorded are left out.
+async fn get_capture_layouts(
+    State(state): State<AppState>,
+    Query(params): Query<LayoutParams>,
+) -> Result<Json<std::collections::HashMap<i64, Vec<WindowLayoutResponse>>>, StatusCode> {
+    let ids: Vec<i64> = params
+        .ids
+        .split(',')
+        .filter_map(|s| s.trim().parse().ok())
+        .collect();
+
+    let rows = state
+        .storage
+        .fetch_layouts_for_captures(&ids)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    let query = params.q.unwrap_or_default();
+    let mut layouts: std::collections::HashMap<i64, Vec<WindowLayoutResponse>> =
+        std::collections::HashMap::new();
+    for row in rows {
+        let Ok(layout) = serde_json::from_str::<OcrLayout>(&row.ocr_json) else {
+            continue;
+        };
+        let highlights = layout
+            .matching_words(&query)
+            .into_iter()
+            .map(|word| word.bounds)
+            .collect();
+        layouts
+            .entry(row.capture_id)
+            .or_default()
+            .push(WindowLayoutResponse {
+                window_name: row.window_name,
+                app_name: row.app_name,
+                image_hash: row.image_hash,
+                layout,
+                highlights,
+            });
+    }
+    Ok(Json(layouts))
+}
+
+async fn get_detection(State(state): State<AppState>) -> Json<DetectionConfig> {
+    Json(state.detection.snapshot())
+}
+
+/// Replace the change-detection thresholds and profiles; capture loops pick
+/// them up on their next frame.
+async fn update_detection(
+    State(state): State<AppState>,
+    Json(input): Json<DetectionConfig>,
+) -> Result<Json<DetectionConfig>, StatusCode> {
+    let all_thresholds =
+        std::iter::once(&input.defaults).chain(input.profiles.iter().map(|p| &p.thresholds));
+    for thresholds in all_thresholds {
+        if thresholds.ssim_sample_size == 0
+            || !(0.0..=1.0).contains(&thresholds.histogram_threshold)
+            || !(-1.0..=1.0).contains(&thresholds.ssim_threshold)
+            || !(0.0..=1.0).contains(&thresholds.tile_change_fraction)
+        {
+            return Err(StatusCode::BAD_REQUEST);
+        }
+    }
+
+    state.detection.replace(input.clone());
+    info!(profiles = input.profiles.len(), "detection settings updated");
+
+    let _ = state.events_tx.send(
+        serde_json::json!({
+            "type": "detection_updated",
+            "profiles": input.profiles.len(),
+        })
+        .to_string(),
+    );
+
+    Ok(Json(input))
+}
+
+async fn capture_status(State(state): State<AppState>) -> Result<Json<CaptureStatus>, StatusCode> {
+    control_reply(&state, None, state.capture.status().await)
+}
+
+async fn pause_capture(State(state): State<AppState>) -> Result<Json<CaptureStatus>, StatusCode> {
+    control_reply(&state, Some("pause"), state.capture.pause().await)
+}
+
+async fn resume_capture(State(state): State<AppState>) -> Result<Json<CaptureStatus>, StatusCode> {
+    control_reply(&state, Some("resume"), state.capture.resume().await)
+}
+
+/// OCR queue metrics, plus the result cache's counters when it is enabled.
+#[derive(Serialize)]
+struct OcrMetricsResponse {
+    #[serde(flatten)]
+    queue: OcrMetrics,
+    cache: Option<OcrCacheStats>,
+}
+
+async fn ocr_metrics(State(state): State<AppState>) -> Json<OcrMetricsResponse> {
+    Json(OcrMetricsResponse {
+        queue: state.capture.ocr_metrics(),
+        cache: state.ocr_cache.as_ref().map(|cache| cache.stats()),
+    })
+}
+
+#[derive(Deserialize)]
+struct SnoozeInput {
+    minutes: u64,
+}
+
+async fn snooze_capture(
+    State(state): State<AppState>,
+    Json(input): Json<SnoozeInput>,
+) -> Result<Json<CaptureStatus>, StatusCode> {
+    if input.minutes == 0 {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    let duration = std::time::Duration::from_secs(input.minutes.saturating_mul(60));
+    control_reply(&state, Some("snooze"), state.capture.snooze(duration).await)
+}
+
+#[derive(Deserialize, Default)]
+struct CaptureNowInput {
+    note: Option<String>,
+}
+
+/// Store a frame from every monitor immediately; the JSON body is optional.
+async fn capture_now(
+    State(state): State<AppState>,
+    input: Option<Json<CaptureNowInput>>,
+) -> Result<Json<CaptureStatus>, StatusCode> {
+    let note = input
+        .and_then(|Json(input)| input.note)
+        .filter(|note| !note.trim().is_empty());
+    control_reply(&state, None, state.capture.capture_now(note).await)
+}
+
+/// Turn the result of a control call into a response; 503 once the capture
+/// loop has stopped. When `action` is set, a `capture_control` event is sent
+/// on `/events`.
+fn control_reply(
+    state: &AppState,
+    action: Option<&str>,
+    result: Result<CaptureStatus>,
+) -> Result<Json<CaptureStatus>, StatusCode> {
+    let status = result.map_err(|err| {
+        warn!("capture control failed: {err}");
+        StatusCode::SERVICE_UNAVAILABLE
+    })?;
+
+    if let Some(action) = action {
+        let _ = state.events_tx.send(
+            serde_json::json!({
+                "type": "capture_control",
+                "action": action,
+                "status": status,
+            })
+            .to_string(),
+        );
+    }
+
+    Ok(Json(status))
+}
+
+#[derive(Deserialize)]
+struct ScheduleParams {
+    limit: Option<usize>,
+}
+
+#[derive(Serialize)]
+struct ScheduleOverview {
+    /// False when no schedule restricts capture.
+    enabled: bool,
+    open: bool,
+    next_change_ms: Option<i64>,
+    upcoming: Vec<ScheduleSlot>,
+}
+
+/// Whether the capture schedule is open now and the next capture periods.
+async fn get_schedule(
+    State(state): State<AppState>,
+    Query(params): Query<ScheduleParams>,
+) -> Json<ScheduleOverview> {
+    let limit = params.limit.unwrap_or(7).min(50);
+    let now = chrono::Local::now();
+    let overview = match &state.schedule {
+        Some(schedule) => ScheduleOverview {
+            enabled: true,
+            open: schedule.is_open_at(now),
+            next_change_ms: schedule.next_change(now).map(|at| at.timestamp_millis()),
+            upcoming: schedule.upcoming(now, limit),
+        },
+        None => ScheduleOverview {
+            enabled: false,
+            open: true,
+            next_change_ms: None,
+            upcoming: Vec::new(),
+        },
+    };
+    Json(overview)
+}
+
+#[derive(Deserialize)]
+struct UsageParams {
+    /// Defaults to the last 24 hours.
+    start_ms: Option<i64>,
+    end_ms: Option<i64>,
+    /// Only titles of this app (`/usage/titles`).
+    app: Option<String>,
+    limit: Option<u32>,
+}
+
+impl UsageParams {
+    fn range(&self) -> Result<(i64, i64), StatusCode> {
+        let end_ms = self.end_ms.unwrap_or_else(time_ms);
+        let start_ms = self.start_ms.unwrap_or(end_ms - 86_400_000);
+        if start_ms >= end_ms {
+            return Err(StatusCode::BAD_REQUEST);
+        }
+        Ok((start_ms, end_ms))
+    }
+
+    fn limit(&self) -> i64 {
+        self.limit.unwrap_or(20).min(500) as i64
+    }
+}
+
+/// Time spent in each app over a range, longest first.
+async fn app_usage(
+    State(state): State<AppState>,
+    Query(params): Query<UsageParams>,
+) -> Result<Json<Vec<UsageTotal>>, StatusCode> {
+    let (start_ms, end_ms) = params.range()?;
+    state
+        .storage
+        .app_usage(start_ms, end_ms, params.limit())
+        .await
+        .map(Json)
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
+}
+
+/// Time spent in each window title over a range, optionally for one app.
+async fn title_usage(
+    State(state): State<AppState>,
+    Query(params): Query<UsageParams>,
+) -> Result<Json<Vec<UsageTotal>>, StatusCode> {
+    let (start_ms, end_ms) = params.range()?;
+    state
+        .storage
+        .title_usage(start_ms, end_ms, params.app.as_deref(), params.limit())
+        .await
+        .map(Json)
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
+}
+
 async fn list_chat_messages(
     State(state): State<AppState>,
     Query(params): Query<ListParams>,
@@ -571,8 +960,7 @@ async fn stream_assistant(
                     // Parse SSE format: extract text from content_block_delta events
                     let mut extracted_text = String::new();
                     for line in chunk.lines() {
-                        if line.starts_with("data: ") {
-                            let json_str = &line[6..]; // Skip "data: " prefix
+                        if let Some(json_str) = line.strip_prefix("data: ") {
                             if let Ok(json) = serde_json::from_str::<serde_json::Value>(json_str) {
                                 // Look for content_block_delta with text
                                 if json["type"] == "content_block_delta" {
@@ -632,6 +1020,26 @@ async fn capture_events(
     Sse::new(stream).keep_alive(KeepAlive::default())
 }
 
+async fn forward_capture_events(
+    mut rx: broadcast::Receiver<CaptureEvent>,
+    tx: broadcast::Sender<String>,
+) {
+    loop {
+        match rx.recv().await {
+            Ok(event) => match serde_json::to_string(&event) {
+                Ok(payload) => {
+                    let _ = tx.send(payload);
+                }
+                Err(err) => warn!("failed to serialize capture event: {err}"),
+            },
+            Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                warn!("dropped {skipped} capture events");
+            }
+            Err(broadcast::error::RecvError::Closed) => break,
+        }
+    }
+}
+
 struct NotifyingSink {
     inner: Arc<SqliteSink>,
     tx: broadcast::Sender<String>,
@@ -639,22 +1047,65 @@ struct NotifyingSink {
 
 #[async_trait]
 impl memri_storage::CaptureSink for NotifyingSink {
-    async fn persist_batch(&self, batch: memri_storage::CaptureBatch) -> Result<()> {
-        self.inner.persist_batch(batch.clone()).await?;
+    async fn persist_batch(
+        &self,
+        batch: memri_storage::CaptureBatch,
+    ) -> Result<memri_storage::PersistedBatch> {
+        let persisted = self.inner.persist_batch(batch.clone()).await?;
 
         // Emit minimal event payload; ignore if no subscribers.
         let _ = self.tx.send(
             serde_json::json!({
                 "type": "capture",
+                "capture_id": persisted.capture_id,
                 "frame_number": batch.frame_number,
                 "timestamp_ms": batch.timestamp_ms,
+                "monitor_id": batch.monitor_id,
+                "monitor_name": batch.monitor_name,
                 "windows": batch.windows.len(),
             })
             .to_string(),
         );
 
+        Ok(persisted)
+    }
+
+    async fn recent_window_hashes(
+        &self,
+        since_ms: i64,
+    ) -> Result<Vec<memri_storage::HashedWindow>> {
+        self.inner.recent_window_hashes(since_ms).await
+    }
+
+    async fn last_frame_number(&self) -> Result<Option<u64>> {
+        self.inner.last_frame_number().await
+    }
+
+    async fn update_window_ocr(
+        &self,
+        window_id: i64,
+        update: memri_storage::WindowOcrUpdate,
+    ) -> Result<()> {
+        let status = update.status;
+        self.inner.update_window_ocr(window_id, update).await?;
+
+        let _ = self.tx.send(
+            serde_json::json!({
+                "type": "ocr_complete",
+                "window_id": window_id,
+                "status": status,
+            })
+            .to_string(),
+        );
         Ok(())
     }
+
+    async fn save_focus_session(
+        &self,
+        session: &memri_storage::FocusSession,
+    ) -> Result<Option<i64>> {
+        self.inner.save_focus_session(session).await
+    }
 }
 
 #[derive(Clone)]
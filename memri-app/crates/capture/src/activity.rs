@@ -0,0 +1,112 @@
+use memri_storage::{CaptureSink, FocusSession};
+use tracing::{debug, warn};
+
+/// An open session is written back at most this often while it lasts, so a
+/// crash loses little time.
+const FLUSH_INTERVAL_MS: i64 = 60_000;
+
+/// The focused window seen in one tick.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct FocusSample {
+    pub(crate) app_name: String,
+    pub(crate) window_name: String,
+    pub(crate) monitor_id: u32,
+}
+
+struct OpenSession {
+    session: FocusSession,
+    /// End time last written to the sink.
+    flushed_end_ms: i64,
+}
+
+/// Turns the focused window of every tick into `focus_sessions` rows. A
+/// session lasts while the same window keeps focus; a gap between ticks
+/// longer than `max_gap_ms` (pause, suspend, closed schedule) ends it at the
+/// last tick that saw it.
+pub(crate) struct ActivityTracker {
+    max_gap_ms: i64,
+    current: Option<OpenSession>,
+}
+
+impl ActivityTracker {
+    pub(crate) fn new(max_gap_ms: i64) -> Self {
+        Self {
+            max_gap_ms: max_gap_ms.max(1),
+            current: None,
+        }
+    }
+
+    /// Record the focused window of a tick; `None` when nothing trackable had
+    /// focus (no window, or a private one).
+    pub(crate) async fn observe(
+        &mut self,
+        focus: Option<FocusSample>,
+        timestamp_ms: i64,
+        sink: &dyn CaptureSink,
+    ) {
+        if let Some(open) = &mut self.current {
+            let gap_ms = timestamp_ms - open.session.end_ms;
+            let same_window = focus.as_ref().is_some_and(|focus| {
+                focus.app_name == open.session.app_name
+                    && focus.window_name == open.session.window_name
+                    && Some(focus.monitor_id) == open.session.monitor_id
+            });
+            if same_window && (0..=self.max_gap_ms).contains(&gap_ms) {
+                open.session.end_ms = timestamp_ms;
+                if timestamp_ms - open.flushed_end_ms >= FLUSH_INTERVAL_MS {
+                    Self::flush(open, sink).await;
+                }
+                return;
+            }
+            // Focus moved on at this tick, unless the loop was not ticking.
+            if (0..=self.max_gap_ms).contains(&gap_ms) {
+                open.session.end_ms = timestamp_ms;
+            }
+            self.close(sink).await;
+        }
+
+        let Some(focus) = focus else {
+            return;
+        };
+        debug!(app = focus.app_name, "focus session started");
+        let mut open = OpenSession {
+            session: FocusSession {
+                id: None,
+                app_name: focus.app_name,
+                window_name: focus.window_name,
+                monitor_id: Some(focus.monitor_id),
+                start_ms: timestamp_ms,
+                end_ms: timestamp_ms,
+            },
+            flushed_end_ms: timestamp_ms,
+        };
+        Self::flush(&mut open, sink).await;
+        self.current = Some(open);
+    }
+
+    /// End the open session at the last tick that saw it, e.g. when capture
+    /// is paused or stops.
+    pub(crate) async fn close(&mut self, sink: &dyn CaptureSink) {
+        if let Some(mut open) = self.current.take() {
+            debug!(
+                app = open.session.app_name,
+                duration_ms = open.session.end_ms - open.session.start_ms,
+                "focus session ended"
+            );
+            Self::flush(&mut open, sink).await;
+        }
+    }
+
+    async fn flush(open: &mut OpenSession, sink: &dyn CaptureSink) {
+        match sink.save_focus_session(&open.session).await {
+            Ok(id) => {
+                open.session.id = open.session.id.or(id);
+                open.flushed_end_ms = open.session.end_ms;
+            }
+            Err(err) => warn!(
+                app = open.session.app_name,
+                "failed to store focus session: {err}"
+            ),
+        }
+    }
+}
@@ -1,27 +1,229 @@
-use image::{imageops::FilterType, DynamicImage, GrayImage};
+use std::sync::{Arc, RwLock};
+
+use image::{imageops, imageops::FilterType, DynamicImage, GrayImage};
+use memri_config::{DetectionConfig, DetectionThresholds};
+use memri_storage::ScreenRect;
 use tracing::trace;
 
 const HISTOGRAM_BINS: usize = 256;
-const HISTOGRAM_THRESHOLD: f32 = 0.08; // total variation distance threshold
-const SSIM_THRESHOLD: f32 = 0.92; // minimum acceptable similarity score
-const SSIM_SAMPLE_SIZE: u32 = 96; // downsample target edge for SSIM calculation
+const TILE_SIZE: u32 = 128; // maximum tile edge in source pixels
+const TILE_SAMPLE_SIZE: u32 = 16; // downsampled edge of each tile
 
 #[derive(Clone)]
 struct FrameSignature {
     histogram: [u32; HISTOGRAM_BINS],
     ssim_sample: Vec<u8>,
+    ssim_sample_size: u32,
+    tiles: TileGrid,
 }
 
 impl FrameSignature {
-    fn from_image(image: &DynamicImage) -> Self {
+    fn from_image(image: &DynamicImage, ssim_sample_size: u32) -> Self {
         let grayscale = image.to_luma8();
         let histogram = build_histogram(&grayscale);
-        let downsampled = downsample_for_ssim(&grayscale);
+        let downsampled = downsample_for_ssim(&grayscale, ssim_sample_size);
+        let tiles = TileGrid::from_grayscale(&grayscale);
 
         Self {
             histogram,
             ssim_sample: downsampled,
+            ssim_sample_size,
+            tiles,
+        }
+    }
+}
+
+/// Frame split into a grid of tiles, each downsampled to a fixed-size luma
+/// sample so small localized changes survive on large monitors.
+#[derive(Clone)]
+pub(crate) struct TileGrid {
+    width: u32,
+    height: u32,
+    cols: u32,
+    rows: u32,
+    sample: GrayImage,
+}
+
+impl TileGrid {
+    fn from_grayscale(image: &GrayImage) -> Self {
+        let (width, height) = image.dimensions();
+        let cols = width.div_ceil(TILE_SIZE).max(1);
+        let rows = height.div_ceil(TILE_SIZE).max(1);
+        let sample = imageops::resize(
+            image,
+            cols * TILE_SAMPLE_SIZE,
+            rows * TILE_SAMPLE_SIZE,
+            FilterType::Triangle,
+        );
+
+        Self {
+            width,
+            height,
+            cols,
+            rows,
+            sample,
+        }
+    }
+
+    pub(crate) fn from_image(image: &DynamicImage) -> Self {
+        Self::from_grayscale(&image.to_luma8())
+    }
+
+    /// Whether `other` has the same size and not a single changed tile.
+    pub(crate) fn matches(&self, other: &TileGrid, thresholds: &DetectionThresholds) -> bool {
+        self.same_layout(other) && !self.changed_tiles(other, thresholds).contains(&true)
+    }
+
+    fn same_layout(&self, other: &TileGrid) -> bool {
+        self.width == other.width && self.height == other.height
+    }
+
+    /// Score every tile against `previous` and return which ones changed,
+    /// in row-major order.
+    fn changed_tiles(&self, previous: &TileGrid, thresholds: &DetectionThresholds) -> Vec<bool> {
+        let samples_per_tile = (TILE_SAMPLE_SIZE * TILE_SAMPLE_SIZE) as f32;
+        let mut changed = Vec::with_capacity((self.cols * self.rows) as usize);
+
+        for row in 0..self.rows {
+            for col in 0..self.cols {
+                let mut differing = 0u32;
+                for dy in 0..TILE_SAMPLE_SIZE {
+                    for dx in 0..TILE_SAMPLE_SIZE {
+                        let x = col * TILE_SAMPLE_SIZE + dx;
+                        let y = row * TILE_SAMPLE_SIZE + dy;
+                        let current = self.sample.get_pixel(x, y)[0];
+                        let before = previous.sample.get_pixel(x, y)[0];
+                        if current.abs_diff(before) >= thresholds.tile_pixel_delta {
+                            differing += 1;
+                        }
+                    }
+                }
+                changed
+                    .push(differing as f32 / samples_per_tile >= thresholds.tile_change_fraction);
+            }
         }
+
+        changed
+    }
+
+    /// Source-pixel rectangle covered by the tile at (`col`, `row`).
+    fn tile_rect(&self, col: u32, row: u32) -> (u32, u32, u32, u32) {
+        let x0 = col * self.width / self.cols;
+        let x1 = (col + 1) * self.width / self.cols;
+        let y0 = row * self.height / self.rows;
+        let y1 = (row + 1) * self.height / self.rows;
+        (x0, y0, x1, y1)
+    }
+
+    /// Merge adjacent changed tiles into bounding rectangles.
+    fn changed_regions(&self, changed: &[bool]) -> Vec<ScreenRect> {
+        let cols = self.cols as usize;
+        let mut visited = vec![false; changed.len()];
+        let mut regions = Vec::new();
+
+        for start in 0..changed.len() {
+            if !changed[start] || visited[start] {
+                continue;
+            }
+
+            let (mut min_col, mut min_row) = (u32::MAX, u32::MAX);
+            let (mut max_col, mut max_row) = (0u32, 0u32);
+            let mut stack = vec![start];
+            visited[start] = true;
+
+            while let Some(idx) = stack.pop() {
+                let (col, row) = ((idx % cols) as u32, (idx / cols) as u32);
+                min_col = min_col.min(col);
+                min_row = min_row.min(row);
+                max_col = max_col.max(col);
+                max_row = max_row.max(row);
+
+                for (dc, dr) in [
+                    (-1i64, 0i64),
+                    (1, 0),
+                    (0, -1),
+                    (0, 1),
+                    (-1, -1),
+                    (1, 1),
+                    (-1, 1),
+                    (1, -1),
+                ] {
+                    let (nc, nr) = (col as i64 + dc, row as i64 + dr);
+                    if nc < 0 || nr < 0 || nc >= self.cols as i64 || nr >= self.rows as i64 {
+                        continue;
+                    }
+                    let neighbour = nr as usize * cols + nc as usize;
+                    if changed[neighbour] && !visited[neighbour] {
+                        visited[neighbour] = true;
+                        stack.push(neighbour);
+                    }
+                }
+            }
+
+            let (x0, y0, _, _) = self.tile_rect(min_col, min_row);
+            let (_, _, x1, y1) = self.tile_rect(max_col, max_row);
+            regions.push(ScreenRect {
+                x: x0 as i32,
+                y: y0 as i32,
+                width: x1 - x0,
+                height: y1 - y0,
+            });
+        }
+
+        regions
+    }
+
+    fn full_frame(&self) -> ScreenRect {
+        ScreenRect {
+            x: 0,
+            y: 0,
+            width: self.width,
+            height: self.height,
+        }
+    }
+}
+
+/// Shared, runtime-tunable detection config. Capture loops read it every
+/// frame, so updates apply from the next evaluated frame.
+#[derive(Debug, Clone, Default)]
+pub struct DetectionSettings {
+    inner: Arc<RwLock<DetectionConfig>>,
+}
+
+impl DetectionSettings {
+    pub fn new(config: DetectionConfig) -> Self {
+        Self {
+            inner: Arc::new(RwLock::new(config)),
+        }
+    }
+
+    pub fn snapshot(&self) -> DetectionConfig {
+        self.inner
+            .read()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .clone()
+    }
+
+    pub fn replace(&self, config: DetectionConfig) {
+        *self
+            .inner
+            .write()
+            .unwrap_or_else(|poisoned| poisoned.into_inner()) = config;
+    }
+
+    /// Thresholds for the profile matching `app_name`/`window_name`, or the defaults.
+    pub fn thresholds_for(&self, app_name: &str, window_name: &str) -> DetectionThresholds {
+        self.inner
+            .read()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .thresholds_for(app_name, window_name)
+    }
+
+    pub fn defaults(&self) -> DetectionThresholds {
+        self.inner
+            .read()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .defaults
     }
 }
 
@@ -36,6 +238,8 @@ pub enum ChangeDecision {
     Significant {
         histogram_delta: f32,
         ssim_score: f32,
+        /// Bounding rectangles of changed areas, in monitor pixel coordinates.
+        changed_regions: Vec<ScreenRect>,
     },
     Insignificant {
         histogram_delta: f32,
@@ -43,19 +247,42 @@ pub enum ChangeDecision {
     },
 }
 
+impl ChangeDecision {
+    /// Whether the frame should be stored.
+    pub fn is_significant(&self) -> bool {
+        matches!(self, Self::FirstFrame | Self::Significant { .. })
+    }
+}
+
 impl ChangeDetector {
     pub fn new() -> Self {
         Self::default()
     }
 
-    pub fn evaluate(&mut self, image: &DynamicImage) -> ChangeDecision {
-        let signature = FrameSignature::from_image(image);
+    pub fn evaluate(
+        &mut self,
+        image: &DynamicImage,
+        thresholds: &DetectionThresholds,
+    ) -> ChangeDecision {
+        let (signature, decision) = self.compare(image, thresholds);
+        self.previous = Some(signature);
+        decision
+    }
+
+    /// Decide like `evaluate` without making `image` the new baseline.
+    pub fn peek(&self, image: &DynamicImage, thresholds: &DetectionThresholds) -> ChangeDecision {
+        self.compare(image, thresholds).1
+    }
+
+    fn compare(
+        &self,
+        image: &DynamicImage,
+        thresholds: &DetectionThresholds,
+    ) -> (FrameSignature, ChangeDecision) {
+        let signature = FrameSignature::from_image(image, thresholds.ssim_sample_size);
 
         match &self.previous {
-            None => {
-                self.previous = Some(signature);
-                ChangeDecision::FirstFrame
-            }
+            None => (signature, ChangeDecision::FirstFrame),
             Some(previous_signature) => {
                 let histogram_delta =
                     histogram_distance(&signature.histogram, &previous_signature.histogram);
@@ -63,21 +290,56 @@ impl ChangeDetector {
                 let ssim_score =
                     compute_ssim(&signature.ssim_sample, &previous_signature.ssim_sample);
 
-                trace!(histogram_delta, ssim_score, "frame diff metrics computed");
+                // A resolution change invalidates the tile grid; treat the whole frame as changed.
+                let mut changed_regions = if signature.tiles.same_layout(&previous_signature.tiles)
+                {
+                    let changed = signature
+                        .tiles
+                        .changed_tiles(&previous_signature.tiles, thresholds);
+                    signature.tiles.changed_regions(&changed)
+                } else {
+                    vec![signature.tiles.full_frame()]
+                };
+
+                let changed_area: u64 = changed_regions
+                    .iter()
+                    .map(|rect| rect.width as u64 * rect.height as u64)
+                    .sum();
+                if changed_area < thresholds.min_changed_area {
+                    changed_regions.clear();
+                }
+
+                // Samples taken at different sizes (the profile switched) are not comparable.
+                let profile_switched =
+                    signature.ssim_sample_size != previous_signature.ssim_sample_size;
 
-                self.previous = Some(signature);
+                trace!(
+                    histogram_delta,
+                    ssim_score,
+                    regions = changed_regions.len(),
+                    "frame diff metrics computed"
+                );
 
-                if histogram_delta >= HISTOGRAM_THRESHOLD || ssim_score <= SSIM_THRESHOLD {
+                let globally_changed = profile_switched
+                    || histogram_delta >= thresholds.histogram_threshold
+                    || ssim_score <= thresholds.ssim_threshold;
+                let decision = if globally_changed || !changed_regions.is_empty() {
+                    // Global shifts (e.g. a theme switch) may not cross the per-tile threshold.
+                    if changed_regions.is_empty() {
+                        changed_regions.push(signature.tiles.full_frame());
+                    }
                     ChangeDecision::Significant {
                         histogram_delta,
                         ssim_score,
+                        changed_regions,
                     }
                 } else {
                     ChangeDecision::Insignificant {
                         histogram_delta,
                         ssim_score,
                     }
-                }
+                };
+                (signature, decision)
             }
         }
     }
@@ -155,16 +417,204 @@ fn build_histogram(image: &GrayImage) -> [u32; HISTOGRAM_BINS] {
     bins
 }
 
-fn downsample_for_ssim(image: &GrayImage) -> Vec<u8> {
-    if image.width() <= SSIM_SAMPLE_SIZE && image.height() <= SSIM_SAMPLE_SIZE {
+fn downsample_for_ssim(image: &GrayImage, sample_size: u32) -> Vec<u8> {
+    let sample_size = sample_size.max(1);
+    if image.width() <= sample_size && image.height() <= sample_size {
         return image.to_vec();
     }
 
     let resized = image::DynamicImage::ImageLuma8(image.clone()).resize(
-        SSIM_SAMPLE_SIZE,
-        SSIM_SAMPLE_SIZE,
+        sample_size,
+        sample_size,
         FilterType::Triangle,
     );
 
     resized.to_luma8().into_raw()
 }
+
+#[cfg(test)]
+mod tests {
+    use image::{Rgba, RgbaImage};
+
+    use super::*;
+
+    /// Thresholds that only look at tiles: the histogram and SSIM checks can
+    /// never fire and any changed area counts.
+    fn tile_thresholds() -> DetectionThresholds {
+        DetectionThresholds {
+            histogram_threshold: 2.0,
+            ssim_threshold: -2.0,
+            min_changed_area: 0,
+            tile_pixel_delta: 10,
+            tile_change_fraction: 0.5,
+            ..DetectionThresholds::default()
+        }
+    }
+
+    /// A mid-grey frame with each `(x, y, width, height, luma)` block painted in.
+    fn frame(width: u32, height: u32, blocks: &[(u32, u32, u32, u32, u8)]) -> DynamicImage {
+        let mut image = RgbaImage::from_pixel(width, height, Rgba([128, 128, 128, 255]));
+        for &(x0, y0, block_width, block_height, luma) in blocks {
+            for y in y0..y0 + block_height {
+                for x in x0..x0 + block_width {
+                    image.put_pixel(x, y, Rgba([luma, luma, luma, 255]));
+                }
+            }
+        }
+        DynamicImage::ImageRgba8(image)
+    }
+
+    fn rect(x: i32, y: i32, width: u32, height: u32) -> ScreenRect {
+        ScreenRect {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    /// Changed regions of `after` against `before`; empty when the frame is
+    /// not significant.
+    fn regions(
+        before: &DynamicImage,
+        after: &DynamicImage,
+        thresholds: &DetectionThresholds,
+    ) -> Vec<ScreenRect> {
+        let mut detector = ChangeDetector::new();
+        assert!(matches!(
+            detector.evaluate(before, thresholds),
+            ChangeDecision::FirstFrame
+        ));
+        match detector.evaluate(after, thresholds) {
+            ChangeDecision::Significant {
+                changed_regions, ..
+            } => changed_regions,
+            _ => Vec::new(),
+        }
+    }
+
+    #[test]
+    fn one_changed_tile_is_one_region() {
+        let before = frame(512, 256, &[]);
+        let after = frame(512, 256, &[(128, 0, 128, 128, 255)]);
+        assert_eq!(
+            regions(&before, &after, &tile_thresholds()),
+            [rect(128, 0, 128, 128)]
+        );
+    }
+
+    #[test]
+    fn touching_tiles_merge_and_separate_ones_do_not() {
+        let before = frame(512, 256, &[]);
+        // Diagonal neighbours count as touching.
+        let touching = frame(
+            512,
+            256,
+            &[(128, 0, 128, 128, 255), (256, 128, 128, 128, 0)],
+        );
+        assert_eq!(
+            regions(&before, &touching, &tile_thresholds()),
+            [rect(128, 0, 256, 256)]
+        );
+
+        let apart = frame(512, 256, &[(0, 0, 128, 128, 255), (384, 128, 128, 128, 0)]);
+        assert_eq!(
+            regions(&before, &apart, &tile_thresholds()),
+            [rect(0, 0, 128, 128), rect(384, 128, 128, 128)]
+        );
+    }
+
+    #[test]
+    fn changes_under_the_tile_thresholds_are_ignored() {
+        let before = frame(256, 128, &[]);
+
+        // About a quarter of the tile's samples change.
+        let corner = frame(256, 128, &[(160, 32, 64, 64, 255)]);
+        assert!(regions(&before, &corner, &tile_thresholds()).is_empty());
+        let sensitive = DetectionThresholds {
+            tile_change_fraction: 0.2,
+            ..tile_thresholds()
+        };
+        assert_eq!(
+            regions(&before, &corner, &sensitive),
+            [rect(128, 0, 128, 128)]
+        );
+
+        // The whole tile changes, but only slightly.
+        let faint = frame(256, 128, &[(128, 0, 128, 128, 133)]);
+        assert!(regions(&before, &faint, &tile_thresholds()).is_empty());
+        let sensitive = DetectionThresholds {
+            tile_pixel_delta: 4,
+            ..tile_thresholds()
+        };
+        assert_eq!(
+            regions(&before, &faint, &sensitive),
+            [rect(128, 0, 128, 128)]
+        );
+    }
+
+    #[test]
+    fn small_changed_areas_are_not_significant() {
+        let thresholds = DetectionThresholds {
+            min_changed_area: 128 * 128 + 1,
+            ..tile_thresholds()
+        };
+        let before = frame(512, 256, &[]);
+        let one_tile = frame(512, 256, &[(0, 0, 128, 128, 255)]);
+        let mut detector = ChangeDetector::new();
+        detector.evaluate(&before, &thresholds);
+        assert!(!detector.evaluate(&one_tile, &thresholds).is_significant());
+
+        let two_tiles = frame(512, 256, &[(0, 0, 256, 128, 255)]);
+        assert_eq!(
+            regions(&before, &two_tiles, &thresholds),
+            [rect(0, 0, 256, 128)]
+        );
+    }
+
+    #[test]
+    fn edge_tiles_cover_frames_that_are_not_multiples_of_the_tile_size() {
+        // 300x200 splits into 3x2 tiles of 100x100.
+        let before = frame(300, 200, &[]);
+        let corner = frame(300, 200, &[(200, 100, 100, 100, 255)]);
+        assert_eq!(
+            regions(&before, &corner, &tile_thresholds()),
+            [rect(200, 100, 100, 100)]
+        );
+
+        let everything = frame(300, 200, &[(0, 0, 300, 200, 255)]);
+        assert_eq!(
+            regions(&before, &everything, &tile_thresholds()),
+            [rect(0, 0, 300, 200)]
+        );
+    }
+
+    #[test]
+    fn peek_keeps_the_baseline() {
+        let thresholds = tile_thresholds();
+        let before = frame(256, 128, &[]);
+        let after = frame(256, 128, &[(0, 0, 128, 128, 255)]);
+        let mut detector = ChangeDetector::new();
+        detector.evaluate(&before, &thresholds);
+
+        assert!(detector.peek(&after, &thresholds).is_significant());
+        assert!(detector.peek(&after, &thresholds).is_significant());
+        assert!(!detector.evaluate(&before, &thresholds).is_significant());
+        assert!(detector.evaluate(&after, &thresholds).is_significant());
+        assert!(!detector.peek(&after, &thresholds).is_significant());
+    }
+
+    #[test]
+    fn resized_frames_change_everywhere() {
+        let before = frame(256, 128, &[]);
+        let after = frame(300, 200, &[]);
+        assert_eq!(
+            regions(&before, &after, &tile_thresholds()),
+            [rect(0, 0, 300, 200)]
+        );
+
+        let grid = TileGrid::from_image(&before);
+        assert!(grid.matches(&TileGrid::from_image(&before), &tile_thresholds()));
+        assert!(!grid.matches(&TileGrid::from_image(&after), &tile_thresholds()));
+    }
+}
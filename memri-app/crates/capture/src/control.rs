@@ -0,0 +1,99 @@
+use std::time::Duration;
+
+use anyhow::Result;
+use serde::Serialize;
+use tokio::sync::oneshot;
+use tokio::time::Instant;
+
+use crate::frame_source::MonitorInfo;
+
+/// Whether a capture loop is taking frames on its own schedule.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RunState {
+    Running,
+    Paused,
+    Snoozed,
+    /// Not paused manually, but outside the configured capture schedule.
+    OutsideSchedule,
+}
+
+/// Snapshot of a capture loop, returned by every `CaptureHandle` control.
+#[derive(Debug, Clone, Serialize)]
+pub struct CaptureStatus {
+    /// Monitors currently being captured.
+    pub monitor_ids: Vec<u32>,
+    /// Connected displays; empty for sources without live displays.
+    pub monitors: Vec<MonitorInfo>,
+    pub state: RunState,
+    /// When a snooze ends and capture resumes on its own.
+    pub snoozed_until_ms: Option<i64>,
+    /// A focused private window is holding capture (see the privacy settings).
+    pub privacy_paused: bool,
+    /// When the capture schedule next opens or closes, if one is configured.
+    pub next_schedule_change_ms: Option<i64>,
+    /// Capture policy pacing the loop (see `[policy]` in the config file).
+    pub policy: &'static str,
+    /// Delay the policy will wait before the next scheduled tick.
+    pub backoff_delay_ms: u64,
+    /// Timestamp of the last stored frame.
+    pub last_frame_ms: Option<i64>,
+    /// Number the next stored capture will get.
+    pub frame_number: u64,
+}
+
+pub(crate) enum Command {
+    Status,
+    Pause,
+    Resume,
+    Snooze(Duration),
+    CaptureNow { note: Option<String> },
+}
+
+pub(crate) struct ControlRequest {
+    pub(crate) command: Command,
+    pub(crate) reply: oneshot::Sender<Result<CaptureStatus>>,
+}
+
+/// Manual pause state of one loop.
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum PauseState {
+    Running,
+    Paused,
+    Snoozed { until: Instant, until_ms: i64 },
+}
+
+impl PauseState {
+    pub(crate) fn snoozed(duration: Duration, now_ms: i64) -> Self {
+        Self::Snoozed {
+            until: Instant::now() + duration,
+            until_ms: now_ms.saturating_add(duration.as_millis() as i64),
+        }
+    }
+
+    pub(crate) fn is_running(&self) -> bool {
+        matches!(self, Self::Running)
+    }
+
+    pub(crate) fn run_state(&self) -> RunState {
+        match self {
+            Self::Running => RunState::Running,
+            Self::Paused => RunState::Paused,
+            Self::Snoozed { .. } => RunState::Snoozed,
+        }
+    }
+
+    pub(crate) fn snooze_deadline(&self) -> Option<Instant> {
+        match self {
+            Self::Snoozed { until, .. } => Some(*until),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn snoozed_until_ms(&self) -> Option<i64> {
+        match self {
+            Self::Snoozed { until_ms, .. } => Some(*until_ms),
+            _ => None,
+        }
+    }
+}
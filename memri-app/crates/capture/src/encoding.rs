@@ -0,0 +1,232 @@
+use std::borrow::Cow;
+use std::io::Cursor;
+use std::path::Path;
+use std::str::FromStr;
+
+use anyhow::{anyhow, Context, Result};
+use image::codecs::jpeg::JpegEncoder;
+use image::imageops::FilterType;
+use image::{DynamicImage, ImageFormat, RgbImage, RgbaImage};
+use memri_config::{ImageConfig, ImageEncoding, ImageProfile};
+use memri_storage::{blob_path, content_hash, write_blob};
+use tracing::debug;
+
+/// AVIF encoder speed (1 slowest .. 10 fastest); capture favours speed.
+#[cfg(feature = "avif")]
+const AVIF_SPEED: u8 = 8;
+
+/// File format of stored window images.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum StorageFormat {
+    Webp,
+    Jpeg,
+    Png,
+    #[cfg(feature = "avif")]
+    Avif,
+}
+
+impl StorageFormat {
+    pub(crate) fn as_str(&self) -> &'static str {
+        match self {
+            Self::Webp => "webp",
+            Self::Jpeg => "jpeg",
+            Self::Png => "png",
+            #[cfg(feature = "avif")]
+            Self::Avif => "avif",
+        }
+    }
+
+    fn extension(&self) -> &'static str {
+        match self {
+            Self::Jpeg => "jpg",
+            other => other.as_str(),
+        }
+    }
+}
+
+impl FromStr for StorageFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self> {
+        match value.trim().to_lowercase().as_str() {
+            "webp" => Ok(Self::Webp),
+            "jpeg" | "jpg" => Ok(Self::Jpeg),
+            "png" => Ok(Self::Png),
+            #[cfg(feature = "avif")]
+            "avif" => Ok(Self::Avif),
+            #[cfg(not(feature = "avif"))]
+            "avif" => Err(anyhow!(
+                "image format \"avif\" requires building memri_capture with the `avif` feature"
+            )),
+            other => Err(anyhow!(
+                "unknown image format {other:?} (expected webp, jpeg, png or avif)"
+            )),
+        }
+    }
+}
+
+/// Parsed form of `ImageEncoding`.
+#[derive(Debug, Clone, Copy)]
+struct EncodeSettings {
+    format: StorageFormat,
+    quality: u8,
+    max_width: u32,
+    max_height: u32,
+}
+
+impl EncodeSettings {
+    fn from_config(encoding: &ImageEncoding) -> Result<Self> {
+        Ok(Self {
+            format: encoding.format.parse()?,
+            quality: encoding.quality.clamp(1, 100),
+            max_width: encoding.max_width,
+            max_height: encoding.max_height,
+        })
+    }
+}
+
+/// Picks and applies the encoding for each stored window image.
+#[derive(Debug)]
+pub(crate) struct ImageStore {
+    defaults: EncodeSettings,
+    /// Checked in order; the first match wins.
+    profiles: Vec<(ImageProfile, EncodeSettings)>,
+}
+
+/// A window image in the content-addressed store.
+#[derive(Debug, Clone)]
+pub(crate) struct StoredImage {
+    pub(crate) path: String,
+    /// SHA-256 of the encoded bytes, which also names the file.
+    pub(crate) hash: String,
+    pub(crate) format: StorageFormat,
+    pub(crate) bytes: u64,
+}
+
+impl ImageStore {
+    /// Fails when the global settings or any profile name an unknown or
+    /// unavailable format.
+    pub(crate) fn from_config(config: &ImageConfig) -> Result<Self> {
+        let defaults = EncodeSettings::from_config(&config.defaults)?;
+        let profiles = config
+            .profiles
+            .iter()
+            .map(|profile| {
+                EncodeSettings::from_config(&profile.encoding)
+                    .with_context(|| format!("image profile {:?}", profile.name))
+                    .map(|settings| (profile.clone(), settings))
+            })
+            .collect::<Result<_>>()?;
+        Ok(Self { defaults, profiles })
+    }
+
+    fn settings_for(&self, app_name: &str, window_name: &str) -> EncodeSettings {
+        self.profiles
+            .iter()
+            .find(|(profile, _)| profile.matches(app_name, window_name))
+            .map(|(_, settings)| *settings)
+            .unwrap_or(self.defaults)
+    }
+
+    /// Encode `image` with the settings for its app and store it under
+    /// `base_dir`, named by the hash of the encoded bytes; an identical image
+    /// stored earlier is reused. A failed lossy encode falls back to PNG.
+    pub(crate) fn save(
+        &self,
+        image: &DynamicImage,
+        app_name: &str,
+        window_name: &str,
+        base_dir: &Path,
+    ) -> Result<StoredImage> {
+        let settings = self.settings_for(app_name, window_name);
+        let image = downscale(image, settings.max_width, settings.max_height);
+
+        let (format, bytes) = match encode(&image, settings.format, settings.quality) {
+            Ok(bytes) => (settings.format, bytes),
+            Err(err) if settings.format != StorageFormat::Png => {
+                debug!(
+                    format = settings.format.as_str(),
+                    "image encode failed, storing PNG: {err}"
+                );
+                (
+                    StorageFormat::Png,
+                    encode(&image, StorageFormat::Png, settings.quality)?,
+                )
+            }
+            Err(err) => return Err(err),
+        };
+
+        let hash = content_hash(&bytes);
+        let path = blob_path(base_dir, &hash, format.extension());
+        write_blob(&path, &hash, &bytes)?;
+        Ok(StoredImage {
+            path: path.to_string_lossy().to_string(),
+            hash,
+            format,
+            bytes: bytes.len() as u64,
+        })
+    }
+}
+
+/// Read back an image written by `ImageStore::save`. AVIF can't be decoded.
+pub(crate) fn load_stored(path: &Path) -> Result<DynamicImage> {
+    let bytes = std::fs::read(path).with_context(|| format!("failed to read {path:?}"))?;
+    if path.extension().is_some_and(|ext| ext == "webp") {
+        let decoded = webp::Decoder::new(&bytes)
+            .decode()
+            .ok_or_else(|| anyhow!("WebP decode failed for {path:?}"))?;
+        let (width, height) = (decoded.width(), decoded.height());
+        let image = if decoded.is_alpha() {
+            RgbaImage::from_raw(width, height, decoded.to_vec()).map(DynamicImage::ImageRgba8)
+        } else {
+            RgbImage::from_raw(width, height, decoded.to_vec()).map(DynamicImage::ImageRgb8)
+        };
+        return image.ok_or_else(|| anyhow!("WebP image {path:?} has an unexpected size"));
+    }
+    Ok(image::load_from_memory(&bytes)?)
+}
+
+/// Shrink to fit within the limits, keeping the aspect ratio; zero means no limit.
+fn downscale(image: &DynamicImage, max_width: u32, max_height: u32) -> Cow<'_, DynamicImage> {
+    let limit = |max: u32| if max == 0 { u32::MAX } else { max };
+    let (max_width, max_height) = (limit(max_width), limit(max_height));
+    if image.width() <= max_width && image.height() <= max_height {
+        return Cow::Borrowed(image);
+    }
+    Cow::Owned(image.resize(max_width, max_height, FilterType::Triangle))
+}
+
+fn encode(image: &DynamicImage, format: StorageFormat, quality: u8) -> Result<Vec<u8>> {
+    let mut buffer = Vec::new();
+    match format {
+        StorageFormat::Webp => {
+            let rgba = image.to_rgba8();
+            let encoded = webp::Encoder::from_rgba(rgba.as_raw(), rgba.width(), rgba.height())
+                .encode_simple(false, f32::from(quality))
+                .map_err(|err| anyhow!("WebP encode failed: {err:?}"))?;
+            buffer.extend_from_slice(&encoded);
+        }
+        StorageFormat::Jpeg => {
+            // JPEG has no alpha channel.
+            let rgb = image.to_rgb8();
+            JpegEncoder::new_with_quality(&mut buffer, quality).encode_image(&rgb)?;
+        }
+        StorageFormat::Png => {
+            image.write_to(&mut Cursor::new(&mut buffer), ImageFormat::Png)?;
+        }
+        #[cfg(feature = "avif")]
+        StorageFormat::Avif => {
+            use image::codecs::avif::AvifEncoder;
+            use image::{ColorType, ImageEncoder};
+
+            let rgba = image.to_rgba8();
+            AvifEncoder::new_with_speed_quality(&mut buffer, AVIF_SPEED, quality).write_image(
+                rgba.as_raw(),
+                rgba.width(),
+                rgba.height(),
+                ColorType::Rgba8,
+            )?;
+        }
+    }
+    Ok(buffer)
+}
@@ -0,0 +1,38 @@
+use serde::Serialize;
+
+use crate::frame_source::MonitorInfo;
+
+/// Capture loop notifications for the embedding application, serialized with
+/// a `type` tag so they can be forwarded to `/events` as-is.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum CaptureEvent {
+    /// A private window took focus and capture is paused until it loses it.
+    PrivacyPaused {
+        app_name: String,
+        timestamp_ms: i64,
+    },
+    PrivacyResumed {
+        timestamp_ms: i64,
+    },
+    /// The capture schedule closed; `until_ms` is when it reopens.
+    SchedulePaused {
+        timestamp_ms: i64,
+        until_ms: Option<i64>,
+    },
+    /// The capture schedule opened; `until_ms` is when it closes again.
+    ScheduleResumed {
+        timestamp_ms: i64,
+        until_ms: Option<i64>,
+    },
+    /// A display was connected (or changed resolution).
+    MonitorAdded {
+        monitor: MonitorInfo,
+        timestamp_ms: i64,
+    },
+    /// A display was disconnected (or changed resolution).
+    MonitorRemoved {
+        monitor: MonitorInfo,
+        timestamp_ms: i64,
+    },
+}
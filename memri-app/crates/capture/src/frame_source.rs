@@ -0,0 +1,239 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use image::DynamicImage;
+use memri_storage::ScreenRect;
+use serde::Serialize;
+
+use crate::window_capture::{CapturedWindow, WindowFilters};
+
+/// A connected display as reported by the frame source.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct MonitorInfo {
+    pub monitor_id: u32,
+    pub name: String,
+    /// Position and resolution on the virtual screen.
+    pub bounds: ScreenRect,
+    pub is_primary: bool,
+}
+
+/// One monitor's image within a frame.
+#[derive(Debug)]
+pub struct MonitorFrame {
+    pub monitor_id: u32,
+    /// Display name; empty when the source does not know it.
+    pub name: String,
+    pub image: DynamicImage,
+    /// Position and size on the virtual screen; used to assign windows.
+    pub bounds: ScreenRect,
+}
+
+/// A monitor together with the windows shown on it.
+#[derive(Debug)]
+pub struct MonitorCapture {
+    pub monitor: MonitorFrame,
+    pub windows: Vec<CapturedWindow>,
+}
+
+/// The monitors captured in one tick plus every window visible at the time.
+#[derive(Debug)]
+pub struct RawCapture {
+    pub monitors: Vec<MonitorFrame>,
+    pub windows: Vec<CapturedWindow>,
+    /// Original capture time for recorded frames; live sources leave this unset
+    /// and the capture loop stamps the frame with the current time.
+    pub timestamp_ms: Option<i64>,
+}
+
+impl RawCapture {
+    /// Pair each monitor with the windows it shows. A window belongs to the
+    /// monitor it overlaps most; windows without known geometry, or entirely
+    /// off-screen, go to the first monitor.
+    pub fn split_by_monitor(self) -> Vec<MonitorCapture> {
+        let mut assigned: Vec<Vec<CapturedWindow>> =
+            self.monitors.iter().map(|_| Vec::new()).collect();
+        if assigned.is_empty() {
+            return Vec::new();
+        }
+
+        for window in self.windows {
+            let index = window
+                .bounds
+                .and_then(|bounds| {
+                    self.monitors
+                        .iter()
+                        .enumerate()
+                        .map(|(index, monitor)| (index, overlap_area(&bounds, &monitor.bounds)))
+                        .filter(|(_, area)| *area > 0)
+                        .max_by_key(|(_, area)| *area)
+                        .map(|(index, _)| index)
+                })
+                .unwrap_or(0);
+            assigned[index].push(window);
+        }
+
+        self.monitors
+            .into_iter()
+            .zip(assigned)
+            .map(|(monitor, windows)| MonitorCapture { monitor, windows })
+            .collect()
+    }
+}
+
+fn overlap_area(a: &ScreenRect, b: &ScreenRect) -> u64 {
+    let x0 = i64::from(a.x).max(i64::from(b.x));
+    let y0 = i64::from(a.y).max(i64::from(b.y));
+    let x1 = (i64::from(a.x) + i64::from(a.width)).min(i64::from(b.x) + i64::from(b.width));
+    let y1 = (i64::from(a.y) + i64::from(a.height)).min(i64::from(b.y) + i64::from(b.height));
+    if x1 <= x0 || y1 <= y0 {
+        return 0;
+    }
+    ((x1 - x0) * (y1 - y0)) as u64
+}
+
+/// Producer of raw frames consumed by the capture loop.
+///
+/// Live backends grab the screen on every call, while replay sources feed
+/// previously recorded frames through the same change detection, OCR and
+/// storage path.
+#[async_trait]
+pub trait FrameSource: Send + Sync {
+    /// Capture the next frame covering `monitor_ids`. Windows are enumerated
+    /// once for all monitors.
+    ///
+    /// Returns `Ok(None)` once the source is exhausted, which stops the loop.
+    async fn capture_frame(
+        &self,
+        monitor_ids: &[u32],
+        capture_unfocused_windows: bool,
+        window_filters: &WindowFilters,
+    ) -> Result<Option<RawCapture>>;
+
+    /// Currently connected displays, polled to follow hot-plugging. Sources
+    /// without live displays (e.g. replay) return `Ok(None)`.
+    async fn list_monitors(&self) -> Result<Option<Vec<MonitorInfo>>> {
+        Ok(None)
+    }
+
+    /// Sources that pace themselves (e.g. replay) are polled without the
+    /// loop's backoff delay in between.
+    fn self_paced(&self) -> bool {
+        false
+    }
+
+    fn name(&self) -> &'static str;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rect(x: i32, y: i32, width: u32, height: u32) -> ScreenRect {
+        ScreenRect {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    fn monitor(monitor_id: u32, bounds: ScreenRect) -> MonitorFrame {
+        MonitorFrame {
+            monitor_id,
+            name: String::new(),
+            image: DynamicImage::new_rgba8(1, 1),
+            bounds,
+        }
+    }
+
+    fn window(window_name: &str, bounds: Option<ScreenRect>) -> CapturedWindow {
+        CapturedWindow {
+            image: DynamicImage::new_rgba8(1, 1),
+            app_name: "App".to_string(),
+            window_name: window_name.to_string(),
+            process_id: 1,
+            executable_path: None,
+            is_focused: false,
+            bounds,
+            z_order: None,
+        }
+    }
+
+    /// Window names assigned to each monitor, in monitor order.
+    fn split(monitors: Vec<MonitorFrame>, windows: Vec<CapturedWindow>) -> Vec<Vec<String>> {
+        RawCapture {
+            monitors,
+            windows,
+            timestamp_ms: None,
+        }
+        .split_by_monitor()
+        .into_iter()
+        .map(|capture| {
+            capture
+                .windows
+                .into_iter()
+                .map(|window| window.window_name)
+                .collect()
+        })
+        .collect()
+    }
+
+    #[test]
+    fn windows_go_to_the_monitor_they_overlap_most() {
+        let monitors = || {
+            vec![
+                monitor(1, rect(0, 0, 1920, 1080)),
+                monitor(2, rect(1920, 0, 1280, 1024)),
+            ]
+        };
+        let windows = vec![
+            window("left", Some(rect(100, 100, 800, 600))),
+            window("right", Some(rect(2000, 100, 800, 600))),
+            // 420 px on the left monitor, 180 on the right.
+            window("mostly left", Some(rect(1500, 100, 600, 400))),
+            // 120 px on the left monitor, 480 on the right.
+            window("mostly right", Some(rect(1800, 100, 600, 400))),
+        ];
+        assert_eq!(
+            split(monitors(), windows),
+            [vec!["left", "mostly left"], vec!["right", "mostly right"]]
+        );
+    }
+
+    #[test]
+    fn windows_without_a_place_on_screen_go_to_the_first_monitor() {
+        let monitors = vec![
+            monitor(1, rect(0, 0, 1920, 1080)),
+            monitor(2, rect(1920, 0, 1920, 1080)),
+        ];
+        let windows = vec![
+            window("unknown", None),
+            window("off screen", Some(rect(10_000, 10_000, 400, 300))),
+            window("right", Some(rect(2000, 0, 400, 300))),
+        ];
+        assert_eq!(
+            split(monitors, windows),
+            [vec!["unknown", "off screen"], vec!["right"]]
+        );
+        assert!(split(Vec::new(), vec![window("lost", None)]).is_empty());
+    }
+
+    #[test]
+    fn monitors_left_of_and_below_the_origin() {
+        let monitors = vec![
+            monitor(1, rect(0, 0, 1920, 1080)),
+            monitor(2, rect(-1280, 200, 1280, 1024)),
+            monitor(3, rect(0, 1080, 1920, 1080)),
+        ];
+        let windows = vec![
+            window("left", Some(rect(-800, 300, 400, 300))),
+            // 300 px on the primary monitor, 100 on the one to its left.
+            window("straddling", Some(rect(-100, 300, 400, 300))),
+            // 100 rows on the primary monitor, 200 on the one below.
+            window("below", Some(rect(200, 980, 400, 300))),
+        ];
+        assert_eq!(
+            split(monitors, windows),
+            [vec!["straddling"], vec!["left"], vec!["below"]]
+        );
+    }
+}
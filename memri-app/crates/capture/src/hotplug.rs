@@ -0,0 +1,89 @@
+use crate::frame_source::MonitorInfo;
+
+/// Displays that appeared or disappeared between two polls.
+#[derive(Debug, Default)]
+pub(crate) struct MonitorChanges {
+    pub(crate) added: Vec<MonitorInfo>,
+    pub(crate) removed: Vec<MonitorInfo>,
+}
+
+impl MonitorChanges {
+    pub(crate) fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty()
+    }
+}
+
+/// Tracks connected displays and decides which of them are captured.
+pub(crate) struct MonitorWatcher {
+    configured: Vec<u32>,
+    capture_all: bool,
+    /// Displays seen on the last poll; `None` until the source has reported
+    /// any (sources without live displays never do).
+    connected: Option<Vec<MonitorInfo>>,
+}
+
+impl MonitorWatcher {
+    pub(crate) fn new(configured: Vec<u32>, capture_all: bool) -> Self {
+        Self {
+            configured,
+            capture_all,
+            connected: None,
+        }
+    }
+
+    /// Connected displays as of the last poll.
+    pub(crate) fn connected(&self) -> &[MonitorInfo] {
+        self.connected.as_deref().unwrap_or_default()
+    }
+
+    /// Record a fresh monitor list and return what changed. The first list is
+    /// the baseline and reports no changes. A display whose name or geometry
+    /// changed is reported as removed and added again.
+    pub(crate) fn update(&mut self, monitors: Vec<MonitorInfo>) -> MonitorChanges {
+        let Some(previous) = self.connected.replace(monitors) else {
+            return MonitorChanges::default();
+        };
+        let current = self.connected();
+
+        MonitorChanges {
+            added: current
+                .iter()
+                .filter(|monitor| !previous.contains(monitor))
+                .cloned()
+                .collect(),
+            removed: previous
+                .iter()
+                .filter(|monitor| !current.contains(monitor))
+                .cloned()
+                .collect(),
+        }
+    }
+
+    /// Monitors to capture: every connected one with `capture_all`, otherwise
+    /// the configured ids that are connected, falling back to the primary (or
+    /// first) display when none is. Before the first poll, the configured ids.
+    pub(crate) fn active(&self) -> Vec<u32> {
+        let Some(connected) = &self.connected else {
+            return self.configured.clone();
+        };
+        if self.capture_all {
+            return connected.iter().map(|monitor| monitor.monitor_id).collect();
+        }
+
+        let selected: Vec<u32> = self
+            .configured
+            .iter()
+            .copied()
+            .filter(|id| connected.iter().any(|monitor| monitor.monitor_id == *id))
+            .collect();
+        if !selected.is_empty() {
+            return selected;
+        }
+        connected
+            .iter()
+            .find(|monitor| monitor.is_primary)
+            .or_else(|| connected.first())
+            .map(|monitor| vec![monitor.monitor_id])
+            .unwrap_or_default()
+    }
+}
@@ -3,34 +3,82 @@
 //! The goal is to encapsulate monitor/window capture, change detection, and
 //! dispatching work items downstream for OCR and storage.
 
+mod activity;
 mod change_detection;
+mod control;
+mod encoding;
+mod events;
+mod frame_source;
+mod hotplug;
 pub mod monitor;
+mod ocr_pool;
+mod phash;
 mod platform;
+mod policy;
+mod preprocess;
+mod privacy;
+mod redaction;
+mod replay;
+mod schedule;
+mod settle;
 mod window_capture;
+mod window_tracker;
+#[cfg(target_os = "linux")]
+mod x11;
 
-use std::cmp;
-use std::fs;
-use std::io::Cursor;
+use std::collections::HashMap;
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
-use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
-
-use anyhow::Result;
-use change_detection::{ChangeDecision, ChangeDetector};
-use image::{codecs::webp::WebPEncoder, ColorType, DynamicImage, ImageFormat};
-use memri_config::AppConfig;
-use memri_ocr::{OcrContext, OcrEngine};
-use memri_storage::{CaptureBatch, CaptureSink, CapturedWindowRecord};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use activity::{ActivityTracker, FocusSample};
+use anyhow::{anyhow, bail, Result};
+use change_detection::{ChangeDecision, ChangeDetector, TileGrid};
+use chrono::Local;
+use control::{Command, ControlRequest, PauseState};
+use hotplug::MonitorWatcher;
+use encoding::ImageStore;
+use memri_config::{
+    AppConfig, CapturePolicyConfig, ImageConfig, PreprocessConfig, PrivacyConfig, RedactionConfig,
+    ScheduleConfig, SettleConfig,
+};
+use memri_ocr::OcrEngine;
+use memri_storage::{
+    CaptureBatch, CaptureSink, CapturedWindowRecord, OcrStatus, ScreenRect, WindowOcrUpdate,
+};
+use ocr_pool::{OcrDone, OcrJob, OcrPool, OcrWorkerContext};
 use once_cell::sync::Lazy;
+use phash::{dhash, PerceptualIndex};
+use policy::CapturePolicy;
+use privacy::PrivacyPolicy;
 use regex::Regex;
-use tokio::sync::mpsc;
+use tokio::sync::{broadcast, mpsc, oneshot};
+use tokio::time::Instant;
 use tracing::{debug, info, instrument, warn};
-use window_capture::WindowFilters;
+use window_tracker::WindowTracker;
+
+pub use change_detection::DetectionSettings;
+pub use control::{CaptureStatus, RunState};
+pub use events::CaptureEvent;
+pub use frame_source::{FrameSource, MonitorCapture, MonitorFrame, MonitorInfo, RawCapture};
+pub use ocr_pool::{BackpressurePolicy, OcrMetrics};
+pub use platform::XcapFrameSource;
+pub use redaction::Redactor;
+pub use replay::{ReplayFrameSource, ReplayMode};
+pub use schedule::{Schedule, ScheduleSlot};
+pub use window_capture::{CapturedWindow, WindowFilters};
+#[cfg(target_os = "linux")]
+pub use x11::X11FrameSource;
 
 /// Configuration values for starting the capture service.
 #[derive(Debug, Clone)]
 pub struct CaptureConfig {
-    pub monitor_id: u32,
+    /// Monitors captured together on every tick, as long as they are connected.
+    pub monitor_ids: Vec<u32>,
+    /// Capture every connected monitor instead of `monitor_ids`.
+    pub capture_all_monitors: bool,
+    /// How often connected monitors are re-enumerated; zero disables hot-plug tracking.
+    pub monitor_poll_interval: Duration,
     pub interval: Duration,
     pub max_interval: Duration,
     pub capture_unfocused_windows: bool,
@@ -38,12 +86,35 @@ pub struct CaptureConfig {
     pub window_include: Vec<String>,
     pub window_ignore: Vec<String>,
     pub image_dir: PathBuf,
+    /// Change-detection thresholds; share one handle across loops to tune them at runtime.
+    pub detection: DetectionSettings,
+    /// Look-back for perceptual-hash dedupe; zero disables it.
+    pub dedupe_window: Duration,
+    pub dedupe_max_distance: u32,
+    pub redaction: RedactionConfig,
+    pub privacy: PrivacyConfig,
+    pub schedule: ScheduleConfig,
+    /// Concurrent OCR workers; windows are OCR'd after their capture is stored.
+    pub ocr_workers: usize,
+    pub ocr_queue_capacity: usize,
+    /// `drop_oldest`, `coalesce` or `block`; see `BackpressurePolicy`.
+    pub ocr_backpressure: String,
+    /// Format, quality and size limits of stored window images, per app.
+    pub images: ImageConfig,
+    /// Image clean-up before OCR, per app.
+    pub preprocess: PreprocessConfig,
+    /// Resampling after a significant change until the screen stops moving.
+    pub settle: SettleConfig,
+    /// Tick timing, forced keyframes and background window refresh.
+    pub policy: CapturePolicyConfig,
 }
 
 impl CaptureConfig {
-    pub fn from_app_config(app: &AppConfig, monitor_id: u32) -> Self {
+    pub fn from_app_config(app: &AppConfig, monitor_ids: Vec<u32>) -> Self {
         Self {
-            monitor_id,
+            monitor_ids,
+            capture_all_monitors: app.capture_all_monitors,
+            monitor_poll_interval: Duration::from_secs(app.monitor_poll_secs),
             interval: Duration::from_millis(app.capture_interval_ms),
             max_interval: Duration::from_millis(app.capture_max_interval_ms),
             capture_unfocused_windows: app.capture_unfocused_windows,
@@ -51,317 +122,1070 @@ impl CaptureConfig {
             window_include: app.window_include.clone(),
             window_ignore: app.window_ignore.clone(),
             image_dir: PathBuf::from(&app.image_dir),
+            detection: DetectionSettings::new(app.detection.clone()),
+            dedupe_window: Duration::from_secs(app.dedupe_window_minutes * 60),
+            dedupe_max_distance: app.dedupe_max_distance,
+            redaction: app.redaction.clone(),
+            privacy: app.privacy.clone(),
+            schedule: app.schedule.clone(),
+            ocr_workers: app.ocr.workers,
+            ocr_queue_capacity: app.ocr.queue_capacity,
+            ocr_backpressure: app.ocr.backpressure.clone(),
+            images: app.images.clone(),
+            preprocess: app.preprocess.clone(),
+            settle: app.settle.clone(),
+            policy: app.policy.clone(),
+        }
+    }
+}
+
+/// Build the frame source selected by `capture_source` in the app config.
+/// `platform` picks the native live backend for the current OS.
+pub fn frame_source_from_app_config(app: &AppConfig) -> Result<Arc<dyn FrameSource>> {
+    match app.capture_source.as_str() {
+        #[cfg(target_os = "linux")]
+        "platform" | "x11" => Ok(Arc::new(X11FrameSource::new())),
+        #[cfg(not(target_os = "linux"))]
+        "platform" => Ok(Arc::new(XcapFrameSource::new())),
+        "xcap" => Ok(Arc::new(XcapFrameSource::new())),
+        "replay" => {
+            let path = app
+                .replay_path
+                .as_deref()
+                .ok_or_else(|| anyhow!("capture_source = \"replay\" requires replay_path"))?;
+            let mode = app.replay_speed.parse::<ReplayMode>()?;
+            Ok(Arc::new(ReplayFrameSource::open(path, mode)?))
         }
+        other => Err(anyhow!("unknown capture source: {other}")),
     }
 }
 
-/// Public handle that allows external components to request a graceful shutdown.
+/// Upper bound between schedule checks, so clock changes and system suspend
+/// are noticed even when the next transition is days away.
+const SCHEDULE_RECHECK: Duration = Duration::from_secs(60);
+
+/// Public handle used by external components to control a running capture
+/// loop. Every control call replies with the loop's resulting status.
 #[derive(Clone)]
 pub struct CaptureHandle {
     shutdown_tx: mpsc::Sender<()>,
+    control_tx: mpsc::Sender<ControlRequest>,
+    ocr: OcrPool,
 }
 
 impl CaptureHandle {
+    pub async fn status(&self) -> Result<CaptureStatus> {
+        self.request(Command::Status).await
+    }
+
+    /// Stop taking scheduled frames until `resume` is called.
+    pub async fn pause(&self) -> Result<CaptureStatus> {
+        self.request(Command::Pause).await
+    }
+
+    /// Resume scheduled capture after a pause or snooze; the next frame is taken immediately.
+    pub async fn resume(&self) -> Result<CaptureStatus> {
+        self.request(Command::Resume).await
+    }
+
+    /// Pause for `duration`, then resume automatically.
+    pub async fn snooze(&self, duration: Duration) -> Result<CaptureStatus> {
+        self.request(Command::Snooze(duration)).await
+    }
+
+    /// Capture and store a frame right away, even if nothing changed or the
+    /// loop is paused. Private windows are still skipped.
+    pub async fn capture_now(&self, note: Option<String>) -> Result<CaptureStatus> {
+        self.request(Command::CaptureNow { note }).await
+    }
+
+    /// Queue depth, drop counts and latencies of the OCR workers.
+    pub fn ocr_metrics(&self) -> OcrMetrics {
+        self.ocr.metrics()
+    }
+
+    /// Stop the loop and wait until it has stored the OCR results in flight.
     pub async fn shutdown(self) {
         if let Err(err) = self.shutdown_tx.send(()).await {
             warn!("capture shutdown channel closed: {err}");
         }
+        self.control_tx.closed().await;
+    }
+
+    async fn request(&self, command: Command) -> Result<CaptureStatus> {
+        let (reply, response) = oneshot::channel();
+        self.control_tx
+            .send(ControlRequest { command, reply })
+            .await
+            .map_err(|_| anyhow!("capture loop is not running"))?;
+        response
+            .await
+            .map_err(|_| anyhow!("capture loop stopped"))?
     }
 }
 
-/// Start the asynchronous capture loop.
+/// Start the asynchronous capture loop covering every monitor in the config.
+/// Loop state changes are published on `events`; send errors (no subscribers)
+/// are ignored. Fails if no monitor is configured or the schedule, capture
+/// policy, OCR backpressure policy or image format is invalid.
 pub async fn start_capture(
     config: CaptureConfig,
+    source: Arc<dyn FrameSource>,
     ocr_engine: Arc<dyn OcrEngine>,
     sink: Arc<dyn CaptureSink>,
+    events: broadcast::Sender<CaptureEvent>,
 ) -> Result<CaptureHandle> {
-    let (shutdown_tx, mut shutdown_rx) = mpsc::channel(1);
-    let mut backoff = Backoff::new(config.interval, config.max_interval);
+    let (shutdown_tx, shutdown_rx) = mpsc::channel(1);
+    let (control_tx, control_rx) = mpsc::channel(8);
+    if config.monitor_ids.is_empty() {
+        bail!("no monitors to capture");
+    }
+    let schedule = Schedule::from_config(&config.schedule)?;
+    let capture_policy = policy::from_config(&config.policy, config.interval, config.max_interval)?;
+    let policy = config.ocr_backpressure.parse::<BackpressurePolicy>()?;
+    let (ocr, ocr_done) = OcrPool::start(
+        OcrWorkerContext {
+            engine: ocr_engine,
+            redactor: Arc::new(Redactor::new(&config.redaction)),
+            images: Arc::new(ImageStore::from_config(&config.images)?),
+            preprocess: config.preprocess.clone(),
+            languages: config.languages.clone(),
+            image_dir: config.image_dir.clone(),
+        },
+        config.ocr_workers,
+        config.ocr_queue_capacity,
+        policy,
+    );
+    let monitors = MonitorWatcher::new(config.monitor_ids.clone(), config.capture_all_monitors);
+
+    let capture_loop = CaptureLoop {
+        active_monitors: monitors.active(),
+        monitors,
+        watch_monitors: true,
+        state: LoopState::new(&config, capture_policy),
+        config,
+        source,
+        ocr: ocr.clone(),
+        ocr_done,
+        sink,
+        events,
+        pause: PauseState::Running,
+        privacy_paused: false,
+        schedule,
+        schedule_open: true,
+        last_frame_ms: None,
+    };
+    tokio::spawn(capture_loop.run(shutdown_rx, control_rx));
+
+    Ok(CaptureHandle {
+        shutdown_tx,
+        control_tx,
+        ocr,
+    })
+}
 
-    tokio::spawn(async move {
-        let mut frame_number: u64 = 0;
-        info!(monitor = config.monitor_id, "capture loop starting");
-        let mut change_detector = ChangeDetector::new();
+/// Why an iteration runs.
+#[derive(Debug)]
+enum Trigger {
+    Scheduled,
+    /// Requested through `CaptureHandle::capture_now`; bypasses change detection.
+    Manual {
+        note: Option<String>,
+    },
+    /// A scheduled tick the capture policy wants stored even without a change.
+    Keyframe,
+}
+
+/// Everything one capture loop owns between iterations.
+struct CaptureLoop {
+    config: CaptureConfig,
+    source: Arc<dyn FrameSource>,
+    ocr: OcrPool,
+    /// Results from the OCR workers, folded back into the reuse caches.
+    ocr_done: mpsc::UnboundedReceiver<OcrDone>,
+    sink: Arc<dyn CaptureSink>,
+    events: broadcast::Sender<CaptureEvent>,
+    state: LoopState,
+    monitors: MonitorWatcher,
+    /// Monitors captured on each tick; follows hot-plugging.
+    active_monitors: Vec<u32>,
+    /// Cleared when the source has no live displays to poll.
+    watch_monitors: bool,
+    pause: PauseState,
+    privacy_paused: bool,
+    schedule: Option<Schedule>,
+    schedule_open: bool,
+    last_frame_ms: Option<i64>,
+}
+
+impl CaptureLoop {
+    async fn run(
+        mut self,
+        mut shutdown_rx: mpsc::Receiver<()>,
+        mut control_rx: mpsc::Receiver<ControlRequest>,
+    ) {
+        info!(
+            monitors = ?self.config.monitor_ids,
+            source = self.source.name(),
+            "capture loop starting"
+        );
+        self.state.seed_frame_number(self.sink.as_ref()).await;
+        self.state.seed_perceptual_index(self.sink.as_ref()).await;
+        self.refresh_monitors().await;
+        self.update_schedule();
+
+        let mut next_tick = Instant::now() + self.delay();
+        let mut next_monitor_poll = Instant::now() + self.config.monitor_poll_interval;
         loop {
+            let snooze_deadline = self.pause.snooze_deadline();
+            let schedule_check = self.schedule_check_at();
             tokio::select! {
                 _ = shutdown_rx.recv() => {
                     info!("capture loop shutting down");
                     break;
                 }
-                _ = tokio::time::sleep(backoff.current_delay()) => {
-                    debug!(monitor = config.monitor_id, delay_ms = backoff.current_delay().as_millis(), "tick");
-                    match perform_iteration(&config, frame_number, &mut change_detector, ocr_engine.clone(), sink.clone()).await {
-                        Ok(outcome) => {
-                            backoff.record(&outcome.decision);
-                            if outcome.captured {
-                                frame_number = frame_number.saturating_add(1);
-                            }
-                        }
-                        Err(err) => {
-                            backoff.on_error();
-                            warn!("capture iteration failed: {err}");
-                        }
+                request = control_rx.recv() => {
+                    let Some(ControlRequest { command, reply }) = request else {
+                        break;
+                    };
+                    let (result, keep_running) = self.handle_command(command, &mut next_tick).await;
+                    let _ = reply.send(result);
+                    if !keep_running {
+                        break;
+                    }
+                }
+                Some(done) = self.ocr_done.recv() => {
+                    self.store_ocr(done).await;
+                }
+                _ = tokio::time::sleep_until(snooze_deadline.unwrap_or(next_tick)), if snooze_deadline.is_some() => {
+                    info!("snooze ended; capture resumed");
+                    self.pause = PauseState::Running;
+                    next_tick = Instant::now();
+                }
+                _ = tokio::time::sleep_until(schedule_check.unwrap_or(next_tick)), if schedule_check.is_some() => {
+                    if self.update_schedule() {
+                        next_tick = Instant::now();
                     }
                 }
+                _ = tokio::time::sleep_until(next_monitor_poll), if self.watch_monitors && !self.config.monitor_poll_interval.is_zero() => {
+                    if self.refresh_monitors().await {
+                        next_tick = Instant::now();
+                    }
+                    next_monitor_poll = Instant::now() + self.config.monitor_poll_interval;
+                }
+                _ = tokio::time::sleep_until(next_tick), if self.pause.is_running() && self.schedule_open && !self.active_monitors.is_empty() => {
+                    debug!(delay_ms = self.delay().as_millis(), "tick");
+                    if let Ok(false) = self.iterate(Trigger::Scheduled).await {
+                        break;
+                    }
+                    next_tick = Instant::now() + self.delay();
+                }
+            }
+        }
+
+        // Store whatever the workers finish before the loop goes away.
+        self.state.activity.close(self.sink.as_ref()).await;
+        self.ocr.close().await;
+        while let Ok(done) = self.ocr_done.try_recv() {
+            self.store_ocr(done).await;
+        }
+    }
+
+    /// Apply one control command. Returns the reply and whether the loop
+    /// should keep running.
+    async fn handle_command(
+        &mut self,
+        command: Command,
+        next_tick: &mut Instant,
+    ) -> (Result<CaptureStatus>, bool) {
+        match command {
+            Command::Status => {}
+            Command::Pause => {
+                info!("capture paused");
+                self.pause = PauseState::Paused;
+                self.state.activity.close(self.sink.as_ref()).await;
+            }
+            Command::Resume => {
+                info!("capture resumed");
+                self.pause = PauseState::Running;
+                *next_tick = Instant::now();
+            }
+            Command::Snooze(duration) => {
+                info!(secs = duration.as_secs(), "capture snoozed");
+                self.pause = PauseState::snoozed(duration, current_time_ms());
+                self.state.activity.close(self.sink.as_ref()).await;
+            }
+            Command::CaptureNow { note } => {
+                info!("capture requested");
+                match self.iterate(Trigger::Manual { note }).await {
+                    Ok(true) => *next_tick = Instant::now() + self.delay(),
+                    Ok(false) => return (Err(anyhow!("frame source exhausted")), false),
+                    Err(err) => return (Err(err), true),
+                }
+            }
+        }
+        (Ok(self.status()), true)
+    }
+
+    /// Run one iteration and fold its outcome into the loop state. Returns
+    /// `Ok(false)` once the frame source has no more frames.
+    async fn iterate(&mut self, trigger: Trigger) -> Result<bool> {
+        let outcome = match perform_iteration(
+            &self.config,
+            self.source.as_ref(),
+            &self.active_monitors,
+            &mut self.state,
+            &self.ocr,
+            self.sink.as_ref(),
+            &trigger,
+        )
+        .await
+        {
+            Ok(Some(outcome)) => outcome,
+            Ok(None) => {
+                info!(
+                    source = self.source.name(),
+                    "frame source exhausted; capture loop stopping"
+                );
+                return Ok(false);
+            }
+            Err(err) => {
+                self.state.policy.on_error();
+                warn!("capture iteration failed: {err}");
+                return Err(err);
+            }
+        };
+
+        match (&outcome.privacy_paused_by, self.privacy_paused) {
+            (Some(app_name), false) => {
+                self.privacy_paused = true;
+                info!(app = app_name, "private window focused; capture paused");
+                let _ = self.events.send(CaptureEvent::PrivacyPaused {
+                    app_name: app_name.clone(),
+                    timestamp_ms: current_time_ms(),
+                });
+            }
+            (None, true) => {
+                self.privacy_paused = false;
+                info!("private window lost focus; capture resumed");
+                let _ = self.events.send(CaptureEvent::PrivacyResumed {
+                    timestamp_ms: current_time_ms(),
+                });
+            }
+            _ => {}
+        }
+        if let Some(decision) = &outcome.decision {
+            self.state.policy.record(decision, outcome.captured_at_ms);
+        }
+        if let Some(timestamp_ms) = outcome.captured_at_ms {
+            self.last_frame_ms = Some(timestamp_ms);
+        }
+        Ok(true)
+    }
+
+    /// Write a finished (or dropped) OCR job to its window row and to every
+    /// row that reused it, then update the reuse caches.
+    async fn store_ocr(&mut self, done: OcrDone) {
+        let record = &done.record;
+        let update = WindowOcrUpdate {
+            status: record.ocr_status,
+            text: record.text.clone(),
+            confidence: record.confidence,
+            ocr_json: record.ocr_json.clone(),
+            ocr_engine: record.ocr_engine.clone(),
+            image_path: record.image_path.clone(),
+            image_format: record.image_format.clone(),
+            image_bytes: record.image_bytes,
+            image_hash: record.image_hash.clone(),
+        };
+        if let Err(err) = self.sink.update_window_ocr(done.window_id, update).await {
+            warn!(
+                window_id = done.window_id,
+                "failed to store OCR result: {err}"
+            );
+        }
+        self.state.complete_ocr(done);
+    }
+
+    /// Poll connected displays, announce changes and update the monitors being
+    /// captured. Returns `true` when that set changed.
+    async fn refresh_monitors(&mut self) -> bool {
+        let monitors = match self.source.list_monitors().await {
+            Ok(Some(monitors)) => monitors,
+            Ok(None) => {
+                self.watch_monitors = false;
+                return false;
+            }
+            Err(err) => {
+                warn!("failed to list monitors: {err}");
+                return false;
+            }
+        };
+
+        let changes = self.monitors.update(monitors);
+        if !changes.is_empty() {
+            let timestamp_ms = current_time_ms();
+            for monitor in changes.removed {
+                info!(
+                    monitor = monitor.monitor_id,
+                    name = monitor.name,
+                    "monitor disconnected"
+                );
+                // A reconnected or resized display starts from a fresh baseline.
+                self.state.change_detectors.remove(&monitor.monitor_id);
+                let _ = self.events.send(CaptureEvent::MonitorRemoved {
+                    monitor,
+                    timestamp_ms,
+                });
+            }
+            for monitor in changes.added {
+                info!(
+                    monitor = monitor.monitor_id,
+                    name = monitor.name,
+                    width = monitor.bounds.width,
+                    height = monitor.bounds.height,
+                    "monitor connected"
+                );
+                let _ = self.events.send(CaptureEvent::MonitorAdded {
+                    monitor,
+                    timestamp_ms,
+                });
             }
         }
-    });
 
-    Ok(CaptureHandle { shutdown_tx })
+        let active = self.monitors.active();
+        if active == self.active_monitors {
+            return false;
+        }
+        info!(monitors = ?active, "captured monitors changed");
+        self.state
+            .change_detectors
+            .retain(|monitor_id, _| active.contains(monitor_id));
+        self.active_monitors = active;
+        true
+    }
+
+    /// Re-evaluate the schedule and announce transitions. Returns `true` when
+    /// the schedule has just opened.
+    fn update_schedule(&mut self) -> bool {
+        let Some(schedule) = &self.schedule else {
+            return false;
+        };
+        let now = Local::now();
+        let open = schedule.is_open_at(now);
+        if open == self.schedule_open {
+            return false;
+        }
+
+        self.schedule_open = open;
+        let timestamp_ms = now.timestamp_millis();
+        let until_ms = schedule.next_change(now).map(|at| at.timestamp_millis());
+        let event = if open {
+            info!("capture schedule opened; capture resumed");
+            CaptureEvent::ScheduleResumed {
+                timestamp_ms,
+                until_ms,
+            }
+        } else {
+            info!("outside capture schedule; capture paused");
+            CaptureEvent::SchedulePaused {
+                timestamp_ms,
+                until_ms,
+            }
+        };
+        let _ = self.events.send(event);
+        open
+    }
+
+    /// When to re-check the schedule: its next change, capped at `SCHEDULE_RECHECK`.
+    fn schedule_check_at(&self) -> Option<Instant> {
+        let schedule = self.schedule.as_ref()?;
+        let now = Local::now();
+        let until_change = schedule
+            .next_change(now)
+            .and_then(|at| (at - now).to_std().ok())
+            .unwrap_or(SCHEDULE_RECHECK);
+        Some(Instant::now() + until_change.min(SCHEDULE_RECHECK))
+    }
+
+    /// Wait before the next scheduled frame.
+    fn delay(&self) -> Duration {
+        if self.source.self_paced() {
+            Duration::ZERO
+        } else {
+            self.state.policy.next_delay()
+        }
+    }
+
+    fn status(&self) -> CaptureStatus {
+        let state = match self.pause.run_state() {
+            RunState::Running if !self.schedule_open => RunState::OutsideSchedule,
+            state => state,
+        };
+        CaptureStatus {
+            monitor_ids: self.active_monitors.clone(),
+            monitors: self.monitors.connected().to_vec(),
+            state,
+            snoozed_until_ms: self.pause.snoozed_until_ms(),
+            privacy_paused: self.privacy_paused,
+            next_schedule_change_ms: self
+                .schedule
+                .as_ref()
+                .and_then(|schedule| schedule.next_change(Local::now()))
+                .map(|at| at.timestamp_millis()),
+            policy: self.state.policy.name(),
+            backoff_delay_ms: self.state.policy.next_delay().as_millis() as u64,
+            last_frame_ms: self.last_frame_ms,
+            frame_number: self.state.next_frame_number,
+        }
+    }
 }
 
 #[derive(Debug)]
 struct IterationOutcome {
-    decision: ChangeDecision,
-    captured: bool,
+    /// `None` when the frame was not evaluated (capture paused for privacy).
+    decision: Option<ChangeDecision>,
+    /// Timestamp of the stored frame; `None` when no monitor was persisted.
+    captured_at_ms: Option<i64>,
+    /// App name of the focused private window that paused this iteration.
+    privacy_paused_by: Option<String>,
+}
+
+/// Comparison state owned by the capture loop.
+struct LoopState {
+    /// Each monitor is compared against its own previous frame.
+    change_detectors: HashMap<u32, ChangeDetector>,
+    /// Shared by all monitors so every stored capture gets a unique number.
+    next_frame_number: u64,
+    window_tracker: WindowTracker,
+    perceptual_index: PerceptualIndex,
+    privacy: PrivacyPolicy,
+    /// Focus sessions, fed from every tick whether or not its frame is stored.
+    activity: ActivityTracker,
+    /// Paces the loop and decides which unchanged frames are still stored.
+    policy: Box<dyn CapturePolicy>,
+}
+
+impl LoopState {
+    fn new(config: &CaptureConfig, policy: Box<dyn CapturePolicy>) -> Self {
+        Self {
+            change_detectors: HashMap::new(),
+            next_frame_number: 0,
+            window_tracker: WindowTracker::new(),
+            perceptual_index: PerceptualIndex::new(
+                config.dedupe_window.as_millis() as i64,
+                config.dedupe_max_distance,
+            ),
+            privacy: PrivacyPolicy::new(&config.privacy),
+            // A tick may legitimately come as late as the longest backoff.
+            activity: ActivityTracker::new(config.max_interval.as_millis() as i64 * 2),
+            policy,
+        }
+    }
+
+    /// Continue numbering after the last stored capture.
+    async fn seed_frame_number(&mut self, sink: &dyn CaptureSink) {
+        match sink.last_frame_number().await {
+            Ok(last) => self.next_frame_number = last.map_or(0, |frame| frame.saturating_add(1)),
+            Err(err) => warn!("failed to load last frame number: {err}"),
+        }
+    }
+
+    /// Load hashes persisted within the dedupe window so a restart does not
+    /// forget recently stored screens. Matches are confirmed tile by tile,
+    /// so each row's stored image is read back; rows whose image can't be
+    /// decoded are left out, and downscaled images never match.
+    async fn seed_perceptual_index(&mut self, sink: &dyn CaptureSink) {
+        if !self.perceptual_index.enabled() {
+            return;
+        }
+        let since_ms = current_time_ms() - self.perceptual_index.window_ms();
+        let rows = match sink.recent_window_hashes(since_ms).await {
+            Ok(rows) => rows,
+            Err(err) => {
+                warn!("failed to load recent window hashes: {err}");
+                return;
+            }
+        };
+        let seeded = tokio::task::spawn_blocking(move || {
+            rows.into_iter()
+                .filter_map(|row| {
+                    let path = row.record.image_path.clone()?;
+                    match encoding::load_stored(Path::new(&path)) {
+                        Ok(image) => Some((row, TileGrid::from_image(&image))),
+                        Err(err) => {
+                            debug!("skipping stored window {path} for dedupe: {err}");
+                            None
+                        }
+                    }
+                })
+                .collect::<Vec<_>>()
+        })
+        .await;
+        match seeded {
+            Ok(rows) => {
+                debug!(rows = rows.len(), "seeded perceptual hash index");
+                self.perceptual_index.seed(rows);
+            }
+            Err(err) => warn!("failed to seed the perceptual hash index: {err}"),
+        }
+    }
+
+    /// Finished OCR becomes the reference for later unchanged frames and
+    /// near-duplicates. Empty or dropped results are forgotten so the window
+    /// is OCR'd again next time.
+    fn complete_ocr(&mut self, done: OcrDone) {
+        let record = done.record;
+        if record.ocr_status != OcrStatus::Done
+            || record.image_path.is_none()
+            || record.text.is_empty()
+        {
+            self.window_tracker.forget(done.window_id);
+            self.perceptual_index.discard(done.window_id);
+            return;
+        }
+        if let (true, Some(phash)) = (self.perceptual_index.enabled(), record.phash) {
+            self.perceptual_index.insert(
+                phash as u64,
+                done.window_id,
+                record.clone(),
+                done.timestamp_ms,
+            );
+        }
+        self.window_tracker.complete(done.window_id, record);
+    }
 }
 
-#[instrument(skip(change_detector, ocr_engine, sink, config))]
+/// Capture one frame of every configured monitor from `source`, then run
+/// change detection and persistence per monitor and queue new windows for
+/// OCR. Returns `Ok(None)` once the source has no more frames.
+#[instrument(skip(source, monitor_ids, state, ocr, sink, config))]
 async fn perform_iteration(
     config: &CaptureConfig,
-    frame_number: u64,
-    change_detector: &mut ChangeDetector,
-    ocr_engine: Arc<dyn OcrEngine>,
-    sink: Arc<dyn CaptureSink>,
-) -> Result<IterationOutcome> {
-    // Placeholder for real monitor/window capture.
-    // In the full implementation this will:
-    // 1. Capture monitor + window thumbnails.
-    // 2. Detect if the frame changed meaningfully.
-    // 3. Dispatch OCR jobs and persist results.
-    debug!(monitor = config.monitor_id, "performing capture iteration");
+    source: &dyn FrameSource,
+    monitor_ids: &[u32],
+    state: &mut LoopState,
+    ocr: &OcrPool,
+    sink: &dyn CaptureSink,
+    trigger: &Trigger,
+) -> Result<Option<IterationOutcome>> {
+    debug!(monitors = ?monitor_ids, "performing capture iteration");
+    if monitor_ids.is_empty() {
+        bail!("no monitors connected");
+    }
 
     let window_filters = WindowFilters::new(&config.window_ignore, &config.window_include);
 
-    let raw_capture = match platform::capture_frame(
-        config.monitor_id,
-        config.capture_unfocused_windows,
-        &window_filters,
-    )
-    .await
+    let mut raw_capture = match source
+        .capture_frame(
+            monitor_ids,
+            config.capture_unfocused_windows,
+            &window_filters,
+        )
+        .await
     {
-        Ok(data) => data,
+        Ok(Some(data)) => data,
+        Ok(None) => return Ok(None),
         Err(err) => {
             warn!(
-                monitor = config.monitor_id,
+                monitors = ?monitor_ids,
                 "platform capture failed: {err}"
             );
             return Err(err);
         }
     };
 
-    let decision = change_detector.evaluate(&raw_capture.monitor_image);
+    // A change caught mid-animation is not stored; wait for the screen to
+    // settle and store that frame instead. Requested captures never wait.
+    let mut settle_skipped_frames = 0;
+    if config.settle.enabled && matches!(trigger, Trigger::Scheduled) {
+        let thresholds = raw_capture
+            .windows
+            .iter()
+            .find(|window| window.is_focused)
+            .map(|window| {
+                config
+                    .detection
+                    .thresholds_for(&window.app_name, &window.window_name)
+            })
+            .unwrap_or_else(|| config.detection.defaults());
+        let changed = raw_capture.monitors.iter().any(|monitor| {
+            state
+                .change_detectors
+                .get(&monitor.monitor_id)
+                .is_some_and(|detector| {
+                    matches!(
+                        detector.peek(&monitor.image, &thresholds),
+                        ChangeDecision::Significant { .. }
+                    )
+                })
+        });
+        if changed {
+            let settled = settle::settle(
+                &config.settle,
+                source,
+                monitor_ids,
+                config.capture_unfocused_windows,
+                &window_filters,
+                &thresholds,
+                raw_capture,
+            )
+            .await;
+            debug!(
+                skipped_frames = settled.skipped_frames,
+                "screen settled after change"
+            );
+            raw_capture = settled.capture;
+            settle_skipped_frames = settled.skipped_frames;
+        }
+    }
 
-    match decision {
+    if state.privacy.pauses_on_focus() {
+        if let Some(window) = raw_capture.windows.iter().find(|window| {
+            window.is_focused
+                && state
+                    .privacy
+                    .is_private(&window.app_name, &window.window_name)
+        }) {
+            debug!(
+                app = window.app_name,
+                "focused window is private; skipping frame"
+            );
+            let app_name = window.app_name.clone();
+            let timestamp_ms = raw_capture.timestamp_ms.unwrap_or_else(current_time_ms);
+            state.activity.observe(None, timestamp_ms, sink).await;
+            return Ok(Some(IterationOutcome {
+                decision: None,
+                captured_at_ms: None,
+                privacy_paused_by: Some(app_name),
+            }));
+        }
+    }
+
+    let before = raw_capture.windows.len();
+    raw_capture.windows.retain(|window| {
+        !state
+            .privacy
+            .is_private(&window.app_name, &window.window_name)
+    });
+    if raw_capture.windows.len() < before {
+        debug!(
+            skipped = before - raw_capture.windows.len(),
+            "skipping private windows"
+        );
+    }
+
+    let timestamp_ms = raw_capture.timestamp_ms.unwrap_or_else(current_time_ms);
+    let keyframe = Trigger::Keyframe;
+    let trigger =
+        if matches!(trigger, Trigger::Scheduled) && state.policy.keyframe_due(timestamp_ms) {
+            debug!(policy = state.policy.name(), "keyframe due");
+            &keyframe
+        } else {
+            trigger
+        };
+
+    // Backoff follows the most active monitor: any significant change resets it.
+    let captures = raw_capture.split_by_monitor();
+
+    // Usage accounting sees every tick, including ones change detection skips.
+    let focus = captures.iter().find_map(|capture| {
+        capture
+            .windows
+            .iter()
+            .find(|window| window.is_focused)
+            .map(|window| FocusSample {
+                app_name: window.app_name.clone(),
+                window_name: window.window_name.clone(),
+                monitor_id: capture.monitor.monitor_id,
+            })
+    });
+    state.activity.observe(focus, timestamp_ms, sink).await;
+
+    let mut decision: Option<ChangeDecision> = None;
+    let mut stored = false;
+    for capture in captures {
+        let (monitor_decision, monitor_stored) = capture_monitor(
+            config,
+            capture,
+            timestamp_ms,
+            settle_skipped_frames,
+            state,
+            ocr,
+            sink,
+            trigger,
+        )
+        .await?;
+        stored |= monitor_stored;
+        if decision
+            .as_ref()
+            .is_none_or(|current| !current.is_significant())
+        {
+            decision = Some(monitor_decision);
+        }
+    }
+    state.window_tracker.evict_stale(timestamp_ms);
+    state.perceptual_index.evict_stale(timestamp_ms);
+
+    Ok(Some(IterationOutcome {
+        decision,
+        captured_at_ms: stored.then_some(timestamp_ms),
+        privacy_paused_by: None,
+    }))
+}
+
+/// Change detection and persistence for one monitor and the windows assigned
+/// to it; windows that need OCR are queued once their rows exist. Returns the
+/// decision and whether a capture was stored.
+#[allow(clippy::too_many_arguments)]
+async fn capture_monitor(
+    config: &CaptureConfig,
+    capture: MonitorCapture,
+    timestamp_ms: i64,
+    settle_skipped_frames: u32,
+    state: &mut LoopState,
+    ocr: &OcrPool,
+    sink: &dyn CaptureSink,
+    trigger: &Trigger,
+) -> Result<(ChangeDecision, bool)> {
+    let MonitorCapture { monitor, windows } = capture;
+    let monitor_id = monitor.monitor_id;
+
+    // The focused window picks the detection profile for the monitor it is on.
+    let thresholds = windows
+        .iter()
+        .find(|window| window.is_focused)
+        .map(|window| {
+            config
+                .detection
+                .thresholds_for(&window.app_name, &window.window_name)
+        })
+        .unwrap_or_else(|| config.detection.defaults());
+    let decision = state
+        .change_detectors
+        .entry(monitor_id)
+        .or_default()
+        .evaluate(&monitor.image, &thresholds);
+
+    let full_frame = ScreenRect {
+        x: 0,
+        y: 0,
+        width: monitor.image.width(),
+        height: monitor.image.height(),
+    };
+    let note = match trigger {
+        Trigger::Manual { note } => note.clone(),
+        Trigger::Scheduled | Trigger::Keyframe => None,
+    };
+
+    let changed_regions = match &decision {
         ChangeDecision::FirstFrame => {
-            debug!(frame_number, "capturing baseline frame");
+            debug!(monitor = monitor_id, "capturing baseline frame");
+            vec![full_frame]
         }
         ChangeDecision::Significant {
             histogram_delta,
             ssim_score,
+            changed_regions,
         } => {
             debug!(
-                frame_number,
-                histogram_delta, ssim_score, "significant change detected"
+                monitor = monitor_id,
+                histogram_delta,
+                ssim_score,
+                regions = changed_regions.len(),
+                "significant change detected"
             );
+            changed_regions.clone()
+        }
+        ChangeDecision::Insignificant { .. } if !matches!(trigger, Trigger::Scheduled) => {
+            debug!(
+                monitor = monitor_id,
+                ?trigger,
+                "capturing forced frame without significant change"
+            );
+            vec![full_frame]
         }
         ChangeDecision::Insignificant {
             histogram_delta,
             ssim_score,
         } => {
             debug!(
-                frame_number,
+                monitor = monitor_id,
                 histogram_delta, ssim_score, "skipping frame without significant change"
             );
-            return Ok(IterationOutcome {
-                decision,
-                captured: false,
-            });
+            return Ok((decision, false));
         }
-    }
+    };
 
-    let timestamp_ms = SystemTime::now()
-        .duration_since(UNIX_EPOCH)
-        .map(|dur| dur.as_millis() as i64)
-        .unwrap_or_default();
+    let frame_number = state.next_frame_number;
+    state.next_frame_number = frame_number.saturating_add(1);
 
-    let ocr_start = Instant::now();
-    let windows = process_windows_for_ocr(
-        &raw_capture.windows,
-        &config.languages,
-        ocr_engine,
-        frame_number,
-        timestamp_ms,
-        &config.image_dir,
-    )
-    .await;
-    let ocr_elapsed = ocr_start.elapsed();
+    let records = prepare_window_records(config, &windows, state, timestamp_ms, monitor_id);
 
     let batch = CaptureBatch {
         frame_number,
         timestamp_ms,
-        windows,
+        monitor_id,
+        monitor_name: Some(monitor.name.clone()).filter(|name| !name.is_empty()),
+        monitor_width: monitor.bounds.width,
+        monitor_height: monitor.bounds.height,
+        changed_regions,
+        windows: records.clone(),
+        note,
+        settle_skipped_frames,
     };
 
     let persist_start = Instant::now();
-    sink.persist_batch(batch).await?;
+    let persisted = sink.persist_batch(batch).await?;
     let persist_elapsed = persist_start.elapsed();
 
+    // Pending windows are remembered right away so an unchanged window in the
+    // next frame points at the same row and receives its text with it.
+    let mut queued: usize = 0;
+    for ((window, record), window_id) in windows.into_iter().zip(records).zip(persisted.window_ids)
+    {
+        if record.reused_window_id.is_some() {
+            continue;
+        }
+        state
+            .window_tracker
+            .remember(&window, window_id, record.clone(), timestamp_ms);
+        if state.perceptual_index.enabled() {
+            state.perceptual_index.stage(window_id, &window);
+        }
+        ocr.submit(OcrJob::new(window_id, window, timestamp_ms, record)).await;
+        queued = queued.saturating_add(1);
+    }
+
     debug!(
         frame_number,
-        ocr_ms = ocr_elapsed.as_millis(),
+        monitor = monitor_id,
+        queued,
         persist_ms = persist_elapsed.as_millis(),
         "capture iteration completed"
     );
 
-    Ok(IterationOutcome {
-        decision,
-        captured: true,
-    })
-}
-
-fn encode_image_png(image: &DynamicImage) -> Result<Vec<u8>, image::ImageError> {
-    let mut buffer = Vec::new();
-    let mut cursor = Cursor::new(&mut buffer);
-    image.write_to(&mut cursor, ImageFormat::Png)?;
-    Ok(buffer)
-}
-
-fn encode_image_webp_lossy(image: &DynamicImage, quality: u8) -> Result<Vec<u8>, image::ImageError> {
-    let rgba = image.to_rgba8();
-    let (w, h) = rgba.dimensions();
-    let mut buffer = Vec::new();
-    {
-        let encoder = WebPEncoder::new_lossless(&mut buffer);
-        encoder.encode(&rgba, w, h, ColorType::Rgba8)?;
-    }
-    Ok(buffer)
+    Ok((decision, true))
 }
 
-#[instrument(skip(windows, ocr_engine, languages))]
-async fn process_windows_for_ocr(
-    windows: &[window_capture::CapturedWindow],
-    languages: &[String],
-    ocr_engine: Arc<dyn OcrEngine>,
-    frame_number: u64,
+/// Build the stored record for each window: the earlier result for unchanged
+/// windows and near-duplicates, otherwise a pending record that the OCR pool
+/// fills in after the capture is persisted. Geometry and process details
+/// always describe the window as it is now.
+fn prepare_window_records(
+    config: &CaptureConfig,
+    windows: &[CapturedWindow],
+    state: &mut LoopState,
     timestamp_ms: i64,
-    image_dir: &Path,
+    monitor_id: u32,
 ) -> Vec<CapturedWindowRecord> {
     let mut records = Vec::with_capacity(windows.len());
 
-    if let Err(err) = fs::create_dir_all(image_dir) {
-        warn!("failed to create image_dir {:?}: {err}", image_dir);
-    }
-
-    let mut idx: usize = 0;
     for window in windows {
-        let ocr_context = OcrContext {
-            window_name: window.window_name.clone(),
-            app_name: window.app_name.clone(),
-            is_focused: window.is_focused,
-            languages: languages.to_vec(),
-        };
-
-        let (png_bytes, image_path) =
-            match save_image_to_disk(&window.image, image_dir, frame_number, timestamp_ms, idx) {
-                Ok(val) => val,
-                Err(err) => {
-                    warn!(
-                        window = window.window_name,
-                        "failed to write window image: {err}"
-                    );
-                    records.push(CapturedWindowRecord {
-                        window_name: window.window_name.clone(),
-                        app_name: window.app_name.clone(),
-                        text: String::new(),
-                        confidence: None,
-                        browser_url: None,
-                        image_base64: None,
-                        ocr_json: None,
-                        image_path: None,
-                    });
-                    idx = idx.saturating_add(1);
-                    continue;
-                }
-            };
-        idx = idx.saturating_add(1);
+        let thresholds = config
+            .detection
+            .thresholds_for(&window.app_name, &window.window_name);
+        let hold_ms = state
+            .policy
+            .window_hold(window.is_focused)
+            .map(|hold| hold.as_millis() as i64);
+        if let Some((window_id, previous)) =
+            state
+                .window_tracker
+                .unchanged_record(window, timestamp_ms, &thresholds, hold_ms)
+        {
+            debug!(
+                window = window.window_name,
+                window_id, "window unchanged; reusing previous OCR and image"
+            );
+            records.push(with_window_state(
+                CapturedWindowRecord {
+                    reused_window_id: Some(window_id),
+                    ..previous
+                },
+                window,
+                monitor_id,
+            ));
+            continue;
+        }
 
-        let ocr_result = ocr_engine
-            .recognize(&png_bytes, &ocr_context)
-            .await
-            .map_err(|err| {
-                warn!(
-                    window = ocr_context.window_name,
-                    engine = ocr_engine.name(),
-                    "OCR failed: {err}"
+        let phash = dhash(&window.image);
+        if state.perceptual_index.enabled() {
+            if let Some((window_id, previous)) =
+                state
+                    .perceptual_index
+                    .find(window, phash, &thresholds, timestamp_ms)
+            {
+                debug!(
+                    window = window.window_name,
+                    window_id, "near-duplicate of an earlier window; storing pointer"
                 );
-                err
-            })
-            .ok();
-
-        let (text, confidence, ocr_json) = match ocr_result {
-            Some(payload) => (payload.text, payload.confidence, payload.json),
-            None => (String::new(), None, None),
-        };
+                records.push(with_window_state(
+                    CapturedWindowRecord {
+                        reused_window_id: Some(window_id),
+                        ..previous
+                    },
+                    window,
+                    monitor_id,
+                ));
+                continue;
+            }
+        }
 
-        records.push(CapturedWindowRecord {
+        let record = CapturedWindowRecord {
             window_name: window.window_name.clone(),
             app_name: window.app_name.clone(),
-            text,
-            confidence,
+            text: String::new(),
+            confidence: None,
             browser_url: extract_browser_url(
                 window.is_focused,
                 &window.app_name,
                 &window.window_name,
             ),
             image_base64: None,
-            ocr_json,
-            image_path: Some(image_path),
-        });
+            ocr_json: None,
+            ocr_engine: None,
+            image_path: None,
+            reused_window_id: None,
+            phash: Some(phash as i64),
+            ocr_status: OcrStatus::Pending,
+            image_format: None,
+            image_bytes: None,
+            image_hash: None,
+            process_id: None,
+            executable_path: None,
+            bounds: None,
+            z_order: None,
+            monitor_id: None,
+            is_focused: None,
+        };
+        records.push(with_window_state(record, window, monitor_id));
     }
 
     records
 }
 
-/// Simple exponential backoff used to throttle capture ticks after repeated
-/// insignificant frames or transient errors.
-#[derive(Debug, Clone)]
-struct Backoff {
-    base: Duration,
-    max: Duration,
-    current: Duration,
-}
-
-impl Backoff {
-    fn new(base: Duration, max: Duration) -> Self {
-        let clamped_max = cmp::max(max, base);
-        Self {
-            base,
-            max: clamped_max,
-            current: base,
-        }
-    }
-
-    fn current_delay(&self) -> Duration {
-        self.current
-    }
-
-    fn record(&mut self, decision: &ChangeDecision) {
-        match decision {
-            ChangeDecision::Significant { .. } | ChangeDecision::FirstFrame => {
-                self.current = self.base;
-            }
-            ChangeDecision::Insignificant { .. } => {
-                let next_ms = (self.current.as_millis() as f32 * 1.5).round() as u64;
-                self.current = Duration::from_millis(next_ms).min(self.max);
-            }
-        }
+fn with_window_state(
+    record: CapturedWindowRecord,
+    window: &CapturedWindow,
+    monitor_id: u32,
+) -> CapturedWindowRecord {
+    CapturedWindowRecord {
+        process_id: Some(window.process_id).filter(|pid| *pid > 0),
+        executable_path: window.executable_path.clone(),
+        bounds: window.bounds,
+        z_order: window.z_order,
+        monitor_id: Some(monitor_id),
+        is_focused: Some(window.is_focused),
+        ..record
     }
+}
 
-    fn on_error(&mut self) {
-        let next = self.current + self.base;
-        self.current = cmp::min(next, self.max);
-    }
+fn current_time_ms() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|dur| dur.as_millis() as i64)
+        .unwrap_or_default()
 }
 
 static URL_RE: Lazy<Regex> = Lazy::new(|| Regex::new(r"https?://\S+").unwrap());
@@ -398,24 +1222,3 @@ fn extract_browser_url(is_focused: bool, app_name: &str, window_title: &str) ->
         Some(url)
     }
 }
-
-fn save_image_to_disk(
-    image: &DynamicImage,
-    base_dir: &Path,
-    frame_number: u64,
-    timestamp_ms: i64,
-    idx: usize,
-) -> Result<(Vec<u8>, String), image::ImageError> {
-    let png_bytes = encode_image_png(image)?;
-
-    // Try to store a compressed WebP to save disk space; fall back to PNG on failure.
-    let (bytes_to_write, filename) = match encode_image_webp_lossy(image, 80) {
-        Ok(webp_bytes) => (webp_bytes, format!("frame_{}_{}_{}.webp", timestamp_ms, frame_number, idx)),
-        Err(_) => (png_bytes.clone(), format!("frame_{}_{}_{}.png", timestamp_ms, frame_number, idx)),
-    };
-
-    let path = base_dir.join(filename);
-    fs::write(&path, &bytes_to_write).map_err(image::ImageError::IoError)?;
-    let path_str = path.to_string_lossy().to_string();
-    Ok((png_bytes, path_str))
-}
@@ -1,10 +1,13 @@
 use anyhow::{anyhow, Context, Result};
 use tracing::warn;
 use image::{DynamicImage, ImageBuffer, Rgba};
+use memri_storage::ScreenRect;
 use std::sync::Arc;
 use tokio::task;
 use xcap::Monitor;
 
+use crate::frame_source::{MonitorFrame, MonitorInfo};
+
 #[derive(Clone)]
 #[allow(dead_code)]
 pub struct SafeMonitor {
@@ -15,6 +18,8 @@ pub struct SafeMonitor {
 #[derive(Clone)]
 #[allow(dead_code)]
 pub struct MonitorData {
+    pub x: i32,
+    pub y: i32,
     pub width: u32,
     pub height: u32,
     pub name: String,
@@ -26,12 +31,16 @@ impl SafeMonitor {
         let monitor_id = monitor.id().context("missing monitor id")?;
         let width = monitor.width().context("missing monitor width")?;
         let height = monitor.height().context("missing monitor height")?;
+        let x = monitor.x().unwrap_or_default();
+        let y = monitor.y().unwrap_or_default();
         let name = monitor.name().unwrap_or_default().to_string();
         let is_primary = monitor.is_primary().unwrap_or(false);
 
         Ok(Self {
             monitor_id,
             data: Arc::new(MonitorData {
+                x,
+                y,
                 width,
                 height,
                 name,
@@ -79,42 +88,35 @@ impl SafeMonitor {
         self.data.height
     }
 
-    #[allow(dead_code)]
+    /// Position and size on the virtual screen.
+    pub fn bounds(&self) -> ScreenRect {
+        ScreenRect {
+            x: self.data.x,
+            y: self.data.y,
+            width: self.data.width,
+            height: self.data.height,
+        }
+    }
+
     pub fn name(&self) -> &str {
         &self.data.name
     }
 
+    pub fn info(&self) -> MonitorInfo {
+        MonitorInfo {
+            monitor_id: self.monitor_id,
+            name: self.data.name.clone(),
+            bounds: self.bounds(),
+            is_primary: self.data.is_primary,
+        }
+    }
+
     #[allow(dead_code)]
     pub fn is_primary(&self) -> bool {
         self.data.is_primary
     }
 }
 
-#[allow(dead_code)]
-pub async fn get_monitor_by_id(id: u32) -> Result<SafeMonitor> {
-    task::spawn_blocking(move || -> Result<SafeMonitor> {
-        let mut monitors: Vec<Monitor> = Monitor::all().map_err(anyhow::Error::from)?.into_iter().collect();
-
-        // Try the requested id first.
-        if let Some(found) = monitors
-            .iter()
-            .find(|m| m.id().unwrap_or_default() == id)
-            .cloned()
-        {
-            return SafeMonitor::new(found);
-        }
-
-        // Fallback: use the first available monitor (often the primary).
-        if let Some(fallback) = monitors.pop() {
-            warn!("monitor {id} not found, falling back to primary monitor");
-            return SafeMonitor::new(fallback);
-        }
-
-        Err(anyhow!("no monitors detected"))
-    })
-    .await?
-}
-
 #[allow(dead_code)]
 pub async fn list_monitors() -> Result<Vec<SafeMonitor>> {
     task::spawn_blocking(|| -> Result<Vec<SafeMonitor>> {
@@ -126,3 +128,73 @@ pub async fn list_monitors() -> Result<Vec<SafeMonitor>> {
     })
     .await?
 }
+
+/// Connected monitors, for the capture loop's hot-plug watcher.
+pub(crate) async fn connected_monitors() -> Result<Vec<MonitorInfo>> {
+    Ok(list_monitors()
+        .await?
+        .iter()
+        .map(SafeMonitor::info)
+        .collect())
+}
+
+/// Resolve `ids` in order, skipping monitors that are not connected. Falls
+/// back to the primary (or first) monitor when none of them is available.
+pub async fn get_monitors_by_ids(ids: &[u32]) -> Result<Vec<SafeMonitor>> {
+    let available = list_monitors().await?;
+    let selected: Vec<SafeMonitor> = ids
+        .iter()
+        .filter_map(|id| {
+            let found = available.iter().find(|m| m.id() == *id).cloned();
+            if found.is_none() {
+                warn!("monitor {id} not found");
+            }
+            found
+        })
+        .collect();
+    if !selected.is_empty() {
+        return Ok(selected);
+    }
+
+    let fallback = available
+        .iter()
+        .find(|m| m.is_primary())
+        .or_else(|| available.first())
+        .cloned()
+        .ok_or_else(|| anyhow!("no monitors detected"))?;
+    warn!(
+        "none of monitors {ids:?} found, falling back to monitor {}",
+        fallback.id()
+    );
+    Ok(vec![fallback])
+}
+
+/// Capture one frame per monitor in `ids`. A monitor that fails to capture is
+/// skipped unless every monitor fails.
+pub(crate) async fn capture_monitor_frames(ids: &[u32]) -> Result<Vec<MonitorFrame>> {
+    let monitors = get_monitors_by_ids(ids).await?;
+    let mut frames = Vec::with_capacity(monitors.len());
+    let mut last_error = None;
+    for monitor in monitors {
+        match monitor.capture_image().await {
+            Ok(image) => frames.push(MonitorFrame {
+                monitor_id: monitor.id(),
+                name: monitor.name().to_string(),
+                image,
+                bounds: monitor.bounds(),
+            }),
+            Err(err) => {
+                warn!(
+                    monitor_id = monitor.id(),
+                    "failed to capture monitor: {err}"
+                );
+                last_error = Some(err);
+            }
+        }
+    }
+
+    match (frames.is_empty(), last_error) {
+        (true, Some(err)) => Err(err),
+        _ => Ok(frames),
+    }
+}
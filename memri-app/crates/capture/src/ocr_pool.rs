@@ -0,0 +1,747 @@
+use std::borrow::Cow;
+use std::collections::VecDeque;
+use std::fs;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+use anyhow::{anyhow, Result};
+use memri_config::PreprocessConfig;
+use memri_ocr::{OcrContext, OcrEngine, OcrPayload, RgbaFrame};
+use memri_storage::{CapturedWindowRecord, OcrStatus};
+use serde::Serialize;
+use tokio::sync::{mpsc, Notify};
+use tokio::task::{self, JoinHandle};
+use tokio::time::Instant;
+use tracing::{debug, warn};
+
+use crate::encoding::ImageStore;
+use crate::preprocess;
+use crate::redaction::Redactor;
+use crate::window_capture::CapturedWindow;
+
+/// Weight of the newest sample in the latency moving averages.
+const LATENCY_SMOOTHING: f64 = 0.2;
+
+/// What `OcrPool::submit` does when the queue is full.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BackpressurePolicy {
+    /// Discard the oldest queued window to make room.
+    DropOldest,
+    /// Replace a queued job for the same window, whether or not the queue is
+    /// full; when there is none and the queue is full, discard the oldest one.
+    Coalesce,
+    /// Wait for a free slot, delaying the capture loop.
+    Block,
+}
+
+impl FromStr for BackpressurePolicy {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self> {
+        match value.trim().to_lowercase().replace('-', "_").as_str() {
+            "drop_oldest" => Ok(Self::DropOldest),
+            "coalesce" => Ok(Self::Coalesce),
+            "block" => Ok(Self::Block),
+            other => Err(anyhow!(
+                "unknown OCR backpressure policy {other:?} (expected drop_oldest, coalesce or block)"
+            )),
+        }
+    }
+}
+
+/// Snapshot of the OCR queue, served by the backend for monitoring.
+#[derive(Debug, Clone, Serialize)]
+pub struct OcrMetrics {
+    pub workers: usize,
+    pub capacity: usize,
+    pub policy: BackpressurePolicy,
+    pub queue_depth: usize,
+    pub in_flight: usize,
+    pub submitted: u64,
+    pub completed: u64,
+    pub failed: u64,
+    /// Jobs discarded by `drop_oldest` (or `coalesce` with a full queue).
+    pub dropped: u64,
+    /// Jobs replaced by a newer frame of the same window.
+    pub coalesced: u64,
+    /// Moving averages of time spent waiting in the queue and in OCR.
+    pub avg_queue_ms: f64,
+    pub avg_ocr_ms: f64,
+    pub max_queue_ms: u64,
+}
+
+/// A stored window waiting for OCR.
+pub(crate) struct OcrJob {
+    pub(crate) window_id: i64,
+    pub(crate) window: CapturedWindow,
+    pub(crate) timestamp_ms: i64,
+    pub(crate) record: CapturedWindowRecord,
+    enqueued_at: Instant,
+}
+
+impl OcrJob {
+    pub(crate) fn new(
+        window_id: i64,
+        window: CapturedWindow,
+        timestamp_ms: i64,
+        record: CapturedWindowRecord,
+    ) -> Self {
+        Self {
+            window_id,
+            window,
+            timestamp_ms,
+            record,
+            enqueued_at: Instant::now(),
+        }
+    }
+
+    fn same_window(&self, other: &OcrJob) -> bool {
+        self.window.app_name == other.window.app_name
+            && self.window.window_name == other.window.window_name
+            && self.window.process_id == other.window.process_id
+    }
+}
+
+/// Finished or discarded job, handed back to the capture loop which stores
+/// it. Writing from the loop keeps the update ordered after any row that
+/// reused the pending record.
+pub(crate) struct OcrDone {
+    pub(crate) window_id: i64,
+    pub(crate) timestamp_ms: i64,
+    /// Filled-in record; status `dropped` when the job never ran.
+    pub(crate) record: CapturedWindowRecord,
+}
+
+/// Everything a worker needs to recognize, redact and store one window.
+pub(crate) struct OcrWorkerContext {
+    pub(crate) engine: Arc<dyn OcrEngine>,
+    pub(crate) redactor: Arc<Redactor>,
+    pub(crate) images: Arc<ImageStore>,
+    pub(crate) preprocess: PreprocessConfig,
+    pub(crate) languages: Vec<String>,
+    pub(crate) image_dir: PathBuf,
+}
+
+#[derive(Default)]
+struct MetricsState {
+    in_flight: usize,
+    submitted: u64,
+    completed: u64,
+    failed: u64,
+    dropped: u64,
+    coalesced: u64,
+    avg_queue_ms: f64,
+    avg_ocr_ms: f64,
+    max_queue_ms: u64,
+}
+
+struct Shared {
+    context: OcrWorkerContext,
+    workers: usize,
+    capacity: usize,
+    policy: BackpressurePolicy,
+    queue: Mutex<VecDeque<OcrJob>>,
+    metrics: Mutex<MetricsState>,
+    job_ready: Notify,
+    space_ready: Notify,
+    closed: AtomicBool,
+    worker_tasks: Mutex<Vec<JoinHandle<()>>>,
+    done_tx: mpsc::UnboundedSender<OcrDone>,
+}
+
+/// Bounded queue of windows served by a fixed set of OCR workers. Results are
+/// reported on the channel returned by `start`.
+#[derive(Clone)]
+pub(crate) struct OcrPool {
+    shared: Arc<Shared>,
+}
+
+impl OcrPool {
+    pub(crate) fn start(
+        context: OcrWorkerContext,
+        workers: usize,
+        capacity: usize,
+        policy: BackpressurePolicy,
+    ) -> (Self, mpsc::UnboundedReceiver<OcrDone>) {
+        if let Err(err) = fs::create_dir_all(&context.image_dir) {
+            warn!("failed to create image_dir {:?}: {err}", context.image_dir);
+        }
+
+        let (done_tx, done_rx) = mpsc::unbounded_channel();
+        let shared = Arc::new(Shared {
+            context,
+            workers: workers.max(1),
+            capacity: capacity.max(1),
+            policy,
+            queue: Mutex::new(VecDeque::new()),
+            metrics: Mutex::new(MetricsState::default()),
+            job_ready: Notify::new(),
+            space_ready: Notify::new(),
+            closed: AtomicBool::new(false),
+            worker_tasks: Mutex::new(Vec::new()),
+            done_tx,
+        });
+        let tasks = (0..shared.workers)
+            .map(|_| tokio::spawn(run_worker(shared.clone())))
+            .collect();
+        *shared.worker_tasks.lock().unwrap() = tasks;
+        (Self { shared }, done_rx)
+    }
+
+    /// Queue a window for OCR, applying the backpressure policy when the
+    /// queue is full. Only `block` waits.
+    pub(crate) async fn submit(&self, job: OcrJob) {
+        let shared = &self.shared;
+        shared.metrics.lock().unwrap().submitted += 1;
+
+        let displaced = loop {
+            let full = {
+                let mut queue = shared.queue.lock().unwrap();
+                let coalesce_at = match shared.policy {
+                    BackpressurePolicy::Coalesce => {
+                        queue.iter().position(|queued| queued.same_window(&job))
+                    }
+                    _ => None,
+                };
+                if let Some(position) = coalesce_at {
+                    shared.metrics.lock().unwrap().coalesced += 1;
+                    break Some(std::mem::replace(&mut queue[position], job));
+                }
+                if queue.len() < shared.capacity {
+                    queue.push_back(job);
+                    shared.job_ready.notify_one();
+                    break None;
+                }
+                if shared.policy != BackpressurePolicy::Block {
+                    shared.metrics.lock().unwrap().dropped += 1;
+                    let oldest = queue.pop_front();
+                    queue.push_back(job);
+                    break oldest;
+                }
+                true
+            };
+            // `notify_one` keeps a permit, so a slot freed between the check
+            // above and this wait is not missed.
+            if full {
+                shared.space_ready.notified().await;
+            }
+        };
+
+        if let Some(displaced) = displaced {
+            debug!(
+                window_id = displaced.window_id,
+                policy = ?shared.policy,
+                "OCR job displaced before it ran"
+            );
+            shared.discard(displaced);
+        }
+    }
+
+    pub(crate) fn metrics(&self) -> OcrMetrics {
+        let shared = &self.shared;
+        let queue_depth = shared.queue.lock().unwrap().len();
+        let metrics = shared.metrics.lock().unwrap();
+        OcrMetrics {
+            workers: shared.workers,
+            capacity: shared.capacity,
+            policy: shared.policy,
+            queue_depth,
+            in_flight: metrics.in_flight,
+            submitted: metrics.submitted,
+            completed: metrics.completed,
+            failed: metrics.failed,
+            dropped: metrics.dropped,
+            coalesced: metrics.coalesced,
+            avg_queue_ms: metrics.avg_queue_ms,
+            avg_ocr_ms: metrics.avg_ocr_ms,
+            max_queue_ms: metrics.max_queue_ms,
+        }
+    }
+
+    /// Stop the workers after the windows they are OCR'ing. Jobs still queued
+    /// are reported as dropped.
+    pub(crate) async fn close(&self) {
+        let shared = &self.shared;
+        shared.closed.store(true, Ordering::SeqCst);
+        let queued: Vec<OcrJob> = shared.queue.lock().unwrap().drain(..).collect();
+        for job in queued {
+            shared.metrics.lock().unwrap().dropped += 1;
+            shared.discard(job);
+        }
+        shared.job_ready.notify_waiters();
+
+        let tasks = std::mem::take(&mut *shared.worker_tasks.lock().unwrap());
+        for task in tasks {
+            if let Err(err) = task.await {
+                warn!("OCR worker failed: {err}");
+            }
+        }
+    }
+}
+
+impl Shared {
+    fn next_job(&self) -> Option<OcrJob> {
+        let job = self.queue.lock().unwrap().pop_front()?;
+        self.metrics.lock().unwrap().in_flight += 1;
+        self.space_ready.notify_one();
+        Some(job)
+    }
+
+    /// Report a job whose OCR will never run, so its rows stop showing as
+    /// pending. No image is written: text redaction needs the OCR result, so
+    /// the window could not be masked.
+    fn discard(&self, job: OcrJob) {
+        let _ = self.done_tx.send(OcrDone {
+            window_id: job.window_id,
+            timestamp_ms: job.timestamp_ms,
+            record: CapturedWindowRecord {
+                ocr_status: OcrStatus::Dropped,
+                ..job.record
+            },
+        });
+    }
+}
+
+async fn run_worker(shared: Arc<Shared>) {
+    loop {
+        let job = loop {
+            // Register for wake-ups before checking, so a job queued or a
+            // close requested in between is not missed.
+            let notified = shared.job_ready.notified();
+            tokio::pin!(notified);
+            notified.as_mut().enable();
+            if let Some(job) = shared.next_job() {
+                break job;
+            }
+            if shared.closed.load(Ordering::SeqCst) {
+                return;
+            }
+            notified.await;
+        };
+
+        let queue_ms = job.enqueued_at.elapsed().as_millis() as u64;
+        let started = Instant::now();
+        let window_id = job.window_id;
+        let timestamp_ms = job.timestamp_ms;
+        let record = recognize_window(&shared.context, job).await;
+        let ocr_ms = started.elapsed().as_millis() as u64;
+
+        {
+            let mut metrics = shared.metrics.lock().unwrap();
+            metrics.in_flight = metrics.in_flight.saturating_sub(1);
+            if record.ocr_status == OcrStatus::Done {
+                metrics.completed += 1;
+            } else {
+                metrics.failed += 1;
+            }
+            let samples = metrics.completed + metrics.failed;
+            metrics.avg_queue_ms = smooth(metrics.avg_queue_ms, queue_ms, samples);
+            metrics.avg_ocr_ms = smooth(metrics.avg_ocr_ms, ocr_ms, samples);
+            metrics.max_queue_ms = metrics.max_queue_ms.max(queue_ms);
+        }
+        debug!(window_id, queue_ms, ocr_ms, "window OCR finished");
+
+        let _ = shared.done_tx.send(OcrDone {
+            window_id,
+            timestamp_ms,
+            record,
+        });
+    }
+}
+
+/// Exponential moving average; the first sample is taken as is.
+fn smooth(average: f64, sample_ms: u64, samples: u64) -> f64 {
+    if samples <= 1 {
+        sample_ms as f64
+    } else {
+        average + LATENCY_SMOOTHING * (sample_ms as f64 - average)
+    }
+}
+
+/// OCR one window from its raw pixels, then store it with `store_window`.
+/// OCR failures produce a record with status `failed` and, like dropped
+/// windows, no image; write failures produce one without an image.
+async fn recognize_window(context: &OcrWorkerContext, job: OcrJob) -> CapturedWindowRecord {
+    let OcrJob { window, mut record, .. } = job;
+
+    let ocr_context = OcrContext {
+        window_name: window.window_name.clone(),
+        app_name: window.app_name.clone(),
+        is_focused: window.is_focused,
+        languages: context.languages.clone(),
+    };
+    let steps = context
+        .preprocess
+        .steps_for(&window.app_name, &window.window_name)
+        .clone();
+    // The pixel steps are CPU-bound, so they run on a blocking thread.
+    let prepared = task::spawn_blocking(move || {
+        let (prepared, converted) = {
+            // Live sources already deliver RGBA8, so this is normally a borrow.
+            let rgba = match window.image.as_rgba8() {
+                Some(rgba) => Cow::Borrowed(rgba),
+                None => Cow::Owned(window.image.to_rgba8()),
+            };
+            let prepared = preprocess::prepare(&rgba, &steps);
+            // Keep a conversion the engine will need instead of redoing it.
+            let converted = match rgba {
+                Cow::Owned(rgba) if prepared.is_none() => Some(rgba),
+                _ => None,
+            };
+            (prepared, converted)
+        };
+        (window, prepared, converted)
+    })
+    .await;
+    let (window, prepared, converted) = match prepared {
+        Ok(prepared) => prepared,
+        Err(err) => {
+            warn!("window preprocessing task failed: {err}");
+            record.ocr_status = OcrStatus::Failed;
+            return record;
+        }
+    };
+
+    let ocr_result = match prepared {
+        Some(prepared) => {
+            preprocess::recognize(context.engine.as_ref(), prepared, &ocr_context).await
+        }
+        None => {
+            let rgba = converted
+                .as_ref()
+                .or_else(|| window.image.as_rgba8())
+                .expect("non-RGBA8 images are converted while preparing");
+            let frame = RgbaFrame {
+                width: rgba.width(),
+                height: rgba.height(),
+                pixels: rgba.as_raw(),
+            };
+            context.engine.recognize_rgba(frame, &ocr_context).await
+        }
+    };
+    let ocr_result = ocr_result
+        .map_err(|err| {
+            warn!(
+                window = ocr_context.window_name,
+                engine = context.engine.name(),
+                "OCR failed: {err}"
+            );
+            err
+        })
+        .ok();
+    drop(converted);
+
+    let Some(payload) = ocr_result else {
+        record.ocr_status = OcrStatus::Failed;
+        return record;
+    };
+    record.ocr_status = OcrStatus::Done;
+    record.confidence = payload.confidence;
+    // A composite engine reports which of its engines produced the text.
+    record.ocr_engine = Some(if payload.layout.engine.is_empty() {
+        context.engine.name().to_string()
+    } else {
+        payload.layout.engine.clone()
+    });
+    store_window(context, window, record, payload).await
+}
+
+/// Mask sensitive content and write the window's image on a blocking thread,
+/// filling in the redacted text and layout of `payload`.
+async fn store_window(
+    context: &OcrWorkerContext,
+    window: CapturedWindow,
+    mut record: CapturedWindowRecord,
+    payload: OcrPayload,
+) -> CapturedWindowRecord {
+    let OcrPayload { text, layout, .. } = payload;
+    let redactor = context.redactor.clone();
+    let images = context.images.clone();
+    let image_dir = context.image_dir.clone();
+    let stored = task::spawn_blocking(move || {
+        // Mask sensitive content before anything is written to disk or the database.
+        let redaction = redactor.apply(&window, &text, &layout);
+        if redaction.regions > 0 {
+            debug!(
+                window = window.window_name,
+                regions = redaction.regions,
+                "redacted sensitive regions"
+            );
+        }
+        let stored = images
+            .save(
+                &redaction.image,
+                &window.app_name,
+                &window.window_name,
+                &image_dir,
+            )
+            .map_err(|err| {
+                warn!(
+                    window = window.window_name,
+                    "failed to write window image: {err}"
+                );
+                err
+            })
+            .ok();
+        // Only the redacted layout is stored, so it never repeats scrubbed text.
+        let layout_json = serde_json::to_string(&redaction.layout).ok();
+        (redaction.text, layout_json, stored)
+    })
+    .await;
+
+    match stored {
+        Ok((text, layout_json, image)) => {
+            record.text = text;
+            record.ocr_json = layout_json;
+            if let Some(image) = image {
+                record.image_path = Some(image.path);
+                record.image_hash = Some(image.hash);
+                record.image_format = Some(image.format.as_str().to_string());
+                record.image_bytes = Some(image.bytes as i64);
+            }
+        }
+        Err(err) => {
+            warn!("window image task failed: {err}");
+            record.ocr_status = OcrStatus::Failed;
+        }
+    }
+    record
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use async_trait::async_trait;
+    use image::{DynamicImage, RgbaImage};
+    use memri_config::{ImageConfig, RedactionConfig};
+    use memri_ocr::OcrLayout;
+    use tokio::sync::Semaphore;
+
+    use super::*;
+
+    /// Holds every recognition until the test hands out a permit, and fails
+    /// windows named `broken`.
+    struct GatedOcr {
+        gate: Semaphore,
+    }
+
+    #[async_trait]
+    impl OcrEngine for GatedOcr {
+        async fn recognize(
+            &self,
+            _image_bytes: &[u8],
+            _context: &OcrContext,
+        ) -> Result<OcrPayload> {
+            unreachable!("the pool hands windows over as pixels")
+        }
+
+        async fn recognize_rgba(
+            &self,
+            frame: RgbaFrame<'_>,
+            context: &OcrContext,
+        ) -> Result<OcrPayload> {
+            self.gate.acquire().await?.forget();
+            if context.window_name == "broken" {
+                return Err(anyhow!("unreadable window"));
+            }
+            Ok(OcrPayload {
+                text: context.window_name.clone(),
+                confidence: Some(0.9),
+                layout: OcrLayout {
+                    width: frame.width,
+                    height: frame.height,
+                    ..OcrLayout::default()
+                },
+            })
+        }
+
+        fn name(&self) -> &'static str {
+            "gated"
+        }
+    }
+
+    fn start_pool(
+        name: &str,
+        capacity: usize,
+        policy: BackpressurePolicy,
+    ) -> (Arc<GatedOcr>, OcrPool, mpsc::UnboundedReceiver<OcrDone>) {
+        let image_dir =
+            std::env::temp_dir().join(format!("memri-ocr-pool-{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&image_dir);
+        let engine = Arc::new(GatedOcr {
+            gate: Semaphore::new(0),
+        });
+        let context = OcrWorkerContext {
+            engine: engine.clone(),
+            redactor: Arc::new(Redactor::new(&RedactionConfig::default())),
+            images: Arc::new(ImageStore::from_config(&ImageConfig::default()).unwrap()),
+            preprocess: PreprocessConfig::default(),
+            languages: vec!["eng".to_string()],
+            image_dir,
+        };
+        let (pool, done) = OcrPool::start(context, 1, capacity, policy);
+        (engine, pool, done)
+    }
+
+    fn job(window_id: i64, window_name: &str) -> OcrJob {
+        let window = CapturedWindow {
+            image: DynamicImage::ImageRgba8(RgbaImage::new(16, 16)),
+            app_name: "App".to_string(),
+            window_name: window_name.to_string(),
+            process_id: 1,
+            executable_path: None,
+            is_focused: false,
+            bounds: None,
+            z_order: None,
+        };
+        let record = CapturedWindowRecord {
+            window_name: window_name.to_string(),
+            app_name: "App".to_string(),
+            text: String::new(),
+            confidence: None,
+            ocr_json: None,
+            ocr_engine: None,
+            image_base64: None,
+            image_path: None,
+            browser_url: None,
+            reused_window_id: None,
+            phash: None,
+            ocr_status: OcrStatus::Pending,
+            image_format: None,
+            image_bytes: None,
+            image_hash: None,
+            process_id: Some(1),
+            executable_path: None,
+            bounds: None,
+            z_order: None,
+            monitor_id: None,
+            is_focused: None,
+        };
+        OcrJob::new(window_id, window, 0, record)
+    }
+
+    /// Wait until the single worker has taken a job off the queue.
+    async fn wait_in_flight(pool: &OcrPool) {
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while pool.metrics().in_flight == 0 {
+                tokio::time::sleep(Duration::from_millis(5)).await;
+            }
+        })
+        .await
+        .expect("worker never picked up a job");
+    }
+
+    async fn next_done(done: &mut mpsc::UnboundedReceiver<OcrDone>) -> OcrDone {
+        tokio::time::timeout(Duration::from_secs(5), done.recv())
+            .await
+            .expect("no job finished")
+            .expect("pool closed")
+    }
+
+    #[tokio::test]
+    async fn drop_oldest_discards_the_front_of_a_full_queue() {
+        let (engine, pool, mut done) = start_pool("drop", 2, BackpressurePolicy::DropOldest);
+        pool.submit(job(1, "a")).await;
+        wait_in_flight(&pool).await;
+        for (window_id, name) in [(2, "b"), (3, "c"), (4, "d")] {
+            pool.submit(job(window_id, name)).await;
+        }
+
+        let dropped = next_done(&mut done).await;
+        assert_eq!(dropped.window_id, 2);
+        assert_eq!(dropped.record.ocr_status, OcrStatus::Dropped);
+        assert_eq!(dropped.record.image_path, None);
+        let metrics = pool.metrics();
+        assert_eq!((metrics.submitted, metrics.dropped), (4, 1));
+        assert_eq!(metrics.queue_depth, 2);
+
+        engine.gate.add_permits(3);
+        let mut finished = Vec::new();
+        for _ in 0..3 {
+            let job = next_done(&mut done).await;
+            assert_eq!(job.record.ocr_status, OcrStatus::Done);
+            assert!(job.record.image_path.is_some());
+            finished.push(job.window_id);
+        }
+        assert_eq!(finished, [1, 3, 4]);
+        assert_eq!(pool.metrics().completed, 3);
+        pool.close().await;
+    }
+
+    #[tokio::test]
+    async fn coalesce_replaces_a_queued_job_for_the_same_window() {
+        let (engine, pool, mut done) = start_pool("coalesce", 4, BackpressurePolicy::Coalesce);
+        pool.submit(job(1, "a")).await;
+        wait_in_flight(&pool).await;
+        for (window_id, name) in [(2, "x"), (3, "y"), (4, "x")] {
+            pool.submit(job(window_id, name)).await;
+        }
+
+        // The queue has room, yet the newer capture of `x` takes the older
+        // one's place instead of queueing behind it.
+        let replaced = next_done(&mut done).await;
+        assert_eq!(replaced.window_id, 2);
+        assert_eq!(replaced.record.ocr_status, OcrStatus::Dropped);
+        let metrics = pool.metrics();
+        assert_eq!((metrics.coalesced, metrics.dropped), (1, 0));
+        assert_eq!(metrics.queue_depth, 2);
+
+        engine.gate.add_permits(3);
+        let mut finished = Vec::new();
+        for _ in 0..3 {
+            finished.push(next_done(&mut done).await.window_id);
+        }
+        assert_eq!(finished, [1, 4, 3]);
+        pool.close().await;
+    }
+
+    #[tokio::test]
+    async fn block_waits_until_a_slot_frees() {
+        let (engine, pool, mut done) = start_pool("block", 1, BackpressurePolicy::Block);
+        let pool = Arc::new(pool);
+        pool.submit(job(1, "a")).await;
+        wait_in_flight(&pool).await;
+        pool.submit(job(2, "b")).await;
+
+        let blocked = tokio::spawn({
+            let pool = pool.clone();
+            async move { pool.submit(job(3, "c")).await }
+        });
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(!blocked.is_finished());
+
+        engine.gate.add_permits(1);
+        assert_eq!(next_done(&mut done).await.window_id, 1);
+        tokio::time::timeout(Duration::from_secs(5), blocked)
+            .await
+            .expect("submit stayed blocked after a slot freed")
+            .unwrap();
+
+        engine.gate.add_permits(2);
+        assert_eq!(next_done(&mut done).await.window_id, 2);
+        assert_eq!(next_done(&mut done).await.window_id, 3);
+        let metrics = pool.metrics();
+        assert_eq!(
+            (metrics.submitted, metrics.completed, metrics.dropped),
+            (3, 3, 0)
+        );
+        pool.close().await;
+    }
+
+    #[tokio::test]
+    async fn failed_ocr_stores_no_image() {
+        let (engine, pool, mut done) = start_pool("failed", 1, BackpressurePolicy::Block);
+        engine.gate.add_permits(1);
+        pool.submit(job(1, "broken")).await;
+
+        let failed = next_done(&mut done).await;
+        assert_eq!(failed.record.ocr_status, OcrStatus::Failed);
+        assert_eq!(failed.record.image_path, None);
+        assert_eq!(pool.metrics().failed, 1);
+        pool.close().await;
+    }
+}
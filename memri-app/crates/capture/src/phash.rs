@@ -0,0 +1,209 @@
+use std::collections::{HashMap, VecDeque};
+
+use image::{imageops::FilterType, DynamicImage};
+use memri_config::DetectionThresholds;
+use memri_storage::{CapturedWindowRecord, HashedWindow};
+use tracing::trace;
+
+use crate::change_detection::TileGrid;
+use crate::window_capture::CapturedWindow;
+
+const DHASH_WIDTH: u32 = 9; // one extra column so each row yields 8 gradient bits
+const DHASH_HEIGHT: u32 = 8;
+
+/// 64-bit difference hash: each bit records whether a pixel is brighter than
+/// its right neighbour in a 9x8 grayscale thumbnail.
+pub(crate) fn dhash(image: &DynamicImage) -> u64 {
+    let thumb = image
+        .resize_exact(DHASH_WIDTH, DHASH_HEIGHT, FilterType::Triangle)
+        .to_luma8();
+
+    let mut hash = 0u64;
+    for y in 0..DHASH_HEIGHT {
+        for x in 0..DHASH_WIDTH - 1 {
+            let left = thumb.get_pixel(x, y)[0];
+            let right = thumb.get_pixel(x + 1, y)[0];
+            hash = (hash << 1) | u64::from(left > right);
+        }
+    }
+    hash
+}
+
+/// Entries kept at most; the oldest go first. Each holds a tile sample of a
+/// few tens of kilobytes.
+const MAX_INDEX_ENTRIES: usize = 256;
+
+struct IndexEntry {
+    hash: u64,
+    window_id: i64,
+    record: CapturedWindowRecord,
+    tiles: TileGrid,
+    stored_ms: i64,
+}
+
+/// Rolling index of recently stored window hashes so screens revisited after
+/// switching away (alt-tab) point at the earlier capture instead of being
+/// stored and OCR'd again.
+pub(crate) struct PerceptualIndex {
+    entries: VecDeque<IndexEntry>,
+    /// Tile samples of stored windows whose OCR hasn't finished yet.
+    pending: HashMap<i64, TileGrid>,
+    window_ms: i64,
+    max_distance: u32,
+}
+
+impl PerceptualIndex {
+    pub(crate) fn new(window_ms: i64, max_distance: u32) -> Self {
+        Self {
+            entries: VecDeque::new(),
+            pending: HashMap::new(),
+            window_ms,
+            max_distance,
+        }
+    }
+
+    pub(crate) fn enabled(&self) -> bool {
+        self.window_ms > 0
+    }
+
+    pub(crate) fn window_ms(&self) -> i64 {
+        self.window_ms
+    }
+
+    /// Seed the index with rows persisted by an earlier run, each with the
+    /// tile sample of its stored image.
+    pub(crate) fn seed(&mut self, rows: Vec<(HashedWindow, TileGrid)>) {
+        for (row, tiles) in rows {
+            self.push(IndexEntry {
+                hash: row.phash as u64,
+                window_id: row.window_id,
+                record: row.record,
+                tiles,
+                stored_ms: row.timestamp_ms,
+            });
+        }
+    }
+
+    /// Find a stored window with the same app and title within the Hamming
+    /// distance limit whose tiles all match `window`. A 64-bit hash is too
+    /// coarse to tell text-heavy screens apart, so it only picks candidates;
+    /// a blinking cursor or one new line in a terminal fails the tile check.
+    pub(crate) fn find(
+        &self,
+        window: &CapturedWindow,
+        hash: u64,
+        thresholds: &DetectionThresholds,
+        now_ms: i64,
+    ) -> Option<(i64, CapturedWindowRecord)> {
+        let mut candidates: Vec<(u32, &IndexEntry)> = self
+            .entries
+            .iter()
+            .filter(|entry| {
+                entry.record.app_name == window.app_name
+                    && entry.record.window_name == window.window_name
+            })
+            .filter(|entry| now_ms - entry.stored_ms <= self.window_ms)
+            .map(|entry| ((entry.hash ^ hash).count_ones(), entry))
+            .filter(|(distance, _)| *distance <= self.max_distance)
+            .collect();
+        if candidates.is_empty() {
+            return None;
+        }
+        candidates.sort_by_key(|(distance, _)| *distance);
+
+        let tiles = TileGrid::from_image(&window.image);
+        let (distance, entry) = candidates
+            .into_iter()
+            .find(|(_, entry)| entry.tiles.matches(&tiles, thresholds))?;
+        trace!(distance, window_id = entry.window_id, "perceptual hash hit");
+        Some((entry.window_id, entry.record.clone()))
+    }
+
+    /// Keep the tile sample of a freshly stored window until its OCR is done.
+    pub(crate) fn stage(&mut self, window_id: i64, window: &CapturedWindow) {
+        self.pending
+            .insert(window_id, TileGrid::from_image(&window.image));
+    }
+
+    /// Add a window whose OCR finished to the index.
+    pub(crate) fn insert(
+        &mut self,
+        hash: u64,
+        window_id: i64,
+        record: CapturedWindowRecord,
+        timestamp_ms: i64,
+    ) {
+        if let Some(tiles) = self.pending.remove(&window_id) {
+            self.push(IndexEntry {
+                hash,
+                window_id,
+                record,
+                tiles,
+                stored_ms: timestamp_ms,
+            });
+        }
+    }
+
+    /// Drop the staged sample of a window that won't be indexed.
+    pub(crate) fn discard(&mut self, window_id: i64) {
+        self.pending.remove(&window_id);
+    }
+
+    fn push(&mut self, entry: IndexEntry) {
+        if self.entries.len() >= MAX_INDEX_ENTRIES {
+            self.entries.pop_front();
+        }
+        self.entries.push_back(entry);
+    }
+
+    pub(crate) fn evict_stale(&mut self, now_ms: i64) {
+        self.entries
+            .retain(|entry| now_ms - entry.stored_ms <= self.window_ms);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use image::{GrayImage, Luma};
+
+    use super::*;
+
+    /// Horizontal gradients in eight horizontal stripes, brightening to the
+    /// right in even stripes and darkening in odd ones.
+    fn stripes(width: u32, height: u32) -> DynamicImage {
+        DynamicImage::ImageLuma8(GrayImage::from_fn(width, height, |x, y| {
+            let value = (x * 255 / (width - 1)) as u8;
+            let brightening = (y * 8 / height).is_multiple_of(2);
+            Luma([if brightening { value } else { 255 - value }])
+        }))
+    }
+
+    #[test]
+    fn bits_record_where_brightness_falls_to_the_right() {
+        // One byte per thumbnail row, top row first.
+        assert_eq!(dhash(&stripes(90, 80)), 0x00ff_00ff_00ff_00ff);
+    }
+
+    #[test]
+    fn flat_images_hash_to_zero() {
+        let flat = DynamicImage::ImageLuma8(GrayImage::from_pixel(64, 64, Luma([128])));
+        assert_eq!(dhash(&flat), 0);
+    }
+
+    #[test]
+    fn hash_ignores_scale_and_colour() {
+        let small = dhash(&stripes(90, 80));
+        assert_eq!(dhash(&stripes(450, 400)), small);
+        assert_eq!(
+            dhash(&DynamicImage::ImageRgba8(stripes(90, 80).to_rgba8())),
+            small
+        );
+    }
+
+    #[test]
+    fn inverted_images_differ_in_every_bit() {
+        let mut inverted = stripes(90, 80);
+        inverted.invert();
+        assert_eq!(dhash(&inverted), !dhash(&stripes(90, 80)));
+    }
+}
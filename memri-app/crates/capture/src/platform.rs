@@ -1,54 +1,82 @@
 use anyhow::Result;
-use image::DynamicImage;
-use tracing::{debug, warn};
+use async_trait::async_trait;
+use tracing::debug;
+#[cfg(target_os = "windows")]
+use tracing::warn;
+
+use crate::frame_source::{FrameSource, MonitorInfo, RawCapture};
+#[cfg(target_os = "windows")]
+use crate::monitor::capture_monitor_frames;
+use crate::monitor::connected_monitors;
+#[cfg(target_os = "windows")]
+use crate::window_capture::capture_all_visible_windows;
+use crate::window_capture::WindowFilters;
+
+/// Live screen capture backed by `xcap`.
+#[derive(Debug, Default)]
+pub struct XcapFrameSource;
+
+impl XcapFrameSource {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+#[async_trait]
+impl FrameSource for XcapFrameSource {
+    async fn capture_frame(
+        &self,
+        monitor_ids: &[u32],
+        capture_unfocused_windows: bool,
+        window_filters: &WindowFilters,
+    ) -> Result<Option<RawCapture>> {
+        capture_frame(monitor_ids, capture_unfocused_windows, window_filters)
+            .await
+            .map(Some)
+    }
 
-use crate::monitor::get_monitor_by_id;
-use crate::window_capture::{capture_all_visible_windows, CapturedWindow, WindowFilters};
+    async fn list_monitors(&self) -> Result<Option<Vec<MonitorInfo>>> {
+        connected_monitors().await.map(Some)
+    }
 
-#[derive(Debug)]
-pub struct RawCapture {
-    pub monitor_image: DynamicImage,
-    pub windows: Vec<CapturedWindow>,
+    fn name(&self) -> &'static str {
+        "xcap"
+    }
 }
 
 #[cfg(target_os = "windows")]
-pub async fn capture_frame(
-    monitor_id: u32,
+async fn capture_frame(
+    monitor_ids: &[u32],
     capture_unfocused_windows: bool,
     window_filters: &WindowFilters,
 ) -> Result<RawCapture> {
-    debug!(monitor_id, "capturing frame via Windows APIs");
+    debug!(?monitor_ids, "capturing frame via Windows APIs");
 
-    let monitor = get_monitor_by_id(monitor_id).await?;
-    let monitor_image = monitor.capture_image().await?;
+    let monitors = capture_monitor_frames(monitor_ids).await?;
 
-    let windows = match capture_all_visible_windows(
-        &monitor,
-        window_filters,
-        capture_unfocused_windows,
-    )
-    .await
+    let windows = match capture_all_visible_windows(window_filters, capture_unfocused_windows).await
     {
         Ok(captured) => captured,
         Err(err) => {
-            warn!(monitor_id, "failed to capture window set: {err}");
+            warn!("failed to capture window set: {err}");
             Vec::new()
         }
     };
 
     Ok(RawCapture {
-        monitor_image,
+        monitors,
         windows,
+        timestamp_ms: None,
     })
 }
 
 #[cfg(not(target_os = "windows"))]
-pub async fn capture_frame(
-    monitor_id: u32,
+async fn capture_frame(
+    monitor_ids: &[u32],
     _capture_unfocused_windows: bool,
     _window_filters: &WindowFilters,
 ) -> Result<RawCapture> {
     use anyhow::anyhow;
-    debug!(monitor_id, "capture request on unsupported platform");
+    debug!(?monitor_ids, "capture request on unsupported platform");
     Err(anyhow!("platform capture not implemented for this OS"))
 }
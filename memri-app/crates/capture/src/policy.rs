@@ -0,0 +1,235 @@
+//! Capture timing policies: how long the loop waits between ticks, when a
+//! frame is stored without a change, and how often background windows are
+//! re-captured.
+
+use std::cmp;
+use std::time::{Duration, Instant};
+
+use anyhow::{anyhow, Result};
+use memri_config::CapturePolicyConfig;
+
+use crate::change_detection::ChangeDecision;
+
+/// Decides the pace of a capture loop from the outcome of its ticks.
+pub(crate) trait CapturePolicy: Send {
+    fn name(&self) -> &'static str;
+
+    /// Wait before the next scheduled tick.
+    fn next_delay(&self) -> Duration;
+
+    /// Fold in a tick's decision (the most significant across monitors) and
+    /// the timestamp of the frame it stored, if any.
+    fn record(&mut self, decision: &ChangeDecision, stored_at_ms: Option<i64>);
+
+    /// A tick failed.
+    fn on_error(&mut self);
+
+    /// Store the frame taken at `timestamp_ms` even if nothing changed.
+    fn keyframe_due(&self, _timestamp_ms: i64) -> bool {
+        false
+    }
+
+    /// How long a changed window keeps pointing at its last stored image and
+    /// text before it is captured again; `None` captures every change.
+    fn window_hold(&self, _is_focused: bool) -> Option<Duration> {
+        None
+    }
+}
+
+/// Build the policy named in the config: `exponential`, `burst`, `focused`
+/// or `keyframe`.
+pub(crate) fn from_config(
+    config: &CapturePolicyConfig,
+    base: Duration,
+    max: Duration,
+) -> Result<Box<dyn CapturePolicy>> {
+    let backoff = Backoff::new(base, max);
+    match config.name.trim().to_lowercase().replace('-', "_").as_str() {
+        "exponential" => Ok(Box::new(backoff)),
+        "burst" => Ok(Box::new(BurstPolicy {
+            backoff,
+            burst: Duration::from_millis(config.burst_interval_ms).min(base),
+            streak: 0,
+        })),
+        "focused" => Ok(Box::new(FocusedPolicy {
+            backoff,
+            background_hold: Duration::from_secs(config.background_interval_secs),
+        })),
+        "keyframe" => Ok(Box::new(KeyframePolicy {
+            backoff,
+            interval_ms: (config.keyframe_minutes.max(1) * 60_000) as i64,
+            last_stored_ms: None,
+            last_stored_at: None,
+        })),
+        other => Err(anyhow!(
+            "unknown capture policy {other:?} (expected exponential, burst, focused or keyframe)"
+        )),
+    }
+}
+
+/// Exponential backoff used to throttle capture ticks after repeated
+/// insignificant frames or transient errors.
+#[derive(Debug, Clone)]
+struct Backoff {
+    base: Duration,
+    max: Duration,
+    current: Duration,
+}
+
+impl Backoff {
+    fn new(base: Duration, max: Duration) -> Self {
+        let clamped_max = cmp::max(max, base);
+        Self {
+            base,
+            max: clamped_max,
+            current: base,
+        }
+    }
+}
+
+impl CapturePolicy for Backoff {
+    fn name(&self) -> &'static str {
+        "exponential"
+    }
+
+    fn next_delay(&self) -> Duration {
+        self.current
+    }
+
+    fn record(&mut self, decision: &ChangeDecision, _stored_at_ms: Option<i64>) {
+        match decision {
+            ChangeDecision::Significant { .. } | ChangeDecision::FirstFrame => {
+                self.current = self.base;
+            }
+            ChangeDecision::Insignificant { .. } => {
+                let next_ms = (self.current.as_millis() as f32 * 1.5).round() as u64;
+                self.current = Duration::from_millis(next_ms).min(self.max);
+            }
+        }
+    }
+
+    fn on_error(&mut self) {
+        let next = self.current + self.base;
+        self.current = cmp::min(next, self.max);
+    }
+}
+
+/// Backoff that speeds up while changes keep coming: every significant tick
+/// after the first halves the delay, down to the burst interval. A quiet tick
+/// returns to the base interval and backs off from there.
+struct BurstPolicy {
+    backoff: Backoff,
+    burst: Duration,
+    /// Significant ticks in a row.
+    streak: u32,
+}
+
+impl CapturePolicy for BurstPolicy {
+    fn name(&self) -> &'static str {
+        "burst"
+    }
+
+    fn next_delay(&self) -> Duration {
+        self.backoff.current
+    }
+
+    fn record(&mut self, decision: &ChangeDecision, stored_at_ms: Option<i64>) {
+        if !decision.is_significant() {
+            self.streak = 0;
+            if self.backoff.current < self.backoff.base {
+                self.backoff.current = self.backoff.base;
+            } else {
+                self.backoff.record(decision, stored_at_ms);
+            }
+            return;
+        }
+
+        self.streak = self.streak.saturating_add(1);
+        self.backoff.current = if self.streak == 1 {
+            self.backoff.base
+        } else {
+            cmp::max(self.backoff.current.min(self.backoff.base) / 2, self.burst)
+        };
+    }
+
+    fn on_error(&mut self) {
+        self.streak = 0;
+        self.backoff.on_error();
+    }
+}
+
+/// Backoff for the loop, but only the focused window is re-captured on every
+/// change; background windows keep their stored result for
+/// `background_hold` before a change is captured.
+struct FocusedPolicy {
+    backoff: Backoff,
+    background_hold: Duration,
+}
+
+impl CapturePolicy for FocusedPolicy {
+    fn name(&self) -> &'static str {
+        "focused"
+    }
+
+    fn next_delay(&self) -> Duration {
+        self.backoff.next_delay()
+    }
+
+    fn record(&mut self, decision: &ChangeDecision, stored_at_ms: Option<i64>) {
+        self.backoff.record(decision, stored_at_ms);
+    }
+
+    fn on_error(&mut self) {
+        self.backoff.on_error();
+    }
+
+    fn window_hold(&self, is_focused: bool) -> Option<Duration> {
+        (!is_focused).then_some(self.background_hold)
+    }
+}
+
+/// Backoff that still stores a frame at least every `interval_ms`, so a
+/// static screen leaves regular entries on the timeline.
+struct KeyframePolicy {
+    backoff: Backoff,
+    interval_ms: i64,
+    /// Frame timestamp of the last stored frame; replayed frames carry their
+    /// recorded time, so it is only compared with other frame timestamps.
+    last_stored_ms: Option<i64>,
+    /// When the last frame was stored, for pacing the loop.
+    last_stored_at: Option<Instant>,
+}
+
+impl CapturePolicy for KeyframePolicy {
+    fn name(&self) -> &'static str {
+        "keyframe"
+    }
+
+    fn next_delay(&self) -> Duration {
+        let delay = self.backoff.next_delay();
+        let Some(last_stored_at) = self.last_stored_at else {
+            return delay;
+        };
+        let until_keyframe =
+            Duration::from_millis(self.interval_ms as u64).saturating_sub(last_stored_at.elapsed());
+        // Never faster than the base interval, e.g. while privacy holds capture.
+        delay.min(cmp::max(until_keyframe, self.backoff.base))
+    }
+
+    fn record(&mut self, decision: &ChangeDecision, stored_at_ms: Option<i64>) {
+        self.backoff.record(decision, stored_at_ms);
+        if stored_at_ms.is_some() {
+            self.last_stored_ms = stored_at_ms;
+            self.last_stored_at = Some(Instant::now());
+        }
+    }
+
+    fn on_error(&mut self) {
+        self.backoff.on_error();
+    }
+
+    fn keyframe_due(&self, timestamp_ms: i64) -> bool {
+        self.last_stored_ms
+            .is_some_and(|last_stored_ms| timestamp_ms - last_stored_ms >= self.interval_ms)
+    }
+}
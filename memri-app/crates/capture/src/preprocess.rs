@@ -0,0 +1,518 @@
+//! Image clean-up before OCR: grayscale, dark-mode inversion, contrast
+//! stretching, upscaling and binarisation, then recognition in horizontal
+//! bands for very tall windows. Word boxes are mapped back to the pixels of
+//! the original window, so redaction and highlights line up with the stored
+//! image.
+
+use anyhow::Result;
+use image::imageops::{self, FilterType};
+use image::RgbaImage;
+use memri_config::PreprocessSteps;
+use memri_ocr::{
+    OcrBlock, OcrContext, OcrEngine, OcrLayout, OcrLine, OcrPayload, OcrRect, RgbaFrame,
+};
+
+/// Rows shared by neighbouring bands, so a line cut by one band is whole in
+/// the next.
+const TILE_OVERLAP: u32 = 64;
+/// Mean luminance (0-255) below which a window counts as dark.
+const DARK_LUMA: u32 = 110;
+/// Share of pixels allowed to clip at each end when stretching contrast.
+const CONTRAST_CLIP: f32 = 0.01;
+
+/// A window image after the pixel steps, ready to be recognized.
+pub(crate) struct Prepared {
+    image: RgbaImage,
+    original_width: u32,
+    original_height: u32,
+    tile_height: u32,
+    /// Names of the steps that ran, recorded in the layout.
+    steps: Vec<String>,
+}
+
+/// Apply the enabled steps to `image`; `None` when none of them would change
+/// anything, so the caller can hand the original pixels to the engine.
+pub(crate) fn prepare(image: &RgbaImage, steps: &PreprocessSteps) -> Option<Prepared> {
+    let (width, height) = image.dimensions();
+    let dark = steps.invert_dark && mean_luma(image) < DARK_LUMA;
+    let scaled = (steps.upscale > 1.0).then(|| {
+        (
+            (width as f32 * steps.upscale).round() as u32,
+            (height as f32 * steps.upscale).round() as u32,
+        )
+    });
+    let scaled_height = scaled.map_or(height, |(_, height)| height);
+    let tiled = steps.tile_height > 0 && scaled_height > steps.tile_height;
+    if !(steps.grayscale
+        || dark
+        || steps.normalize_contrast
+        || scaled.is_some()
+        || steps.binarize
+        || tiled)
+    {
+        return None;
+    }
+
+    let mut prepared = image.clone();
+    let mut applied = Vec::new();
+    if steps.grayscale {
+        grayscale(&mut prepared);
+        applied.push("grayscale".to_string());
+    }
+    if dark {
+        imageops::invert(&mut prepared);
+        applied.push("invert".to_string());
+    }
+    if steps.normalize_contrast && stretch_contrast(&mut prepared) {
+        applied.push("contrast".to_string());
+    }
+    if let Some((scaled_width, scaled_height)) = scaled {
+        prepared = imageops::resize(
+            &prepared,
+            scaled_width,
+            scaled_height,
+            FilterType::CatmullRom,
+        );
+        applied.push(format!("upscale:{}", steps.upscale));
+    }
+    if steps.binarize {
+        binarize(&mut prepared);
+        applied.push("binarize".to_string());
+    }
+
+    Some(Prepared {
+        image: prepared,
+        original_width: width,
+        original_height: height,
+        tile_height: steps.tile_height,
+        steps: applied,
+    })
+}
+
+/// Recognize a prepared image, band by band when it is taller than the tile
+/// height, and return one payload in the original window's coordinates.
+/// Any band failing fails the whole window rather than storing part of it.
+pub(crate) async fn recognize(
+    engine: &dyn OcrEngine,
+    prepared: Prepared,
+    context: &OcrContext,
+) -> Result<OcrPayload> {
+    let Prepared {
+        image,
+        original_width,
+        original_height,
+        tile_height,
+        mut steps,
+    } = prepared;
+    let bands = bands(image.height(), tile_height);
+    if bands.len() > 1 {
+        steps.push(format!("tiles:{}", bands.len()));
+    }
+
+    let mut results = Vec::with_capacity(bands.len());
+    for band in &bands {
+        let payload = if bands.len() == 1 {
+            engine.recognize_rgba(frame(&image), context).await?
+        } else {
+            let tile =
+                imageops::crop_imm(&image, 0, band.top, image.width(), band.height).to_image();
+            engine.recognize_rgba(frame(&tile), context).await?
+        };
+        results.push(payload);
+    }
+
+    let scale_x = image.width() as f32 / original_width.max(1) as f32;
+    let scale_y = image.height() as f32 / original_height.max(1) as f32;
+    let map = |rect: OcrRect, top: u32| OcrRect {
+        x: (rect.x as f32 / scale_x) as u32,
+        y: ((rect.y + top) as f32 / scale_y) as u32,
+        width: (rect.width as f32 / scale_x).ceil() as u32,
+        height: (rect.height as f32 / scale_y).ceil() as u32,
+    };
+
+    let mut blocks = Vec::new();
+    for (band, payload) in bands.iter().zip(&results) {
+        for block in &payload.layout.blocks {
+            let lines: Vec<OcrLine> = block
+                .lines
+                .iter()
+                .filter(|line| band.keeps(line.bounds.y + line.bounds.height / 2 + band.top))
+                .map(|line| {
+                    let mut line = line.clone();
+                    line.bounds = map(line.bounds, band.top);
+                    for word in &mut line.words {
+                        word.bounds = map(word.bounds, band.top);
+                    }
+                    line
+                })
+                .collect();
+            if !lines.is_empty() {
+                blocks.push(OcrBlock::new(lines));
+            }
+        }
+    }
+
+    let first = &results[0];
+    let layout = OcrLayout {
+        engine: first.layout.engine.clone(),
+        language: first.layout.language.clone(),
+        width: original_width,
+        height: original_height,
+        blocks,
+        preprocessing: steps,
+    };
+    let (text, confidence) = if results.len() == 1 {
+        (first.text.clone(), first.confidence)
+    } else if layout.blocks.is_empty() {
+        // Engines without word boxes can't be de-duplicated across bands.
+        let texts: Vec<&str> = results
+            .iter()
+            .map(|payload| payload.text.as_str())
+            .collect();
+        (texts.join("\n"), None)
+    } else {
+        let lines: Vec<&str> = layout.lines().map(|line| line.text.as_str()).collect();
+        (lines.join("\n"), layout.confidence())
+    };
+
+    Ok(OcrPayload {
+        text,
+        confidence,
+        layout,
+    })
+}
+
+/// A horizontal strip of the prepared image. Lines whose centre falls in
+/// `keep_from..keep_to` belong to this band; the rest of the overlap is left
+/// to its neighbour.
+struct Band {
+    top: u32,
+    height: u32,
+    keep_from: u32,
+    keep_to: u32,
+}
+
+impl Band {
+    fn keeps(&self, y: u32) -> bool {
+        (self.keep_from..self.keep_to).contains(&y)
+    }
+}
+
+fn bands(height: u32, tile_height: u32) -> Vec<Band> {
+    if tile_height == 0 || height <= tile_height {
+        return vec![Band {
+            top: 0,
+            height,
+            keep_from: 0,
+            keep_to: u32::MAX,
+        }];
+    }
+    let tile_height = tile_height.max(TILE_OVERLAP * 4);
+    let step = tile_height - TILE_OVERLAP;
+    let mut bands = Vec::new();
+    let mut top = 0;
+    loop {
+        let last = top + tile_height >= height;
+        bands.push(Band {
+            top,
+            height: if last { height - top } else { tile_height },
+            keep_from: if top == 0 { 0 } else { top + TILE_OVERLAP / 2 },
+            keep_to: if last {
+                u32::MAX
+            } else {
+                top + tile_height - TILE_OVERLAP / 2
+            },
+        });
+        if last {
+            return bands;
+        }
+        top += step;
+    }
+}
+
+fn frame(image: &RgbaImage) -> RgbaFrame<'_> {
+    RgbaFrame {
+        width: image.width(),
+        height: image.height(),
+        pixels: image.as_raw(),
+    }
+}
+
+fn luma(pixel: &[u8]) -> u8 {
+    ((299 * pixel[0] as u32 + 587 * pixel[1] as u32 + 114 * pixel[2] as u32) / 1000) as u8
+}
+
+/// Mean luminance over every fourth pixel, which is plenty to tell a dark
+/// theme from a light one.
+fn mean_luma(image: &RgbaImage) -> u32 {
+    let (sum, count) = image
+        .as_raw()
+        .chunks_exact(4)
+        .step_by(4)
+        .fold((0u64, 0u64), |(sum, count), pixel| {
+            (sum + luma(pixel) as u64, count + 1)
+        });
+    sum.checked_div(count).map_or(255, |mean| mean as u32)
+}
+
+fn luma_histogram(image: &RgbaImage) -> [u64; 256] {
+    let mut histogram = [0u64; 256];
+    for pixel in image.as_raw().chunks_exact(4) {
+        histogram[luma(pixel) as usize] += 1;
+    }
+    histogram
+}
+
+fn grayscale(image: &mut RgbaImage) {
+    for pixel in image.pixels_mut() {
+        let value = luma(&pixel.0);
+        pixel.0[..3].fill(value);
+    }
+}
+
+/// Map the luminance range between the clipped extremes onto 0-255. Returns
+/// false, leaving the image alone, when it is (nearly) a single shade.
+fn stretch_contrast(image: &mut RgbaImage) -> bool {
+    let histogram = luma_histogram(image);
+    let total: u64 = histogram.iter().sum();
+    let clip = (total as f32 * CONTRAST_CLIP) as u64;
+    let percentile = |from_top: bool| {
+        let mut seen = 0;
+        for offset in 0..256 {
+            let value = if from_top { 255 - offset } else { offset };
+            seen += histogram[value];
+            if seen > clip {
+                return value as i32;
+            }
+        }
+        if from_top {
+            0
+        } else {
+            255
+        }
+    };
+    let (low, high) = (percentile(false), percentile(true));
+    if high - low < 2 {
+        return false;
+    }
+    let mut lookup = [0u8; 256];
+    for (value, mapped) in lookup.iter_mut().enumerate() {
+        *mapped = ((value as i32 - low) * 255 / (high - low)).clamp(0, 255) as u8;
+    }
+    for pixel in image.pixels_mut() {
+        for channel in &mut pixel.0[..3] {
+            *channel = lookup[*channel as usize];
+        }
+    }
+    true
+}
+
+/// Black and white at the threshold that best separates the two luminance
+/// classes (Otsu's method).
+fn binarize(image: &mut RgbaImage) {
+    let histogram = luma_histogram(image);
+    let total: u64 = histogram.iter().sum();
+    let weighted_total: f64 = histogram
+        .iter()
+        .enumerate()
+        .map(|(value, count)| value as f64 * *count as f64)
+        .sum();
+
+    let (mut background, mut background_sum) = (0u64, 0f64);
+    let (mut threshold, mut best_variance) = (128u8, 0f64);
+    for (value, count) in histogram.iter().enumerate() {
+        background += count;
+        if background == 0 {
+            continue;
+        }
+        let foreground = total - background;
+        if foreground == 0 {
+            break;
+        }
+        background_sum += value as f64 * *count as f64;
+        let background_mean = background_sum / background as f64;
+        let foreground_mean = (weighted_total - background_sum) / foreground as f64;
+        let variance =
+            background as f64 * foreground as f64 * (background_mean - foreground_mean).powi(2);
+        if variance > best_variance {
+            best_variance = variance;
+            threshold = value as u8;
+        }
+    }
+
+    for pixel in image.pixels_mut() {
+        let value = if luma(&pixel.0) > threshold { 255 } else { 0 };
+        pixel.0[..3].fill(value);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use async_trait::async_trait;
+    use memri_ocr::OcrWord;
+
+    use super::*;
+
+    /// Reports one word at each of `boxes`, in the coordinates of whatever
+    /// image it is given.
+    struct FixedBoxes(Vec<OcrRect>);
+
+    #[async_trait]
+    impl OcrEngine for FixedBoxes {
+        async fn recognize(
+            &self,
+            _image_bytes: &[u8],
+            _context: &OcrContext,
+        ) -> Result<OcrPayload> {
+            unreachable!("preprocessed windows are recognized from pixels")
+        }
+
+        async fn recognize_rgba(
+            &self,
+            frame: RgbaFrame<'_>,
+            _context: &OcrContext,
+        ) -> Result<OcrPayload> {
+            let lines: Vec<OcrLine> = self
+                .0
+                .iter()
+                .map(|bounds| {
+                    OcrLine::new(vec![OcrWord {
+                        text: "word".to_string(),
+                        bounds: *bounds,
+                        confidence: Some(0.9),
+                    }])
+                })
+                .collect();
+            Ok(OcrPayload {
+                text: "word".to_string(),
+                confidence: Some(0.9),
+                layout: OcrLayout {
+                    engine: "fixed".to_string(),
+                    width: frame.width,
+                    height: frame.height,
+                    blocks: vec![OcrBlock::new(lines)],
+                    ..OcrLayout::default()
+                },
+            })
+        }
+
+        fn name(&self) -> &'static str {
+            "fixed"
+        }
+    }
+
+    fn context() -> OcrContext {
+        OcrContext {
+            window_name: "window".to_string(),
+            app_name: "app".to_string(),
+            is_focused: true,
+            languages: vec!["eng".to_string()],
+        }
+    }
+
+    fn prepared(width: u32, height: u32, original: (u32, u32), tile_height: u32) -> Prepared {
+        Prepared {
+            image: RgbaImage::new(width, height),
+            original_width: original.0,
+            original_height: original.1,
+            tile_height,
+            steps: Vec::new(),
+        }
+    }
+
+    fn rect(x: u32, y: u32, width: u32, height: u32) -> OcrRect {
+        OcrRect {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    fn word_boxes(payload: &OcrPayload) -> Vec<OcrRect> {
+        payload
+            .layout
+            .lines()
+            .flat_map(|line| line.words.iter().map(|word| word.bounds))
+            .collect()
+    }
+
+    #[test]
+    fn short_images_are_one_band() {
+        for (height, tile_height) in [(1000, 0), (400, 400), (10, 2000)] {
+            let bands = bands(height, tile_height);
+            assert_eq!(bands.len(), 1);
+            assert_eq!((bands[0].top, bands[0].height), (0, height));
+            assert!(bands[0].keeps(0) && bands[0].keeps(u32::MAX - 1));
+        }
+    }
+
+    #[test]
+    fn bands_overlap_and_split_the_overlap_between_them() {
+        let bands = bands(1000, 400);
+        let spans: Vec<(u32, u32)> = bands.iter().map(|band| (band.top, band.height)).collect();
+        assert_eq!(spans, [(0, 400), (336, 400), (672, 328)]);
+
+        assert_eq!(bands[0].keep_from, 0);
+        assert_eq!(bands[2].keep_to, u32::MAX);
+        for pair in bands.windows(2) {
+            // Each row belongs to exactly one band, inside the overlap.
+            assert_eq!(pair[0].keep_to, pair[1].keep_from);
+            assert!(pair[1].top < pair[0].keep_to);
+            assert!(pair[1].keep_from < pair[0].top + pair[0].height);
+        }
+    }
+
+    #[test]
+    fn bands_are_never_smaller_than_four_overlaps() {
+        let bands = bands(1000, 10);
+        assert!(bands.len() > 1);
+        assert!(bands.iter().all(|band| band.height <= TILE_OVERLAP * 4));
+        assert_eq!(bands[0].height, TILE_OVERLAP * 4);
+        let last = bands.last().unwrap();
+        assert_eq!(last.top + last.height, 1000);
+    }
+
+    #[tokio::test]
+    async fn upscaled_boxes_map_back_to_the_original_window() {
+        let engine = FixedBoxes(vec![rect(20, 40, 61, 21)]);
+        let payload = recognize(&engine, prepared(200, 100, (100, 50), 0), &context())
+            .await
+            .unwrap();
+
+        assert_eq!(word_boxes(&payload), [rect(10, 20, 31, 11)]);
+        assert_eq!(
+            payload.layout.lines().next().unwrap().bounds,
+            rect(10, 20, 31, 11)
+        );
+        assert_eq!((payload.layout.width, payload.layout.height), (100, 50));
+        assert_eq!(payload.text, "word");
+    }
+
+    #[tokio::test]
+    async fn band_boxes_are_offset_and_kept_once() {
+        // A word near the top of every band: each band past the first finds
+        // it inside the overlap the previous band keeps, so only one stays.
+        let engine = FixedBoxes(vec![rect(5, 10, 40, 10)]);
+        let payload = recognize(&engine, prepared(300, 1000, (300, 1000), 400), &context())
+            .await
+            .unwrap();
+        assert_eq!(word_boxes(&payload), [rect(5, 10, 40, 10)]);
+
+        // Mid-band words are each kept by their own band, shifted by its top.
+        let engine = FixedBoxes(vec![rect(5, 100, 40, 10)]);
+        let payload = recognize(&engine, prepared(300, 1000, (300, 1000), 400), &context())
+            .await
+            .unwrap();
+        assert_eq!(
+            word_boxes(&payload),
+            [
+                rect(5, 100, 40, 10),
+                rect(5, 436, 40, 10),
+                rect(5, 772, 40, 10)
+            ]
+        );
+        assert_eq!(payload.text, "word\nword\nword");
+        assert_eq!(payload.layout.preprocessing, ["tiles:3"]);
+    }
+}
@@ -0,0 +1,116 @@
+use memri_config::PrivacyConfig;
+use regex::Regex;
+use tracing::warn;
+
+/// Title markers browsers use for private windows.
+const PRIVATE_BROWSING_TITLES: &[&str] = &[
+    "InPrivate",
+    "Incognito",
+    "Private Browsing",
+    "Private Window",
+    "Tor Browser",
+];
+
+/// Password managers, matched against both app names and titles (web vaults).
+const PASSWORD_MANAGERS: &[&str] = &[
+    "1Password",
+    "Bitwarden",
+    "KeePass",
+    "KeePassXC",
+    "LastPass",
+    "Dashlane",
+    "Keeper",
+    "Enpass",
+    "Proton Pass",
+    "Keychain Access",
+    "Seahorse",
+    "KWalletManager",
+];
+
+/// Banking and payment sites, matched against window titles.
+const BANKING_TITLES: &[&str] = &[
+    "Online Banking",
+    "Bank of America",
+    "Chase",
+    "Wells Fargo",
+    "Citibank",
+    "Capital One",
+    "Barclays",
+    "HSBC",
+    "Santander",
+    "PayPal",
+    "Revolut",
+    "Monzo",
+];
+
+/// Built-in plus user-configured rules for windows that must never be recorded.
+pub(crate) struct PrivacyPolicy {
+    enabled: bool,
+    pause_on_focus: bool,
+    app_pattern: Option<Regex>,
+    title_pattern: Option<Regex>,
+}
+
+impl PrivacyPolicy {
+    pub(crate) fn new(config: &PrivacyConfig) -> Self {
+        let apps: Vec<&str> = PASSWORD_MANAGERS
+            .iter()
+            .copied()
+            .chain(config.apps.iter().map(String::as_str))
+            .collect();
+        let titles: Vec<&str> = PRIVATE_BROWSING_TITLES
+            .iter()
+            .chain(PASSWORD_MANAGERS)
+            .chain(BANKING_TITLES)
+            .copied()
+            .chain(config.titles.iter().map(String::as_str))
+            .collect();
+
+        Self {
+            enabled: config.enabled,
+            pause_on_focus: config.pause_on_focus,
+            app_pattern: keyword_pattern(&apps),
+            title_pattern: keyword_pattern(&titles),
+        }
+    }
+
+    /// Whether the window must be skipped.
+    pub(crate) fn is_private(&self, app_name: &str, window_name: &str) -> bool {
+        self.enabled
+            && (self
+                .app_pattern
+                .as_ref()
+                .is_some_and(|pattern| pattern.is_match(app_name))
+                || self
+                    .title_pattern
+                    .as_ref()
+                    .is_some_and(|pattern| pattern.is_match(window_name)))
+    }
+
+    /// Whether a private window with focus should pause the whole loop.
+    pub(crate) fn pauses_on_focus(&self) -> bool {
+        self.enabled && self.pause_on_focus
+    }
+}
+
+/// Case-insensitive whole-word alternation, so "Chase" does not hit "purchase".
+fn keyword_pattern(keywords: &[&str]) -> Option<Regex> {
+    let alternation = keywords
+        .iter()
+        .map(|keyword| keyword.trim())
+        .filter(|keyword| !keyword.is_empty())
+        .map(regex::escape)
+        .collect::<Vec<_>>()
+        .join("|");
+    if alternation.is_empty() {
+        return None;
+    }
+
+    match Regex::new(&format!(r"(?i)\b(?:{alternation})\b")) {
+        Ok(regex) => Some(regex),
+        Err(err) => {
+            warn!("failed to build privacy pattern: {err}");
+            None
+        }
+    }
+}
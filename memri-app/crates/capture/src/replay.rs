@@ -0,0 +1,272 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::Duration;
+
+use anyhow::{anyhow, Context, Result};
+use async_trait::async_trait;
+use memri_storage::ScreenRect;
+use serde::Deserialize;
+use tokio::sync::Mutex;
+use tokio::task;
+use tokio::time::Instant;
+use tracing::{debug, info};
+
+use crate::frame_source::{FrameSource, MonitorFrame, RawCapture};
+use crate::window_capture::{CapturedWindow, WindowFilters};
+
+const MANIFEST_FILE: &str = "manifest.json";
+
+/// How recorded frames are paced when replayed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReplayMode {
+    /// Keep the spacing between the recorded timestamps.
+    Realtime,
+    /// Emit frames back-to-back.
+    AsFastAsPossible,
+}
+
+impl FromStr for ReplayMode {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self> {
+        match value.trim().to_lowercase().as_str() {
+            "realtime" | "original" => Ok(Self::Realtime),
+            "fast" | "asap" => Ok(Self::AsFastAsPossible),
+            other => Err(anyhow!("unknown replay speed: {other}")),
+        }
+    }
+}
+
+/// On-disk description of a recording. Image paths are relative to the
+/// directory containing the manifest.
+#[derive(Debug, Deserialize)]
+struct ReplayManifest {
+    frames: Vec<ReplayFrame>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct ReplayFrame {
+    timestamp_ms: i64,
+    /// Monitor shown by `monitor_image`; frames without one are replayed on
+    /// the first requested monitor.
+    #[serde(default)]
+    monitor_id: Option<u32>,
+    #[serde(default)]
+    monitor_image: Option<PathBuf>,
+    /// Multi-monitor recordings list every monitor here instead.
+    #[serde(default)]
+    monitors: Vec<ReplayMonitor>,
+    #[serde(default)]
+    windows: Vec<ReplayWindow>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct ReplayMonitor {
+    monitor_id: u32,
+    #[serde(default)]
+    name: String,
+    image: PathBuf,
+    /// Defaults to the image size at the screen origin.
+    #[serde(default)]
+    bounds: Option<ScreenRect>,
+}
+
+impl ReplayFrame {
+    /// The frame's monitors that are among `monitor_ids`.
+    fn monitors_for(&self, monitor_ids: &[u32]) -> Vec<ReplayMonitor> {
+        let mut monitors = self.monitors.clone();
+        if let Some(image) = &self.monitor_image {
+            monitors.push(ReplayMonitor {
+                monitor_id: self
+                    .monitor_id
+                    .or_else(|| monitor_ids.first().copied())
+                    .unwrap_or_default(),
+                name: String::new(),
+                image: image.clone(),
+                bounds: None,
+            });
+        }
+        monitors.retain(|monitor| monitor_ids.contains(&monitor.monitor_id));
+        monitors
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct ReplayWindow {
+    image: PathBuf,
+    app_name: String,
+    window_name: String,
+    #[serde(default)]
+    process_id: i32,
+    #[serde(default)]
+    executable_path: Option<String>,
+    #[serde(default)]
+    is_focused: bool,
+    #[serde(default)]
+    bounds: Option<ScreenRect>,
+    #[serde(default)]
+    z_order: Option<i32>,
+}
+
+#[derive(Default)]
+struct ReplayState {
+    cursor: usize,
+    /// Wall-clock instant and recorded timestamp of the first replayed frame.
+    clock: Option<(Instant, i64)>,
+}
+
+/// Frame source that replays a recorded directory of monitor and window images.
+pub struct ReplayFrameSource {
+    base_dir: PathBuf,
+    frames: Vec<ReplayFrame>,
+    mode: ReplayMode,
+    state: Mutex<ReplayState>,
+}
+
+impl ReplayFrameSource {
+    /// Open a recording from either a manifest file or a directory containing
+    /// `manifest.json`.
+    pub fn open(path: impl AsRef<Path>, mode: ReplayMode) -> Result<Self> {
+        let path = path.as_ref();
+        let manifest_path = if path.is_dir() {
+            path.join(MANIFEST_FILE)
+        } else {
+            path.to_path_buf()
+        };
+
+        let contents = fs::read_to_string(&manifest_path)
+            .with_context(|| format!("failed to read replay manifest {manifest_path:?}"))?;
+        let mut manifest: ReplayManifest = serde_json::from_str(&contents)
+            .with_context(|| format!("failed to parse replay manifest {manifest_path:?}"))?;
+        manifest.frames.sort_by_key(|frame| frame.timestamp_ms);
+
+        let base_dir = manifest_path
+            .parent()
+            .map(Path::to_path_buf)
+            .unwrap_or_default();
+
+        info!(
+            frames = manifest.frames.len(),
+            ?mode,
+            "loaded replay manifest from {:?}",
+            manifest_path
+        );
+
+        Ok(Self {
+            base_dir,
+            frames: manifest.frames,
+            mode,
+            state: Mutex::new(ReplayState::default()),
+        })
+    }
+}
+
+#[async_trait]
+impl FrameSource for ReplayFrameSource {
+    async fn capture_frame(
+        &self,
+        monitor_ids: &[u32],
+        capture_unfocused_windows: bool,
+        window_filters: &WindowFilters,
+    ) -> Result<Option<RawCapture>> {
+        let mut state = self.state.lock().await;
+
+        let (frame, monitors) = loop {
+            let Some(frame) = self.frames.get(state.cursor) else {
+                debug!(?monitor_ids, "replay exhausted");
+                return Ok(None);
+            };
+            state.cursor += 1;
+            let monitors = frame.monitors_for(monitor_ids);
+            if !monitors.is_empty() {
+                break (frame, monitors);
+            }
+        };
+
+        if self.mode == ReplayMode::Realtime {
+            let (started, first_ts) = *state
+                .clock
+                .get_or_insert((Instant::now(), frame.timestamp_ms));
+            let offset_ms = frame.timestamp_ms.saturating_sub(first_ts).max(0) as u64;
+            tokio::time::sleep_until(started + Duration::from_millis(offset_ms)).await;
+        }
+        drop(state);
+
+        let windows: Vec<ReplayWindow> = frame
+            .windows
+            .iter()
+            .filter(|window| capture_unfocused_windows || window.is_focused)
+            .filter(|window| window_filters.is_valid(&window.app_name, &window.window_name))
+            .cloned()
+            .collect();
+
+        let base_dir = self.base_dir.clone();
+        let timestamp_ms = frame.timestamp_ms;
+        let capture =
+            task::spawn_blocking(move || load_frame(&base_dir, timestamp_ms, monitors, windows))
+                .await??;
+        Ok(Some(capture))
+    }
+
+    fn self_paced(&self) -> bool {
+        true
+    }
+
+    fn name(&self) -> &'static str {
+        "replay"
+    }
+}
+
+fn load_frame(
+    base_dir: &Path,
+    timestamp_ms: i64,
+    monitors: Vec<ReplayMonitor>,
+    windows: Vec<ReplayWindow>,
+) -> Result<RawCapture> {
+    let monitors = monitors
+        .into_iter()
+        .map(|monitor| {
+            let monitor_path = base_dir.join(&monitor.image);
+            let image = image::open(&monitor_path)
+                .with_context(|| format!("failed to load replay frame {monitor_path:?}"))?;
+            let bounds = monitor.bounds.unwrap_or(ScreenRect {
+                x: 0,
+                y: 0,
+                width: image.width(),
+                height: image.height(),
+            });
+            Ok(MonitorFrame {
+                monitor_id: monitor.monitor_id,
+                name: monitor.name,
+                image,
+                bounds,
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let windows = windows
+        .into_iter()
+        .map(|window| {
+            let image_path = base_dir.join(&window.image);
+            let image = image::open(&image_path)
+                .with_context(|| format!("failed to load replay window {image_path:?}"))?;
+            Ok(CapturedWindow {
+                image,
+                app_name: window.app_name,
+                window_name: window.window_name,
+                process_id: window.process_id,
+                executable_path: window.executable_path,
+                is_focused: window.is_focused,
+                bounds: window.bounds,
+                z_order: window.z_order,
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    Ok(RawCapture {
+        monitors,
+        windows,
+        timestamp_ms: Some(timestamp_ms),
+    })
+}
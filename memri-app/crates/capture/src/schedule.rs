@@ -0,0 +1,298 @@
+use anyhow::{anyhow, bail, Context, Result};
+use chrono::{DateTime, Datelike, Days, Local, NaiveDate, TimeDelta, TimeZone};
+use memri_config::{ScheduleConfig, ScheduleWindow};
+use serde::Serialize;
+
+const MINUTES_PER_DAY: u32 = 24 * 60;
+const WEEKDAYS: [&str; 7] = [
+    "monday",
+    "tuesday",
+    "wednesday",
+    "thursday",
+    "friday",
+    "saturday",
+    "sunday",
+];
+/// How many days ahead occurrences are expanded; enough for a full week of slots.
+const HORIZON_DAYS: u64 = 8;
+
+/// One period in which capture is allowed, in epoch milliseconds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct ScheduleSlot {
+    pub start_ms: i64,
+    pub end_ms: i64,
+}
+
+/// Compiled schedule windows, evaluated in local time.
+#[derive(Debug, Clone)]
+pub struct Schedule {
+    rules: Vec<Rule>,
+}
+
+#[derive(Debug, Clone)]
+struct Rule {
+    /// Indexed from Monday.
+    days: [bool; 7],
+    /// Minutes after midnight; `end` may run into the next day.
+    start: u32,
+    end: u32,
+}
+
+impl Schedule {
+    /// Compile the configured windows. Returns `None` when capture is not
+    /// restricted (schedule disabled or no windows).
+    pub fn from_config(config: &ScheduleConfig) -> Result<Option<Self>> {
+        if !config.enabled || config.windows.is_empty() {
+            return Ok(None);
+        }
+        let rules = config
+            .windows
+            .iter()
+            .map(Rule::parse)
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Some(Self { rules }))
+    }
+
+    pub fn is_open_at(&self, now: DateTime<Local>) -> bool {
+        self.slots_around(now)
+            .iter()
+            .any(|(start, end)| *start <= now && now < *end)
+    }
+
+    /// When the schedule next opens or closes after `now`.
+    pub fn next_change(&self, now: DateTime<Local>) -> Option<DateTime<Local>> {
+        self.slots_around(now)
+            .into_iter()
+            .find(|(_, end)| now < *end)
+            .map(|(start, end)| if now < start { start } else { end })
+    }
+
+    /// The current (if open) and following capture periods, at most `limit`.
+    pub fn upcoming(&self, now: DateTime<Local>, limit: usize) -> Vec<ScheduleSlot> {
+        self.slots_around(now)
+            .into_iter()
+            .filter(|(_, end)| now < *end)
+            .take(limit)
+            .map(|(start, end)| ScheduleSlot {
+                start_ms: start.timestamp_millis(),
+                end_ms: end.timestamp_millis(),
+            })
+            .collect()
+    }
+
+    /// Occurrences from yesterday (overnight windows) to the horizon, sorted
+    /// and merged so touching or overlapping windows form one slot.
+    fn slots_around(&self, now: DateTime<Local>) -> Vec<(DateTime<Local>, DateTime<Local>)> {
+        let today = now.date_naive();
+        let Some(first_day) = today.checked_sub_days(Days::new(1)) else {
+            return Vec::new();
+        };
+
+        let mut slots = Vec::new();
+        for day in first_day.iter_days().take(HORIZON_DAYS as usize + 1) {
+            let weekday = day.weekday().num_days_from_monday() as usize;
+            for rule in self.rules.iter().filter(|rule| rule.days[weekday]) {
+                if let (Some(start), Some(end)) =
+                    (local_at(day, rule.start), local_at(day, rule.end))
+                {
+                    slots.push((start, end));
+                }
+            }
+        }
+
+        slots.sort();
+        let mut merged: Vec<(DateTime<Local>, DateTime<Local>)> = Vec::with_capacity(slots.len());
+        for (start, end) in slots {
+            match merged.last_mut() {
+                Some(last) if start <= last.1 => last.1 = last.1.max(end),
+                _ => merged.push((start, end)),
+            }
+        }
+        merged
+    }
+}
+
+impl Rule {
+    fn parse(window: &ScheduleWindow) -> Result<Self> {
+        let mut days = [window.days.is_empty(); 7];
+        for spec in &window.days {
+            for day in parse_days(spec)? {
+                days[day] = true;
+            }
+        }
+
+        let start = parse_time(&window.start)
+            .with_context(|| format!("invalid schedule start {:?}", window.start))?;
+        let mut end = parse_time(&window.end)
+            .with_context(|| format!("invalid schedule end {:?}", window.end))?;
+        if start == MINUTES_PER_DAY {
+            bail!("schedule start must be before 24:00");
+        }
+        if end <= start {
+            end += MINUTES_PER_DAY;
+        }
+
+        Ok(Self { days, start, end })
+    }
+}
+
+/// Day indexes (from Monday) named by one `days` entry.
+fn parse_days(spec: &str) -> Result<Vec<usize>> {
+    let spec = spec.trim().to_lowercase();
+    match spec.as_str() {
+        "daily" | "everyday" | "*" => return Ok((0..7).collect()),
+        "weekdays" => return Ok((0..5).collect()),
+        "weekends" => return Ok(vec![5, 6]),
+        _ => {}
+    }
+
+    if let Some((from, to)) = spec.split_once('-') {
+        let (from, to) = (parse_day(from)?, parse_day(to)?);
+        // Ranges may wrap the week, e.g. `fri-mon`.
+        let len = (to + 7 - from) % 7 + 1;
+        return Ok((0..len).map(|offset| (from + offset) % 7).collect());
+    }
+    Ok(vec![parse_day(&spec)?])
+}
+
+/// Day index from a full name or any prefix of at least three letters (`mon`, `tues`).
+fn parse_day(name: &str) -> Result<usize> {
+    let name = name.trim();
+    WEEKDAYS
+        .iter()
+        .position(|day| name.len() >= 3 && day.starts_with(name))
+        .ok_or_else(|| anyhow!("invalid schedule day {name:?}"))
+}
+
+/// `HH:MM` as minutes after midnight; `24:00` is allowed as an end of day.
+fn parse_time(value: &str) -> Result<u32> {
+    let (hours, minutes) = value
+        .trim()
+        .split_once(':')
+        .ok_or_else(|| anyhow!("expected HH:MM"))?;
+    let hours: u32 = hours.parse()?;
+    let minutes: u32 = minutes.parse()?;
+    if minutes >= 60 || hours > 24 || (hours == 24 && minutes > 0) {
+        bail!("time out of range");
+    }
+    Ok(hours * 60 + minutes)
+}
+
+/// Local time `minutes` after midnight of `day`. Times skipped by a DST jump
+/// resolve to the first valid instant after the gap.
+fn local_at(day: NaiveDate, minutes: u32) -> Option<DateTime<Local>> {
+    let naive = day.and_hms_opt(0, 0, 0)? + TimeDelta::minutes(minutes as i64);
+    Local.from_local_datetime(&naive).earliest().or_else(|| {
+        Local
+            .from_local_datetime(&(naive + TimeDelta::hours(1)))
+            .earliest()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn schedule(windows: &[(&[&str], &str, &str)]) -> Schedule {
+        let config = ScheduleConfig {
+            enabled: true,
+            windows: windows
+                .iter()
+                .map(|(days, start, end)| ScheduleWindow {
+                    days: days.iter().map(|day| day.to_string()).collect(),
+                    start: start.to_string(),
+                    end: end.to_string(),
+                })
+                .collect(),
+        };
+        Schedule::from_config(&config).unwrap().unwrap()
+    }
+
+    /// Local time in mid-June 2024, away from DST changes; the 12th is a Wednesday.
+    fn at(day: u32, hour: u32, minute: u32) -> DateTime<Local> {
+        Local
+            .with_ymd_and_hms(2024, 6, day, hour, minute, 0)
+            .unwrap()
+    }
+
+    #[test]
+    fn working_hours_open_on_weekdays_only() {
+        let schedule = schedule(&[(&["mon-fri"], "09:00", "17:30")]);
+        assert!(schedule.is_open_at(at(12, 9, 0)));
+        assert!(schedule.is_open_at(at(12, 17, 29)));
+        assert!(!schedule.is_open_at(at(12, 17, 30)));
+        assert!(!schedule.is_open_at(at(12, 8, 59)));
+        assert!(!schedule.is_open_at(at(15, 12, 0))); // Saturday
+
+        assert_eq!(schedule.next_change(at(12, 8, 0)), Some(at(12, 9, 0)));
+        assert_eq!(schedule.next_change(at(12, 10, 0)), Some(at(12, 17, 30)));
+        // Friday evening waits for Monday.
+        assert_eq!(schedule.next_change(at(14, 18, 0)), Some(at(17, 9, 0)));
+    }
+
+    #[test]
+    fn windows_ending_before_they_start_run_past_midnight() {
+        let schedule = schedule(&[(&["fri"], "22:00", "06:00")]);
+        assert!(schedule.is_open_at(at(14, 23, 0)));
+        assert!(schedule.is_open_at(at(15, 5, 59)));
+        assert!(!schedule.is_open_at(at(15, 6, 0)));
+        assert!(!schedule.is_open_at(at(13, 23, 0))); // Thursday
+        assert_eq!(schedule.next_change(at(15, 2, 0)), Some(at(15, 6, 0)));
+    }
+
+    #[test]
+    fn touching_windows_form_one_slot() {
+        let schedule = schedule(&[(&[], "09:00", "12:00"), (&[], "12:00", "24:00")]);
+        assert_eq!(
+            schedule.upcoming(at(12, 8, 0), 2),
+            [
+                ScheduleSlot {
+                    start_ms: at(12, 9, 0).timestamp_millis(),
+                    end_ms: at(13, 0, 0).timestamp_millis(),
+                },
+                ScheduleSlot {
+                    start_ms: at(13, 9, 0).timestamp_millis(),
+                    end_ms: at(14, 0, 0).timestamp_millis(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn disabled_or_empty_schedules_do_not_restrict_capture() {
+        let mut config = ScheduleConfig::default();
+        assert!(Schedule::from_config(&config).unwrap().is_none());
+        config.windows.push(ScheduleWindow {
+            days: Vec::new(),
+            start: "09:00".to_string(),
+            end: "17:00".to_string(),
+        });
+        config.enabled = false;
+        assert!(Schedule::from_config(&config).unwrap().is_none());
+    }
+
+    #[test]
+    fn day_specs() {
+        assert_eq!(parse_days("fri-mon").unwrap(), [4, 5, 6, 0]);
+        assert_eq!(parse_days("Weekends").unwrap(), [5, 6]);
+        assert_eq!(parse_days("tues").unwrap(), [1]);
+        assert_eq!(parse_days("daily").unwrap().len(), 7);
+        assert!(parse_days("mo").is_err());
+        assert!(parse_days("funday").is_err());
+    }
+
+    #[test]
+    fn times() {
+        assert_eq!(parse_time("09:30").unwrap(), 570);
+        assert_eq!(parse_time(" 24:00 ").unwrap(), MINUTES_PER_DAY);
+        for invalid in ["24:01", "9:60", "0930", "nine:00"] {
+            assert!(parse_time(invalid).is_err(), "{invalid}");
+        }
+        let window = ScheduleWindow {
+            days: Vec::new(),
+            start: "24:00".to_string(),
+            end: "06:00".to_string(),
+        };
+        assert!(Rule::parse(&window).is_err());
+    }
+}
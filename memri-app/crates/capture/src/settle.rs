@@ -0,0 +1,88 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+use memri_config::{DetectionThresholds, SettleConfig};
+use tokio::time::Instant;
+use tracing::{debug, warn};
+
+use crate::change_detection::ChangeDetector;
+use crate::frame_source::{FrameSource, RawCapture};
+use crate::window_capture::WindowFilters;
+
+/// The frame chosen after waiting for the screen to stop changing.
+pub(crate) struct Settled {
+    pub(crate) capture: RawCapture,
+    /// Samples taken and thrown away before `capture`.
+    pub(crate) skipped_frames: u32,
+}
+
+/// Resample `first` until `stable_frames` consecutive samples show no
+/// significant change on any monitor, or `timeout_ms` has passed, and return
+/// the last sample. Each sample is compared with the one before it, so a slow
+/// fade keeps the loop waiting while a finished animation does not.
+///
+/// A failing or exhausted source ends the wait early with the latest sample.
+pub(crate) async fn settle(
+    config: &SettleConfig,
+    source: &dyn FrameSource,
+    monitor_ids: &[u32],
+    capture_unfocused_windows: bool,
+    window_filters: &WindowFilters,
+    thresholds: &DetectionThresholds,
+    first: RawCapture,
+) -> Settled {
+    let interval = Duration::from_millis(config.interval_ms);
+    let deadline = Instant::now() + Duration::from_millis(config.timeout_ms);
+
+    let mut detectors: HashMap<u32, ChangeDetector> = HashMap::new();
+    for monitor in &first.monitors {
+        detectors
+            .entry(monitor.monitor_id)
+            .or_default()
+            .evaluate(&monitor.image, thresholds);
+    }
+
+    let mut latest = first;
+    let mut skipped_frames: u32 = 0;
+    let mut stable: u32 = 0;
+    while stable < config.stable_frames {
+        if Instant::now() + interval > deadline {
+            debug!(skipped_frames, "screen did not settle before the deadline");
+            break;
+        }
+        // Replay sources already pace their frames.
+        if !source.self_paced() {
+            tokio::time::sleep(interval).await;
+        }
+
+        let sample = match source
+            .capture_frame(monitor_ids, capture_unfocused_windows, window_filters)
+            .await
+        {
+            Ok(Some(sample)) => sample,
+            Ok(None) => break,
+            Err(err) => {
+                warn!("capture failed while settling: {err}");
+                break;
+            }
+        };
+
+        // Every monitor is compared, so none of them keeps a stale baseline.
+        let mut moving = false;
+        for monitor in &sample.monitors {
+            moving |= detectors
+                .entry(monitor.monitor_id)
+                .or_default()
+                .evaluate(&monitor.image, thresholds)
+                .is_significant();
+        }
+        stable = if moving { 0 } else { stable + 1 };
+        skipped_frames = skipped_frames.saturating_add(1);
+        latest = sample;
+    }
+
+    Settled {
+        capture: latest,
+        skipped_frames,
+    }
+}
@@ -1,21 +1,31 @@
 use std::collections::HashSet;
 
+#[cfg(target_os = "windows")]
 use anyhow::{Error, Result};
-use image::{DynamicImage, ImageBuffer};
+use image::DynamicImage;
+#[cfg(target_os = "windows")]
+use image::ImageBuffer;
+use memri_storage::ScreenRect;
+#[cfg(any(target_os = "windows", target_os = "linux"))]
 use once_cell::sync::Lazy;
+#[cfg(target_os = "windows")]
 use tracing::{debug, error, trace};
+#[cfg(target_os = "windows")]
 use xcap::Window;
 
-use crate::monitor::SafeMonitor;
-
 #[derive(Debug, Clone)]
-#[allow(dead_code)]
 pub struct CapturedWindow {
     pub image: DynamicImage,
     pub app_name: String,
     pub window_name: String,
     pub process_id: i32,
+    /// Full path of the process executable, when it can be resolved.
+    pub executable_path: Option<String>,
     pub is_focused: bool,
+    /// Position and size on the virtual screen, when the source knows it.
+    pub bounds: Option<ScreenRect>,
+    /// Stacking position; higher values are closer to the front.
+    pub z_order: Option<i32>,
 }
 
 #[derive(Debug, Clone)]
@@ -68,8 +78,29 @@ static SKIP_APPS: Lazy<HashSet<&'static str>> = Lazy::new(|| {
     ])
 });
 
-#[cfg(not(target_os = "windows"))]
-static SKIP_APPS: Lazy<HashSet<&'static str>> = Lazy::new(HashSet::new);
+/// Matched against the WM_CLASS instance name, which is lowercase by convention.
+#[cfg(target_os = "linux")]
+static SKIP_APPS: Lazy<HashSet<&'static str>> = Lazy::new(|| {
+    HashSet::from([
+        "gnome-shell",
+        "plasmashell",
+        "xfce4-panel",
+        "xfdesktop",
+        "lxpanel",
+        "pcmanfm-desktop",
+        "mate-panel",
+        "budgie-panel",
+        "cinnamon",
+        "polybar",
+        "tint2",
+        "plank",
+        "docky",
+        "cairo-dock",
+        "conky",
+        "desktop_window",
+        "nautilus-desktop",
+    ])
+});
 
 #[cfg(target_os = "windows")]
 static SKIP_TITLES: Lazy<HashSet<&'static str>> = Lazy::new(|| {
@@ -87,11 +118,73 @@ static SKIP_TITLES: Lazy<HashSet<&'static str>> = Lazy::new(|| {
     ])
 });
 
-#[cfg(not(target_os = "windows"))]
-static SKIP_TITLES: Lazy<HashSet<&'static str>> = Lazy::new(HashSet::new);
+#[cfg(target_os = "linux")]
+static SKIP_TITLES: Lazy<HashSet<&'static str>> = Lazy::new(|| {
+    HashSet::from([
+        "Desktop",
+        "Desktop — Plasma",
+        "Plasma",
+        "xfce4-panel",
+        "xfdesktop",
+        "Top Bar",
+        "Bottom Panel",
+        "Plank",
+        "Docky",
+    ])
+});
 
+/// Whether a window belongs to the desktop shell (taskbars, panels, docks,
+/// the desktop itself) and should never be captured.
+#[cfg(any(target_os = "windows", target_os = "linux"))]
+pub(crate) fn is_system_window(app_name: &str, title: &str) -> bool {
+    SKIP_APPS.contains(app_name) || SKIP_TITLES.contains(title)
+}
+
+/// Resolve the executable of `process_id` from `/proc`.
+#[cfg(target_os = "linux")]
+pub(crate) fn executable_path(process_id: i32) -> Option<String> {
+    if process_id <= 0 {
+        return None;
+    }
+    std::fs::read_link(format!("/proc/{process_id}/exe"))
+        .ok()
+        .map(|path| path.to_string_lossy().to_string())
+}
+
+/// Resolve the executable of `process_id`; needs only limited query rights,
+/// so it also works for elevated processes.
+#[cfg(target_os = "windows")]
+pub(crate) fn executable_path(process_id: i32) -> Option<String> {
+    use windows::core::PWSTR;
+    use windows::Win32::Foundation::CloseHandle;
+    use windows::Win32::System::Threading::{
+        OpenProcess, QueryFullProcessImageNameW, PROCESS_NAME_WIN32,
+        PROCESS_QUERY_LIMITED_INFORMATION,
+    };
+
+    if process_id <= 0 {
+        return None;
+    }
+    let mut buffer = [0u16; 1024];
+    let mut len = buffer.len() as u32;
+    // SAFETY: the handle is closed before returning and `buffer` outlives the call.
+    unsafe {
+        let process =
+            OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, false, process_id as u32).ok()?;
+        let result = QueryFullProcessImageNameW(
+            process,
+            PROCESS_NAME_WIN32,
+            PWSTR(buffer.as_mut_ptr()),
+            &mut len,
+        );
+        let _ = CloseHandle(process);
+        result.ok()?;
+    }
+    Some(String::from_utf16_lossy(&buffer[..len as usize]))
+}
+
+#[cfg(target_os = "windows")]
 pub async fn capture_all_visible_windows(
-    monitor: &SafeMonitor,
     window_filters: &WindowFilters,
     capture_unfocused_windows: bool,
 ) -> Result<Vec<CapturedWindow>> {
@@ -102,8 +195,7 @@ pub async fn capture_all_visible_windows(
     }
 
     let mut captured = Vec::new();
-    let monitor_id = monitor.id();
-    trace!(monitor_id, "processing visible windows");
+    trace!(windows = windows.len(), "processing visible windows");
     for window in windows {
         let app_name = match window.app_name() {
             Ok(name) => name.to_string(),
@@ -121,7 +213,7 @@ pub async fn capture_all_visible_windows(
             }
         };
 
-        if SKIP_APPS.contains(app_name.as_str()) || SKIP_TITLES.contains(title.as_str()) {
+        if is_system_window(&app_name, &title) {
             debug!("skipping known system window");
             continue;
         }
@@ -142,6 +234,7 @@ pub async fn capture_all_visible_windows(
         }
 
         let process_id = window.pid().unwrap_or_default() as i32;
+        let z_order = window.z().ok();
 
         let buffer = match window.capture_image() {
             Ok(buffer) => buffer,
@@ -159,13 +252,25 @@ pub async fn capture_all_visible_windows(
             }
         };
         let image = DynamicImage::ImageRgba8(rgba);
+        let bounds = match (window.x(), window.y()) {
+            (Ok(x), Ok(y)) => Some(ScreenRect {
+                x,
+                y,
+                width: image.width(),
+                height: image.height(),
+            }),
+            _ => None,
+        };
 
         captured.push(CapturedWindow {
             image,
             app_name,
             window_name: title,
             process_id,
+            executable_path: executable_path(process_id),
             is_focused,
+            bounds,
+            z_order,
         });
     }
 
@@ -0,0 +1,137 @@
+use std::collections::HashMap;
+
+use memri_config::DetectionThresholds;
+use memri_storage::CapturedWindowRecord;
+use tracing::trace;
+
+use crate::change_detection::{ChangeDecision, ChangeDetector};
+use crate::window_capture::CapturedWindow;
+
+/// Windows not seen for this long lose their signature and stored record.
+const WINDOW_STATE_TTL_MS: i64 = 5 * 60 * 1000;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct WindowKey {
+    app_name: String,
+    window_name: String,
+    process_id: i32,
+}
+
+impl WindowKey {
+    fn from_window(window: &CapturedWindow) -> Self {
+        Self {
+            app_name: window.app_name.clone(),
+            window_name: window.window_name.clone(),
+            process_id: window.process_id,
+        }
+    }
+}
+
+struct TrackedWindow {
+    detector: ChangeDetector,
+    /// Last persisted record for this window and its row id.
+    stored: Option<(i64, CapturedWindowRecord)>,
+    /// When `stored` was captured.
+    stored_at_ms: i64,
+    /// The window changed after `stored` was captured.
+    outdated: bool,
+    last_seen_ms: i64,
+}
+
+/// Per-window change signatures so windows that did not change inside a
+/// significant frame can reuse their previous OCR result and image.
+#[derive(Default)]
+pub(crate) struct WindowTracker {
+    windows: HashMap<WindowKey, TrackedWindow>,
+}
+
+impl WindowTracker {
+    pub(crate) fn new() -> Self {
+        Self::default()
+    }
+
+    /// Update the window's signature and return its previous record when the
+    /// window is unchanged and that record was persisted. With `hold_ms`, a
+    /// changed window keeps its record until the record is that old.
+    pub(crate) fn unchanged_record(
+        &mut self,
+        window: &CapturedWindow,
+        timestamp_ms: i64,
+        thresholds: &DetectionThresholds,
+        hold_ms: Option<i64>,
+    ) -> Option<(i64, CapturedWindowRecord)> {
+        let tracked = self
+            .windows
+            .entry(WindowKey::from_window(window))
+            .or_insert_with(|| TrackedWindow {
+                detector: ChangeDetector::new(),
+                stored: None,
+                stored_at_ms: timestamp_ms,
+                outdated: false,
+                last_seen_ms: timestamp_ms,
+            });
+        tracked.last_seen_ms = timestamp_ms;
+
+        if !matches!(
+            tracked.detector.evaluate(&window.image, thresholds),
+            ChangeDecision::Insignificant { .. }
+        ) {
+            tracked.outdated = true;
+        }
+        let held = hold_ms.is_some_and(|hold_ms| timestamp_ms - tracked.stored_at_ms < hold_ms);
+        if tracked.outdated && !held {
+            tracked.stored = None;
+        }
+        tracked.stored.clone()
+    }
+
+    /// Remember the persisted record for a freshly processed window. Its OCR
+    /// may still be pending; see `complete` and `forget`.
+    pub(crate) fn remember(
+        &mut self,
+        window: &CapturedWindow,
+        window_id: i64,
+        record: CapturedWindowRecord,
+        timestamp_ms: i64,
+    ) {
+        if let Some(tracked) = self.windows.get_mut(&WindowKey::from_window(window)) {
+            tracked.stored = Some((window_id, record));
+            tracked.stored_at_ms = timestamp_ms;
+            tracked.outdated = false;
+        }
+    }
+
+    /// Swap in the finished OCR result for a window still pointing at `window_id`.
+    pub(crate) fn complete(&mut self, window_id: i64, record: CapturedWindowRecord) {
+        if let Some(stored) = self.stored_mut(window_id) {
+            stored.1 = record;
+        }
+    }
+
+    /// Drop the stored record for `window_id` so the window is OCR'd again.
+    pub(crate) fn forget(&mut self, window_id: i64) {
+        for tracked in self.windows.values_mut() {
+            if matches!(&tracked.stored, Some((id, _)) if *id == window_id) {
+                tracked.stored = None;
+            }
+        }
+    }
+
+    fn stored_mut(&mut self, window_id: i64) -> Option<&mut (i64, CapturedWindowRecord)> {
+        self.windows
+            .values_mut()
+            .filter_map(|tracked| tracked.stored.as_mut())
+            .find(|(id, _)| *id == window_id)
+    }
+
+    pub(crate) fn evict_stale(&mut self, now_ms: i64) {
+        let before = self.windows.len();
+        self.windows
+            .retain(|_, tracked| now_ms - tracked.last_seen_ms <= WINDOW_STATE_TTL_MS);
+        trace!(
+            evicted = before - self.windows.len(),
+            tracked = self.windows.len(),
+            "window tracker pruned"
+        );
+    }
+}
@@ -0,0 +1,344 @@
+use std::fs;
+
+use anyhow::{anyhow, Context, Result};
+use async_trait::async_trait;
+use image::{DynamicImage, RgbaImage};
+use memri_storage::ScreenRect;
+use tokio::task;
+use tracing::{debug, trace, warn};
+use xcb::x;
+
+use crate::frame_source::{FrameSource, MonitorInfo, RawCapture};
+use crate::monitor::{capture_monitor_frames, connected_monitors};
+use crate::window_capture::{executable_path, is_system_window, CapturedWindow, WindowFilters};
+
+xcb::atoms_struct! {
+    struct EwmhAtoms {
+        net_client_list => b"_NET_CLIENT_LIST",
+        net_client_list_stacking => b"_NET_CLIENT_LIST_STACKING",
+        net_active_window => b"_NET_ACTIVE_WINDOW",
+        net_wm_name => b"_NET_WM_NAME",
+        net_wm_pid => b"_NET_WM_PID",
+        net_wm_state => b"_NET_WM_STATE",
+        net_wm_state_hidden => b"_NET_WM_STATE_HIDDEN",
+        net_wm_window_type => b"_NET_WM_WINDOW_TYPE",
+        net_wm_window_type_desktop => b"_NET_WM_WINDOW_TYPE_DESKTOP",
+        net_wm_window_type_dock => b"_NET_WM_WINDOW_TYPE_DOCK",
+        net_wm_window_type_toolbar => b"_NET_WM_WINDOW_TYPE_TOOLBAR",
+        net_wm_window_type_menu => b"_NET_WM_WINDOW_TYPE_MENU",
+        net_wm_window_type_splash => b"_NET_WM_WINDOW_TYPE_SPLASH",
+        net_wm_window_type_notification => b"_NET_WM_WINDOW_TYPE_NOTIFICATION",
+        utf8_string => b"UTF8_STRING",
+    }
+}
+
+impl EwmhAtoms {
+    /// Window types owned by the desktop shell rather than applications.
+    fn is_shell_type(&self, window_type: x::Atom) -> bool {
+        window_type != x::ATOM_NONE
+            && [
+                self.net_wm_window_type_desktop,
+                self.net_wm_window_type_dock,
+                self.net_wm_window_type_toolbar,
+                self.net_wm_window_type_menu,
+                self.net_wm_window_type_splash,
+                self.net_wm_window_type_notification,
+            ]
+            .contains(&window_type)
+    }
+}
+
+/// Live capture for X11 sessions. Monitors are grabbed through `xcap` so ids
+/// line up with `list_monitors`; top-level windows are enumerated via EWMH.
+#[derive(Debug, Default)]
+pub struct X11FrameSource;
+
+impl X11FrameSource {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+#[async_trait]
+impl FrameSource for X11FrameSource {
+    async fn capture_frame(
+        &self,
+        monitor_ids: &[u32],
+        capture_unfocused_windows: bool,
+        window_filters: &WindowFilters,
+    ) -> Result<Option<RawCapture>> {
+        debug!(?monitor_ids, "capturing frame via X11");
+
+        let monitors = capture_monitor_frames(monitor_ids).await?;
+
+        let filters = window_filters.clone();
+        let windows = match task::spawn_blocking(move || {
+            capture_client_windows(capture_unfocused_windows, &filters)
+        })
+        .await?
+        {
+            Ok(captured) => captured,
+            Err(err) => {
+                warn!("failed to capture window set: {err}");
+                Vec::new()
+            }
+        };
+
+        Ok(Some(RawCapture {
+            monitors,
+            windows,
+            timestamp_ms: None,
+        }))
+    }
+
+    async fn list_monitors(&self) -> Result<Option<Vec<MonitorInfo>>> {
+        connected_monitors().await.map(Some)
+    }
+
+    fn name(&self) -> &'static str {
+        "x11"
+    }
+}
+
+fn capture_client_windows(
+    capture_unfocused_windows: bool,
+    window_filters: &WindowFilters,
+) -> Result<Vec<CapturedWindow>> {
+    let (conn, screen_num) =
+        xcb::Connection::connect(None).context("failed to connect to X server")?;
+    let setup = conn.get_setup();
+    let root = setup
+        .roots()
+        .nth(screen_num as usize)
+        .ok_or_else(|| anyhow!("X screen {screen_num} not found"))?
+        .root();
+
+    let atoms = EwmhAtoms::intern_all(&conn)?;
+    if atoms.net_client_list == x::ATOM_NONE {
+        return Err(anyhow!("window manager does not publish _NET_CLIENT_LIST"));
+    }
+
+    let clients: Vec<x::Window> =
+        get_property(&conn, root, atoms.net_client_list, x::ATOM_WINDOW)?;
+    let active = get_property::<x::Window>(&conn, root, atoms.net_active_window, x::ATOM_WINDOW)?
+        .first()
+        .copied();
+    // Bottom-to-top, so the index is the z-order.
+    let stacking: Vec<x::Window> =
+        get_property(&conn, root, atoms.net_client_list_stacking, x::ATOM_WINDOW)
+            .unwrap_or_default();
+
+    let mut captured = Vec::new();
+    trace!(clients = clients.len(), "processing EWMH client windows");
+    for window in clients {
+        let window_types: Vec<x::Atom> =
+            get_property(&conn, window, atoms.net_wm_window_type, x::ATOM_ATOM).unwrap_or_default();
+        if window_types.iter().any(|t| atoms.is_shell_type(*t)) {
+            debug!("skipping shell window by EWMH type");
+            continue;
+        }
+
+        let title = read_title(&conn, &atoms, window);
+        let process_id = get_property::<u32>(&conn, window, atoms.net_wm_pid, x::ATOM_CARDINAL)
+            .ok()
+            .and_then(|pid| pid.first().copied())
+            .unwrap_or_default() as i32;
+        let (instance, class) = read_wm_class(&conn, window);
+        let app_name = match class.or_else(|| process_name(process_id)) {
+            Some(name) => name,
+            None => {
+                debug!("skipping window without app name ({title})");
+                continue;
+            }
+        };
+
+        let shell_key = instance.as_deref().unwrap_or(&app_name);
+        if is_system_window(shell_key, &title) {
+            debug!("skipping known system window");
+            continue;
+        }
+
+        if !window_filters.is_valid(&app_name, &title) {
+            continue;
+        }
+
+        if is_minimized(&conn, &atoms, window) {
+            debug!("skipping minimized window {app_name} ({title})");
+            continue;
+        }
+
+        let is_focused = active == Some(window);
+        if !capture_unfocused_windows && !is_focused {
+            continue;
+        }
+
+        let bounds = window_bounds(&conn, root, window);
+        let image = match capture_window_image(&conn, window) {
+            Ok(image) => image,
+            Err(err) => {
+                warn!("failed to capture window image for {app_name}: {err}");
+                continue;
+            }
+        };
+
+        captured.push(CapturedWindow {
+            image,
+            app_name,
+            window_name: title,
+            process_id,
+            executable_path: executable_path(process_id),
+            is_focused,
+            bounds,
+            z_order: stacking
+                .iter()
+                .position(|stacked| *stacked == window)
+                .map(|index| index as i32),
+        });
+    }
+
+    Ok(captured)
+}
+
+/// Read a property, returning an empty list when it is missing or has an
+/// unexpected format.
+fn get_property<P: x::PropEl + Copy>(
+    conn: &xcb::Connection,
+    window: x::Window,
+    property: x::Atom,
+    r#type: x::Atom,
+) -> Result<Vec<P>> {
+    if property == x::ATOM_NONE {
+        return Ok(Vec::new());
+    }
+
+    let reply = conn.wait_for_reply(conn.send_request(&x::GetProperty {
+        delete: false,
+        window,
+        property,
+        r#type,
+        long_offset: 0,
+        long_length: u32::MAX,
+    }))?;
+
+    if reply.format() != P::FORMAT {
+        return Ok(Vec::new());
+    }
+    Ok(reply.value::<P>().to_vec())
+}
+
+fn read_title(conn: &xcb::Connection, atoms: &EwmhAtoms, window: x::Window) -> String {
+    let utf8 = get_property::<u8>(conn, window, atoms.net_wm_name, atoms.utf8_string)
+        .unwrap_or_default();
+    let raw = if utf8.is_empty() {
+        get_property::<u8>(conn, window, x::ATOM_WM_NAME, x::ATOM_ANY).unwrap_or_default()
+    } else {
+        utf8
+    };
+    String::from_utf8_lossy(&raw).trim().to_string()
+}
+
+/// Split `WM_CLASS` into its (instance, class) pair.
+fn read_wm_class(conn: &xcb::Connection, window: x::Window) -> (Option<String>, Option<String>) {
+    let raw = get_property::<u8>(conn, window, x::ATOM_WM_CLASS, x::ATOM_STRING).unwrap_or_default();
+    let mut parts = raw
+        .split(|b| *b == 0)
+        .filter(|part| !part.is_empty())
+        .map(|part| String::from_utf8_lossy(part).to_string());
+    (parts.next(), parts.next())
+}
+
+fn process_name(process_id: i32) -> Option<String> {
+    if process_id <= 0 {
+        return None;
+    }
+    fs::read_to_string(format!("/proc/{process_id}/comm"))
+        .ok()
+        .map(|name| name.trim().to_string())
+        .filter(|name| !name.is_empty())
+}
+
+fn is_minimized(conn: &xcb::Connection, atoms: &EwmhAtoms, window: x::Window) -> bool {
+    let hidden = get_property::<x::Atom>(conn, window, atoms.net_wm_state, x::ATOM_ATOM)
+        .map(|state| state.contains(&atoms.net_wm_state_hidden))
+        .unwrap_or(false);
+    if hidden {
+        return true;
+    }
+
+    conn.wait_for_reply(conn.send_request(&x::GetWindowAttributes { window }))
+        .map(|attrs| attrs.map_state() != x::MapState::Viewable)
+        .unwrap_or(true)
+}
+
+/// Window geometry translated into root (screen) coordinates.
+fn window_bounds(conn: &xcb::Connection, root: x::Window, window: x::Window) -> Option<ScreenRect> {
+    let geometry = conn
+        .wait_for_reply(conn.send_request(&x::GetGeometry {
+            drawable: x::Drawable::Window(window),
+        }))
+        .ok()?;
+    let origin = conn
+        .wait_for_reply(conn.send_request(&x::TranslateCoordinates {
+            src_window: window,
+            dst_window: root,
+            src_x: 0,
+            src_y: 0,
+        }))
+        .ok()?;
+
+    Some(ScreenRect {
+        x: i32::from(origin.dst_x()),
+        y: i32::from(origin.dst_y()),
+        width: u32::from(geometry.width()),
+        height: u32::from(geometry.height()),
+    })
+}
+
+fn capture_window_image(conn: &xcb::Connection, window: x::Window) -> Result<DynamicImage> {
+    let drawable = x::Drawable::Window(window);
+    let geometry = conn.wait_for_reply(conn.send_request(&x::GetGeometry { drawable }))?;
+    let (width, height) = (geometry.width(), geometry.height());
+    if width == 0 || height == 0 {
+        return Err(anyhow!("window has invalid dimensions"));
+    }
+
+    let reply = conn.wait_for_reply(conn.send_request(&x::GetImage {
+        format: x::ImageFormat::ZPixmap,
+        drawable,
+        x: 0,
+        y: 0,
+        width,
+        height,
+        plane_mask: u32::MAX,
+    }))?;
+
+    let setup = conn.get_setup();
+    let depth = reply.depth();
+    let bits_per_pixel = setup
+        .pixmap_formats()
+        .iter()
+        .find(|format| format.depth() == depth)
+        .map(|format| format.bits_per_pixel())
+        .ok_or_else(|| anyhow!("no pixmap format for depth {depth}"))?;
+    if !matches!(depth, 24 | 32) || bits_per_pixel != 32 {
+        return Err(anyhow!(
+            "unsupported X image format: depth {depth}, {bits_per_pixel} bpp"
+        ));
+    }
+
+    // 32bpp ZPixmap scanlines need no padding; pixels are BGRX (LSB) or XRGB (MSB).
+    let msb_first = setup.image_byte_order() == x::ImageOrder::MsbFirst;
+    let mut rgba = Vec::with_capacity(width as usize * height as usize * 4);
+    for pixel in reply.data().chunks_exact(4) {
+        let (r, g, b) = if msb_first {
+            (pixel[1], pixel[2], pixel[3])
+        } else {
+            (pixel[2], pixel[1], pixel[0])
+        };
+        rgba.extend_from_slice(&[r, g, b, 255]);
+    }
+
+    RgbaImage::from_raw(width as u32, height as u32, rgba)
+        .map(DynamicImage::ImageRgba8)
+        .ok_or_else(|| anyhow!("failed to convert window buffer"))
+}
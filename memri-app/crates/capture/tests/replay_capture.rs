@@ -0,0 +1,246 @@
+//! Drives the capture loop end to end from a recorded replay: change
+//! detection, window reuse, the OCR pool, redaction and image storage, with
+//! an in-memory sink and an engine that returns fixed text.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use anyhow::Result;
+use async_trait::async_trait;
+use image::{Rgba, RgbaImage};
+use memri_capture::{
+    start_capture, CaptureConfig, DetectionSettings, ReplayFrameSource, ReplayMode,
+};
+use memri_config::{
+    CapturePolicyConfig, DetectionConfig, ImageConfig, PreprocessConfig, PrivacyConfig,
+    RedactionConfig, ScheduleConfig, SettleConfig,
+};
+use memri_ocr::{OcrContext, OcrEngine, OcrLayout, OcrPayload, RgbaFrame};
+use memri_storage::{CaptureBatch, CaptureSink, OcrStatus, PersistedBatch, WindowOcrUpdate};
+use serde_json::json;
+use tokio::sync::broadcast;
+
+/// Keeps every batch and OCR update, numbering windows from 1.
+#[derive(Default)]
+struct MemorySink {
+    batches: Mutex<Vec<CaptureBatch>>,
+    updates: Mutex<Vec<(i64, WindowOcrUpdate)>>,
+    next_window_id: AtomicUsize,
+}
+
+#[async_trait]
+impl CaptureSink for MemorySink {
+    async fn persist_batch(&self, batch: CaptureBatch) -> Result<PersistedBatch> {
+        let window_ids = batch
+            .windows
+            .iter()
+            .map(|_| self.next_window_id.fetch_add(1, Ordering::SeqCst) as i64 + 1)
+            .collect();
+        let mut batches = self.batches.lock().unwrap();
+        batches.push(batch);
+        Ok(PersistedBatch {
+            capture_id: batches.len() as i64,
+            window_ids,
+        })
+    }
+
+    async fn update_window_ocr(&self, window_id: i64, update: WindowOcrUpdate) -> Result<()> {
+        self.updates.lock().unwrap().push((window_id, update));
+        Ok(())
+    }
+}
+
+/// Reads the same card number in every window.
+#[derive(Default)]
+struct FixedTextOcr {
+    calls: AtomicUsize,
+}
+
+#[async_trait]
+impl OcrEngine for FixedTextOcr {
+    async fn recognize(&self, _image_bytes: &[u8], _context: &OcrContext) -> Result<OcrPayload> {
+        unreachable!("the capture loop hands windows over as pixels")
+    }
+
+    async fn recognize_rgba(
+        &self,
+        frame: RgbaFrame<'_>,
+        _context: &OcrContext,
+    ) -> Result<OcrPayload> {
+        self.calls.fetch_add(1, Ordering::SeqCst);
+        Ok(OcrPayload {
+            text: "card 4111 1111 1111 1111".to_string(),
+            confidence: Some(0.9),
+            layout: OcrLayout {
+                engine: self.name().to_string(),
+                width: frame.width,
+                height: frame.height,
+                ..OcrLayout::default()
+            },
+        })
+    }
+
+    fn name(&self) -> &'static str {
+        "fixed"
+    }
+}
+
+/// A white image with a dark block covering `share` of its width.
+fn save_image(dir: &Path, name: &str, width: u32, height: u32, share: f32) {
+    let edge = (width as f32 * share) as u32;
+    let image = RgbaImage::from_fn(width, height, |x, _| {
+        if x < edge {
+            Rgba([20, 20, 20, 255])
+        } else {
+            Rgba([250, 250, 250, 255])
+        }
+    });
+    image.save(dir.join(name)).unwrap();
+}
+
+/// Three frames: the baseline, an identical frame that change detection
+/// skips, and a frame where the screen changed and a terminal opened while
+/// the editor stayed the same.
+fn write_recording(dir: &Path) {
+    save_image(dir, "screen-1.png", 320, 200, 0.1);
+    save_image(dir, "screen-2.png", 320, 200, 0.8);
+    save_image(dir, "editor.png", 120, 80, 0.3);
+    save_image(dir, "terminal.png", 120, 80, 0.6);
+
+    let editor = json!({
+        "image": "editor.png",
+        "app_name": "Editor",
+        "window_name": "notes.txt",
+        "is_focused": true,
+        "bounds": { "x": 0, "y": 0, "width": 120, "height": 80 },
+    });
+    let terminal = json!({
+        "image": "terminal.png",
+        "app_name": "Terminal",
+        "window_name": "shell",
+        "bounds": { "x": 160, "y": 0, "width": 120, "height": 80 },
+    });
+    let manifest = json!({
+        "frames": [
+            { "timestamp_ms": 1_000, "monitor_image": "screen-1.png", "windows": [editor] },
+            { "timestamp_ms": 2_000, "monitor_image": "screen-1.png", "windows": [editor] },
+            {
+                "timestamp_ms": 3_000,
+                "monitor_image": "screen-2.png",
+                "windows": [editor, terminal],
+            },
+        ],
+    });
+    fs::write(dir.join("manifest.json"), manifest.to_string()).unwrap();
+}
+
+fn capture_config(image_dir: PathBuf) -> CaptureConfig {
+    CaptureConfig {
+        monitor_ids: vec![0],
+        capture_all_monitors: false,
+        monitor_poll_interval: Duration::ZERO,
+        interval: Duration::from_millis(100),
+        max_interval: Duration::from_millis(400),
+        capture_unfocused_windows: true,
+        languages: vec!["eng".to_string()],
+        window_include: Vec::new(),
+        window_ignore: Vec::new(),
+        image_dir,
+        detection: DetectionSettings::new(DetectionConfig::default()),
+        dedupe_window: Duration::ZERO,
+        dedupe_max_distance: 0,
+        redaction: RedactionConfig::default(),
+        privacy: PrivacyConfig {
+            enabled: false,
+            pause_on_focus: false,
+            apps: Vec::new(),
+            titles: Vec::new(),
+        },
+        schedule: ScheduleConfig::default(),
+        ocr_workers: 2,
+        ocr_queue_capacity: 8,
+        ocr_backpressure: "block".to_string(),
+        images: ImageConfig::default(),
+        preprocess: PreprocessConfig::default(),
+        settle: SettleConfig {
+            enabled: false,
+            interval_ms: 0,
+            stable_frames: 0,
+            timeout_ms: 0,
+        },
+        policy: CapturePolicyConfig {
+            name: "exponential".to_string(),
+            burst_interval_ms: 0,
+            background_interval_secs: 0,
+            keyframe_minutes: 0,
+        },
+    }
+}
+
+#[tokio::test]
+async fn replayed_frames_are_stored_recognized_and_redacted() {
+    let dir = std::env::temp_dir().join(format!("memri-replay-test-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    write_recording(&dir);
+
+    let source = Arc::new(ReplayFrameSource::open(&dir, ReplayMode::AsFastAsPossible).unwrap());
+    let engine = Arc::new(FixedTextOcr::default());
+    let sink = Arc::new(MemorySink::default());
+    let (events, _) = broadcast::channel(16);
+    let handle = start_capture(
+        capture_config(dir.join("images")),
+        source,
+        engine.clone(),
+        sink.clone(),
+        events,
+    )
+    .await
+    .unwrap();
+
+    // The loop stops by itself once the replay is exhausted, after storing
+    // the OCR results still in flight.
+    tokio::time::timeout(Duration::from_secs(30), async {
+        while handle.status().await.is_ok() {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await
+    .expect("capture loop did not finish the replay");
+
+    let batches = sink.batches.lock().unwrap();
+    let timestamps: Vec<i64> = batches.iter().map(|batch| batch.timestamp_ms).collect();
+    assert_eq!(timestamps, [1_000, 3_000], "the unchanged frame is skipped");
+    assert_eq!(batches[1].frame_number, batches[0].frame_number + 1);
+
+    let first = &batches[0].windows;
+    assert_eq!(first.len(), 1);
+    assert_eq!(first[0].ocr_status, OcrStatus::Pending);
+
+    // The unchanged editor points at its first row; only the terminal is new.
+    let second = &batches[1].windows;
+    assert_eq!(second.len(), 2);
+    assert_eq!(second[0].app_name, "Editor");
+    assert_eq!(second[0].reused_window_id, Some(1));
+    assert_eq!(second[1].app_name, "Terminal");
+    assert_eq!(second[1].reused_window_id, None);
+    assert_eq!(engine.calls.load(Ordering::SeqCst), 2);
+
+    let mut updates = sink.updates.lock().unwrap().clone();
+    updates.sort_by_key(|(window_id, _)| *window_id);
+    let window_ids: Vec<i64> = updates.iter().map(|(window_id, _)| *window_id).collect();
+    assert_eq!(window_ids, [1, 3]);
+    for (_, update) in &updates {
+        assert_eq!(update.status, OcrStatus::Done);
+        assert_eq!(update.text, "card [REDACTED]");
+        assert_eq!(update.ocr_engine.as_deref(), Some("fixed"));
+        let image_path = update.image_path.as_deref().expect("window image stored");
+        assert!(Path::new(image_path).exists());
+    }
+
+    drop(batches);
+    let _ = fs::remove_dir_all(&dir);
+}
@@ -0,0 +1,145 @@
+//! Change-detection thresholds and the per-app profiles that override them.
+
+use serde::{Deserialize, Serialize};
+
+use crate::file_loader::{DetectionProfileSection, DetectionSection};
+
+pub const DEFAULT_HISTOGRAM_THRESHOLD: f32 = 0.08;
+pub const DEFAULT_SSIM_THRESHOLD: f32 = 0.92;
+pub const DEFAULT_SSIM_SAMPLE_SIZE: u32 = 96;
+pub const DEFAULT_MIN_CHANGED_AREA: u64 = 0;
+pub const DEFAULT_TILE_PIXEL_DELTA: u8 = 32;
+pub const DEFAULT_TILE_CHANGE_FRACTION: f32 = 0.04;
+
+/// Thresholds deciding whether a frame differs enough from the previous one.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct DetectionThresholds {
+    /// Total variation distance between luma histograms that counts as a change.
+    pub histogram_threshold: f32,
+    /// SSIM score at or below which frames are considered different.
+    pub ssim_threshold: f32,
+    /// Edge length of the downsampled image used for SSIM.
+    pub ssim_sample_size: u32,
+    /// Minimum total area (in pixels) of changed tiles for a local change to count.
+    pub min_changed_area: u64,
+    /// Per-sample luma difference (0-255) that counts a tile sample as changed.
+    #[serde(default = "default_tile_pixel_delta")]
+    pub tile_pixel_delta: u8,
+    /// Share of a tile's samples that must change for the tile to count.
+    #[serde(default = "default_tile_change_fraction")]
+    pub tile_change_fraction: f32,
+}
+
+fn default_tile_pixel_delta() -> u8 {
+    DEFAULT_TILE_PIXEL_DELTA
+}
+
+fn default_tile_change_fraction() -> f32 {
+    DEFAULT_TILE_CHANGE_FRACTION
+}
+
+impl Default for DetectionThresholds {
+    fn default() -> Self {
+        Self {
+            histogram_threshold: DEFAULT_HISTOGRAM_THRESHOLD,
+            ssim_threshold: DEFAULT_SSIM_THRESHOLD,
+            ssim_sample_size: DEFAULT_SSIM_SAMPLE_SIZE,
+            min_changed_area: DEFAULT_MIN_CHANGED_AREA,
+            tile_pixel_delta: DEFAULT_TILE_PIXEL_DELTA,
+            tile_change_fraction: DEFAULT_TILE_CHANGE_FRACTION,
+        }
+    }
+}
+
+/// Thresholds applied while a matching app or window title has focus.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DetectionProfile {
+    pub name: String,
+    /// Case-insensitive substrings matched against the focused app name.
+    #[serde(default)]
+    pub match_apps: Vec<String>,
+    /// Case-insensitive substrings matched against the focused window title.
+    #[serde(default)]
+    pub match_titles: Vec<String>,
+    pub thresholds: DetectionThresholds,
+}
+
+impl DetectionProfile {
+    pub fn matches(&self, app_name: &str, window_name: &str) -> bool {
+        let app_name = app_name.to_lowercase();
+        let window_name = window_name.to_lowercase();
+        self.match_apps
+            .iter()
+            .any(|pattern| app_name.contains(&pattern.to_lowercase()))
+            || self
+                .match_titles
+                .iter()
+                .any(|pattern| window_name.contains(&pattern.to_lowercase()))
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct DetectionConfig {
+    /// Thresholds used when no profile matches.
+    pub defaults: DetectionThresholds,
+    /// Profiles checked in order; the first match wins.
+    #[serde(default)]
+    pub profiles: Vec<DetectionProfile>,
+}
+
+impl DetectionConfig {
+    /// Resolve the profile for the focused window, if any.
+    pub fn profile_for(&self, app_name: &str, window_name: &str) -> Option<&DetectionProfile> {
+        self.profiles
+            .iter()
+            .find(|profile| profile.matches(app_name, window_name))
+    }
+
+    pub fn thresholds_for(&self, app_name: &str, window_name: &str) -> DetectionThresholds {
+        self.profile_for(app_name, window_name)
+            .map(|profile| profile.thresholds)
+            .unwrap_or(self.defaults)
+    }
+}
+
+pub(crate) fn profiles_from_section(
+    section: Option<DetectionSection>,
+    defaults: DetectionThresholds,
+) -> Vec<DetectionProfile> {
+    section
+        .map(|section| section.profiles)
+        .unwrap_or_default()
+        .into_iter()
+        .map(|profile| profile_from_section(profile, defaults))
+        .collect()
+}
+
+/// Fields missing from a profile fall back to the global thresholds.
+fn profile_from_section(
+    section: DetectionProfileSection,
+    defaults: DetectionThresholds,
+) -> DetectionProfile {
+    DetectionProfile {
+        name: section.name,
+        match_apps: section.match_apps,
+        match_titles: section.match_titles,
+        thresholds: DetectionThresholds {
+            histogram_threshold: section
+                .histogram_threshold
+                .unwrap_or(defaults.histogram_threshold),
+            ssim_threshold: section.ssim_threshold.unwrap_or(defaults.ssim_threshold),
+            ssim_sample_size: section
+                .ssim_sample_size
+                .unwrap_or(defaults.ssim_sample_size),
+            min_changed_area: section
+                .min_changed_area
+                .unwrap_or(defaults.min_changed_area),
+            tile_pixel_delta: section
+                .tile_pixel_delta
+                .unwrap_or(defaults.tile_pixel_delta),
+            tile_change_fraction: section
+                .tile_change_fraction
+                .unwrap_or(defaults.tile_change_fraction),
+        },
+    }
+}
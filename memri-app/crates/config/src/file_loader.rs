@@ -3,6 +3,9 @@ use std::{env, fs, path::Path};
 use anyhow::Result;
 use serde::Deserialize;
 
+use crate::redaction::MaskRect;
+use crate::schedule::ScheduleWindow;
+
 /// Configuration loaded from `memri-config.toml` (or `memri.config.toml`) at repo root.
 /// All fields are optional; if present they will populate environment variables
 /// consumed by the rest of the app to avoid `.env` usage.
@@ -12,12 +15,32 @@ pub struct FileConfig {
     pub app: AppSection,
     #[serde(default)]
     pub api: ApiSection,
+    #[serde(default)]
+    pub detection: DetectionSection,
+    #[serde(default)]
+    pub redaction: RedactionSection,
+    #[serde(default)]
+    pub privacy: PrivacySection,
+    #[serde(default)]
+    pub schedule: ScheduleSection,
+    #[serde(default)]
+    pub ocr: OcrSection,
+    #[serde(default)]
+    pub images: ImagesSection,
+    #[serde(default)]
+    pub preprocess: PreprocessSection,
+    #[serde(default)]
+    pub settle: SettleSection,
+    #[serde(default)]
+    pub policy: PolicySection,
 }
 
 #[derive(Debug, Default, Deserialize)]
 pub struct AppSection {
     pub monitor_id: Option<u32>,
     pub monitor_ids: Option<Vec<u32>>,
+    pub capture_all_monitors: Option<bool>,
+    pub monitor_poll_secs: Option<u64>,
     pub capture_interval_ms: Option<u64>,
     pub capture_max_interval_ms: Option<u64>,
     pub capture_unfocused_windows: Option<bool>,
@@ -28,6 +51,158 @@ pub struct AppSection {
     pub retention_days: Option<u64>,
     pub max_captures: Option<u64>,
     pub image_dir: Option<String>,
+    pub capture_source: Option<String>,
+    pub replay_path: Option<String>,
+    pub replay_speed: Option<String>,
+    pub dedupe_window_minutes: Option<u64>,
+    pub dedupe_max_distance: Option<u32>,
+}
+
+/// Global change-detection thresholds plus per-app profiles. Profiles can't be
+/// expressed as env vars, so they are read straight from the file.
+#[derive(Debug, Default, Deserialize)]
+pub struct DetectionSection {
+    pub histogram_threshold: Option<f32>,
+    pub ssim_threshold: Option<f32>,
+    pub ssim_sample_size: Option<u32>,
+    pub min_changed_area: Option<u64>,
+    pub tile_pixel_delta: Option<u8>,
+    pub tile_change_fraction: Option<f32>,
+    #[serde(default)]
+    pub profiles: Vec<DetectionProfileSection>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct DetectionProfileSection {
+    pub name: String,
+    #[serde(default)]
+    pub match_apps: Vec<String>,
+    #[serde(default)]
+    pub match_titles: Vec<String>,
+    pub histogram_threshold: Option<f32>,
+    pub ssim_threshold: Option<f32>,
+    pub ssim_sample_size: Option<u32>,
+    pub min_changed_area: Option<u64>,
+    pub tile_pixel_delta: Option<u8>,
+    pub tile_change_fraction: Option<f32>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct RedactionSection {
+    pub enabled: Option<bool>,
+    pub builtin_patterns: Option<bool>,
+    #[serde(default)]
+    pub patterns: Vec<String>,
+    #[serde(default)]
+    pub masks: Vec<MaskRect>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct PrivacySection {
+    pub enabled: Option<bool>,
+    pub pause_on_focus: Option<bool>,
+    pub apps: Option<Vec<String>>,
+    pub titles: Option<Vec<String>>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct ScheduleSection {
+    pub enabled: Option<bool>,
+    #[serde(default)]
+    pub windows: Vec<ScheduleWindow>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct OcrSection {
+    pub workers: Option<usize>,
+    pub queue_capacity: Option<usize>,
+    pub backpressure: Option<String>,
+    pub engine: Option<String>,
+    pub engines: Option<Vec<String>>,
+    pub strategy: Option<String>,
+    pub min_confidence: Option<f32>,
+    pub cache_capacity: Option<usize>,
+    pub cache_persistent: Option<bool>,
+    pub cache_max_entries: Option<u64>,
+    pub tesseract_data_path: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct SettleSection {
+    pub enabled: Option<bool>,
+    pub interval_ms: Option<u64>,
+    pub stable_frames: Option<u32>,
+    pub timeout_ms: Option<u64>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct PolicySection {
+    pub name: Option<String>,
+    pub burst_interval_ms: Option<u64>,
+    pub background_interval_secs: Option<u64>,
+    pub keyframe_minutes: Option<u64>,
+}
+
+/// Global image encoding plus per-app profiles, which are read straight from
+/// the file like detection profiles.
+#[derive(Debug, Default, Deserialize)]
+pub struct ImagesSection {
+    pub format: Option<String>,
+    pub quality: Option<u8>,
+    pub max_width: Option<u32>,
+    pub max_height: Option<u32>,
+    #[serde(default)]
+    pub profiles: Vec<ImageProfileSection>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct ImageProfileSection {
+    pub name: String,
+    #[serde(default)]
+    pub match_apps: Vec<String>,
+    #[serde(default)]
+    pub match_titles: Vec<String>,
+    pub format: Option<String>,
+    pub quality: Option<u8>,
+    pub max_width: Option<u32>,
+    pub max_height: Option<u32>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct PreprocessSection {
+    pub grayscale: Option<bool>,
+    pub invert_dark: Option<bool>,
+    pub normalize_contrast: Option<bool>,
+    pub upscale: Option<f32>,
+    pub binarize: Option<bool>,
+    pub tile_height: Option<u32>,
+    #[serde(default)]
+    pub profiles: Vec<PreprocessProfileSection>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct PreprocessProfileSection {
+    pub name: String,
+    #[serde(default)]
+    pub match_apps: Vec<String>,
+    #[serde(default)]
+    pub match_titles: Vec<String>,
+    pub grayscale: Option<bool>,
+    pub invert_dark: Option<bool>,
+    pub normalize_contrast: Option<bool>,
+    pub upscale: Option<f32>,
+    pub binarize: Option<bool>,
+    pub tile_height: Option<u32>,
+}
+
+/// Sections with nested tables or lists that can't round-trip through env vars.
+#[derive(Debug, Default)]
+pub struct StructuredSections {
+    pub detection: DetectionSection,
+    pub redaction: RedactionSection,
+    pub schedule: ScheduleSection,
+    pub images: ImagesSection,
+    pub preprocess: PreprocessSection,
 }
 
 #[derive(Debug, Default, Deserialize)]
@@ -39,7 +214,9 @@ pub struct ApiSection {
 
 const CANDIDATES: &[&str] = &["memri-config.toml", "memri.config.toml", "config/memri-config.toml"];
 
-pub fn load_file_config_into_env() -> Result<()> {
+/// Populate missing env vars from the config file and return the sections
+/// that only exist in the file.
+pub fn load_file_config_into_env() -> Result<Option<StructuredSections>> {
     let cfg = read_first_config()?;
     if let Some(cfg) = cfg {
         set_if_missing("MEMRI_MONITOR_ID", cfg.app.monitor_id.map(|v| v.to_string()));
@@ -49,6 +226,16 @@ pub fn load_file_config_into_env() -> Result<()> {
                 .monitor_ids
                 .map(|v| v.iter().map(|n| n.to_string()).collect::<Vec<_>>().join(",")),
         );
+        set_if_missing(
+            "MEMRI_CAPTURE_ALL_MONITORS",
+            cfg.app
+                .capture_all_monitors
+                .map(|v| if v { "true".into() } else { "false".into() }),
+        );
+        set_if_missing(
+            "MEMRI_MONITOR_POLL_SECS",
+            cfg.app.monitor_poll_secs.map(|v| v.to_string()),
+        );
         set_if_missing(
             "MEMRI_CAPTURE_INTERVAL_MS",
             cfg.app.capture_interval_ms.map(|v| v.to_string()),
@@ -81,13 +268,169 @@ pub fn load_file_config_into_env() -> Result<()> {
         set_if_missing("MEMRI_RETENTION_DAYS", cfg.app.retention_days.map(|v| v.to_string()));
         set_if_missing("MEMRI_MAX_CAPTURES", cfg.app.max_captures.map(|v| v.to_string()));
         set_if_missing("MEMRI_IMAGE_DIR", cfg.app.image_dir);
+        set_if_missing("MEMRI_CAPTURE_SOURCE", cfg.app.capture_source);
+        set_if_missing("MEMRI_REPLAY_PATH", cfg.app.replay_path);
+        set_if_missing("MEMRI_REPLAY_SPEED", cfg.app.replay_speed);
+        set_if_missing(
+            "MEMRI_DEDUPE_WINDOW_MINUTES",
+            cfg.app.dedupe_window_minutes.map(|v| v.to_string()),
+        );
+        set_if_missing(
+            "MEMRI_DEDUPE_MAX_DISTANCE",
+            cfg.app.dedupe_max_distance.map(|v| v.to_string()),
+        );
 
         // API-specific vars used by backend startup.
         set_if_missing("MEMRI_API_ADDR", cfg.api.addr);
         set_if_missing("MEMRI_API_KEY", cfg.api.key);
         set_if_missing("ANTHROPIC_API_KEY", cfg.api.anthropic_api_key);
+
+        set_if_missing(
+            "MEMRI_DETECTION_HISTOGRAM_THRESHOLD",
+            cfg.detection.histogram_threshold.map(|v| v.to_string()),
+        );
+        set_if_missing(
+            "MEMRI_DETECTION_SSIM_THRESHOLD",
+            cfg.detection.ssim_threshold.map(|v| v.to_string()),
+        );
+        set_if_missing(
+            "MEMRI_DETECTION_SSIM_SAMPLE_SIZE",
+            cfg.detection.ssim_sample_size.map(|v| v.to_string()),
+        );
+        set_if_missing(
+            "MEMRI_DETECTION_MIN_CHANGED_AREA",
+            cfg.detection.min_changed_area.map(|v| v.to_string()),
+        );
+        set_if_missing(
+            "MEMRI_DETECTION_TILE_PIXEL_DELTA",
+            cfg.detection.tile_pixel_delta.map(|v| v.to_string()),
+        );
+        set_if_missing(
+            "MEMRI_DETECTION_TILE_CHANGE_FRACTION",
+            cfg.detection.tile_change_fraction.map(|v| v.to_string()),
+        );
+        set_if_missing(
+            "MEMRI_PRIVACY_ENABLED",
+            cfg.privacy.enabled.map(|v| v.to_string()),
+        );
+        set_if_missing(
+            "MEMRI_PRIVACY_PAUSE_ON_FOCUS",
+            cfg.privacy.pause_on_focus.map(|v| v.to_string()),
+        );
+        set_if_missing("MEMRI_PRIVACY_APPS", cfg.privacy.apps.map(|v| v.join(",")));
+        set_if_missing("MEMRI_PRIVACY_TITLES", cfg.privacy.titles.map(|v| v.join(",")));
+        set_if_missing("MEMRI_OCR_WORKERS", cfg.ocr.workers.map(|v| v.to_string()));
+        set_if_missing(
+            "MEMRI_OCR_QUEUE_CAPACITY",
+            cfg.ocr.queue_capacity.map(|v| v.to_string()),
+        );
+        set_if_missing("MEMRI_OCR_BACKPRESSURE", cfg.ocr.backpressure);
+        set_if_missing("MEMRI_OCR_ENGINE", cfg.ocr.engine);
+        set_if_missing("MEMRI_OCR_ENGINES", cfg.ocr.engines.map(|v| v.join(",")));
+        set_if_missing("MEMRI_OCR_STRATEGY", cfg.ocr.strategy);
+        set_if_missing(
+            "MEMRI_OCR_MIN_CONFIDENCE",
+            cfg.ocr.min_confidence.map(|v| v.to_string()),
+        );
+        set_if_missing(
+            "MEMRI_OCR_CACHE_CAPACITY",
+            cfg.ocr.cache_capacity.map(|v| v.to_string()),
+        );
+        set_if_missing(
+            "MEMRI_OCR_CACHE_PERSISTENT",
+            cfg.ocr.cache_persistent.map(|v| v.to_string()),
+        );
+        set_if_missing(
+            "MEMRI_OCR_CACHE_MAX_ENTRIES",
+            cfg.ocr.cache_max_entries.map(|v| v.to_string()),
+        );
+        set_if_missing("MEMRI_TESSERACT_DATA_PATH", cfg.ocr.tesseract_data_path);
+        set_if_missing("MEMRI_SETTLE_ENABLED", cfg.settle.enabled.map(|v| v.to_string()));
+        set_if_missing(
+            "MEMRI_SETTLE_INTERVAL_MS",
+            cfg.settle.interval_ms.map(|v| v.to_string()),
+        );
+        set_if_missing(
+            "MEMRI_SETTLE_STABLE_FRAMES",
+            cfg.settle.stable_frames.map(|v| v.to_string()),
+        );
+        set_if_missing(
+            "MEMRI_SETTLE_TIMEOUT_MS",
+            cfg.settle.timeout_ms.map(|v| v.to_string()),
+        );
+        set_if_missing("MEMRI_CAPTURE_POLICY", cfg.policy.name);
+        set_if_missing(
+            "MEMRI_BURST_INTERVAL_MS",
+            cfg.policy.burst_interval_ms.map(|v| v.to_string()),
+        );
+        set_if_missing(
+            "MEMRI_BACKGROUND_INTERVAL_SECS",
+            cfg.policy.background_interval_secs.map(|v| v.to_string()),
+        );
+        set_if_missing(
+            "MEMRI_KEYFRAME_MINUTES",
+            cfg.policy.keyframe_minutes.map(|v| v.to_string()),
+        );
+        set_if_missing("MEMRI_IMAGE_FORMAT", cfg.images.format.clone());
+        set_if_missing(
+            "MEMRI_IMAGE_QUALITY",
+            cfg.images.quality.map(|v| v.to_string()),
+        );
+        set_if_missing(
+            "MEMRI_IMAGE_MAX_WIDTH",
+            cfg.images.max_width.map(|v| v.to_string()),
+        );
+        set_if_missing(
+            "MEMRI_IMAGE_MAX_HEIGHT",
+            cfg.images.max_height.map(|v| v.to_string()),
+        );
+        set_if_missing(
+            "MEMRI_PREPROCESS_GRAYSCALE",
+            cfg.preprocess.grayscale.map(|v| v.to_string()),
+        );
+        set_if_missing(
+            "MEMRI_PREPROCESS_INVERT_DARK",
+            cfg.preprocess.invert_dark.map(|v| v.to_string()),
+        );
+        set_if_missing(
+            "MEMRI_PREPROCESS_CONTRAST",
+            cfg.preprocess.normalize_contrast.map(|v| v.to_string()),
+        );
+        set_if_missing(
+            "MEMRI_PREPROCESS_UPSCALE",
+            cfg.preprocess.upscale.map(|v| v.to_string()),
+        );
+        set_if_missing(
+            "MEMRI_PREPROCESS_BINARIZE",
+            cfg.preprocess.binarize.map(|v| v.to_string()),
+        );
+        set_if_missing(
+            "MEMRI_PREPROCESS_TILE_HEIGHT",
+            cfg.preprocess.tile_height.map(|v| v.to_string()),
+        );
+
+        set_if_missing(
+            "MEMRI_REDACTION_ENABLED",
+            cfg.redaction.enabled.map(|v| v.to_string()),
+        );
+        set_if_missing(
+            "MEMRI_REDACTION_BUILTINS",
+            cfg.redaction.builtin_patterns.map(|v| v.to_string()),
+        );
+        set_if_missing(
+            "MEMRI_SCHEDULE_ENABLED",
+            cfg.schedule.enabled.map(|v| v.to_string()),
+        );
+
+        return Ok(Some(StructuredSections {
+            detection: cfg.detection,
+            redaction: cfg.redaction,
+            schedule: cfg.schedule,
+            images: cfg.images,
+            preprocess: cfg.preprocess,
+        }));
     }
-    Ok(())
+    Ok(None)
 }
 
 fn read_first_config() -> Result<Option<FileConfig>> {
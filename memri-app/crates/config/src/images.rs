@@ -0,0 +1,97 @@
+//! How window images are encoded on disk, globally and per app.
+
+use serde::{Deserialize, Serialize};
+
+use crate::file_loader::{ImageProfileSection, ImagesSection};
+
+pub const DEFAULT_IMAGE_FORMAT: &str = "webp";
+pub const DEFAULT_IMAGE_QUALITY: u8 = 80;
+
+/// Output settings for one stored window image.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ImageEncoding {
+    /// `webp`, `jpeg`, `png` or `avif` (when built with the `avif` feature).
+    pub format: String,
+    /// Lossy quality from 1 (smallest) to 100 (best); ignored for PNG.
+    pub quality: u8,
+    /// Images larger than this are scaled down, keeping the aspect ratio (0 = no limit).
+    pub max_width: u32,
+    pub max_height: u32,
+}
+
+impl Default for ImageEncoding {
+    fn default() -> Self {
+        Self {
+            format: DEFAULT_IMAGE_FORMAT.to_string(),
+            quality: DEFAULT_IMAGE_QUALITY,
+            max_width: 0,
+            max_height: 0,
+        }
+    }
+}
+
+/// Encoding used for windows of a matching app or title.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ImageProfile {
+    pub name: String,
+    /// Case-insensitive substrings matched against the window's app name.
+    #[serde(default)]
+    pub match_apps: Vec<String>,
+    /// Case-insensitive substrings matched against the window title.
+    #[serde(default)]
+    pub match_titles: Vec<String>,
+    pub encoding: ImageEncoding,
+}
+
+impl ImageProfile {
+    pub fn matches(&self, app_name: &str, window_name: &str) -> bool {
+        let app_name = app_name.to_lowercase();
+        let window_name = window_name.to_lowercase();
+        self.match_apps
+            .iter()
+            .any(|pattern| app_name.contains(&pattern.to_lowercase()))
+            || self
+                .match_titles
+                .iter()
+                .any(|pattern| window_name.contains(&pattern.to_lowercase()))
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ImageConfig {
+    /// Encoding used when no profile matches.
+    pub defaults: ImageEncoding,
+    /// Profiles checked in order; the first match wins.
+    #[serde(default)]
+    pub profiles: Vec<ImageProfile>,
+}
+
+pub(crate) fn profiles_from_section(
+    section: Option<ImagesSection>,
+    defaults: &ImageEncoding,
+) -> Vec<ImageProfile> {
+    section
+        .map(|section| section.profiles)
+        .unwrap_or_default()
+        .into_iter()
+        .map(|profile| profile_from_section(profile, defaults))
+        .collect()
+}
+
+/// Fields missing from a profile fall back to the global settings.
+fn profile_from_section(section: ImageProfileSection, defaults: &ImageEncoding) -> ImageProfile {
+    ImageProfile {
+        name: section.name,
+        match_apps: section.match_apps,
+        match_titles: section.match_titles,
+        encoding: ImageEncoding {
+            format: section
+                .format
+                .map(|format| format.to_lowercase())
+                .unwrap_or_else(|| defaults.format.clone()),
+            quality: section.quality.unwrap_or(defaults.quality).clamp(1, 100),
+            max_width: section.max_width.unwrap_or(defaults.max_width),
+            max_height: section.max_height.unwrap_or(defaults.max_height),
+        },
+    }
+}
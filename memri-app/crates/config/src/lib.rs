@@ -9,16 +9,63 @@ use std::path::Path;
 
 use anyhow::{Context, Result};
 
+mod detection;
 mod file_loader;
+mod images;
+mod preprocess;
+mod redaction;
+mod schedule;
+
+pub use detection::{
+    DetectionConfig, DetectionProfile, DetectionThresholds, DEFAULT_HISTOGRAM_THRESHOLD,
+    DEFAULT_MIN_CHANGED_AREA, DEFAULT_SSIM_SAMPLE_SIZE, DEFAULT_SSIM_THRESHOLD,
+    DEFAULT_TILE_CHANGE_FRACTION, DEFAULT_TILE_PIXEL_DELTA,
+};
+pub use images::{
+    ImageConfig, ImageEncoding, ImageProfile, DEFAULT_IMAGE_FORMAT, DEFAULT_IMAGE_QUALITY,
+};
+pub use preprocess::{
+    PreprocessConfig, PreprocessProfile, PreprocessSteps, DEFAULT_PREPROCESS_TILE_HEIGHT,
+    DEFAULT_PREPROCESS_UPSCALE,
+};
+pub use redaction::{MaskRect, RedactionConfig};
+pub use schedule::{ScheduleConfig, ScheduleWindow};
 
 pub const DEFAULT_DATABASE_URL: &str = "sqlite://./memri.db";
 pub const DEFAULT_LANGUAGES: &str = "en";
 pub const DEFAULT_IMAGE_DIR: &str = "captures";
+pub const DEFAULT_CAPTURE_SOURCE: &str = "platform";
+pub const DEFAULT_REPLAY_SPEED: &str = "realtime";
+pub const DEFAULT_DEDUPE_WINDOW_MINUTES: u64 = 0;
+pub const DEFAULT_DEDUPE_MAX_DISTANCE: u32 = 4;
+pub const DEFAULT_MONITOR_POLL_SECS: u64 = 5;
+pub const DEFAULT_OCR_WORKERS: usize = 2;
+pub const DEFAULT_OCR_QUEUE_CAPACITY: usize = 64;
+pub const DEFAULT_OCR_BACKPRESSURE: &str = "coalesce";
+pub const DEFAULT_OCR_ENGINE: &str = "auto";
+pub const DEFAULT_OCR_ENGINES: &str = "windows,tesseract";
+pub const DEFAULT_OCR_STRATEGY: &str = "fallback";
+pub const DEFAULT_OCR_MIN_CONFIDENCE: f32 = 0.6;
+pub const DEFAULT_OCR_CACHE_CAPACITY: usize = 256;
+pub const DEFAULT_OCR_CACHE_MAX_ENTRIES: u64 = 10_000;
+pub const DEFAULT_SETTLE_INTERVAL_MS: u64 = 150;
+pub const DEFAULT_SETTLE_STABLE_FRAMES: u32 = 2;
+pub const DEFAULT_SETTLE_TIMEOUT_MS: u64 = 1500;
+pub const DEFAULT_CAPTURE_POLICY: &str = "exponential";
+pub const DEFAULT_BURST_INTERVAL_MS: u64 = 500;
+pub const DEFAULT_BACKGROUND_INTERVAL_SECS: u64 = 60;
+pub const DEFAULT_KEYFRAME_MINUTES: u64 = 10;
 
 #[derive(Debug, Clone)]
 pub struct AppConfig {
     pub monitor_id: u32,
     pub monitor_ids: Vec<u32>,
+    /// Capture every connected monitor, including ones plugged in later,
+    /// instead of only the configured ids.
+    pub capture_all_monitors: bool,
+    /// How often (seconds) connected monitors are re-enumerated to follow
+    /// hot-plugging (0 disables).
+    pub monitor_poll_secs: u64,
     pub capture_interval_ms: u64,
     /// Maximum interval after backoff when no significant changes are detected.
     pub capture_max_interval_ms: u64,
@@ -31,19 +78,128 @@ pub struct AppConfig {
     pub retention_days: u64,
     /// Maximum number of capture rows to keep (0 disables).
     pub max_captures: u64,
-    /// Directory to store captured window images (written as PNG).
+    /// Directory to store captured window images (encoded as set in `images`).
     pub image_dir: String,
+    /// Frame source for the capture loop: `platform`, `xcap`, `x11` or `replay`.
+    pub capture_source: String,
+    /// Recording directory or manifest consumed by the `replay` source.
+    pub replay_path: Option<String>,
+    /// Replay pacing: `realtime` keeps recorded spacing, `fast` replays back-to-back.
+    pub replay_speed: String,
+    /// How far back (minutes) near-duplicate windows are matched by perceptual hash (0 disables).
+    pub dedupe_window_minutes: u64,
+    /// Maximum Hamming distance between 64-bit hashes to count as a duplicate.
+    pub dedupe_max_distance: u32,
+    /// Change-detection thresholds and per-app profiles.
+    pub detection: DetectionConfig,
+    /// Patterns and fixed masks blacked out before images and text are stored.
+    pub redaction: RedactionConfig,
+    /// Private browsing, password manager and banking window handling.
+    pub privacy: PrivacyConfig,
+    /// Working-hours windows; capture pauses outside of them.
+    pub schedule: ScheduleConfig,
+    /// Background OCR worker pool.
+    pub ocr: OcrConfig,
+    /// Format, quality and size limits for stored window images.
+    pub images: ImageConfig,
+    /// Image clean-up before OCR, per app.
+    pub preprocess: PreprocessConfig,
+    /// Waiting for the screen to stop changing before a frame is stored.
+    pub settle: SettleConfig,
+    /// How the delay between ticks adapts and which frames are forced.
+    pub policy: CapturePolicyConfig,
+}
+
+#[derive(Debug, Clone)]
+pub struct OcrConfig {
+    /// Windows recognized in parallel.
+    pub workers: usize,
+    /// Windows waiting for a worker before backpressure applies.
+    pub queue_capacity: usize,
+    /// What happens when the queue is full: `drop_oldest`, `coalesce` or `block`.
+    pub backpressure: String,
+    /// `windows`, `tesseract`, `auto` to pick the best one available, or
+    /// `composite` to combine `engines`.
+    pub engine: String,
+    /// Engines combined by the `composite` engine, in order of preference.
+    pub engines: Vec<String>,
+    /// How `composite` combines them: `fallback`, `race` or `best_confidence`.
+    pub strategy: String,
+    /// Below this confidence a composite result is not good enough and the
+    /// other engines' results are considered.
+    pub min_confidence: f32,
+    /// OCR results kept in memory, keyed by image hash and languages
+    /// (0 disables the in-memory cache).
+    pub cache_capacity: usize,
+    /// Also keep results in the database, across restarts. Results that
+    /// redaction would change are only cached in memory.
+    pub cache_persistent: bool,
+    /// Rows kept by the persistent cache (0 for no limit).
+    pub cache_max_entries: u64,
+    /// Directory holding Tesseract `*.traineddata` files; `None` uses
+    /// Tesseract's own default (`TESSDATA_PREFIX`).
+    pub tesseract_data_path: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct SettleConfig {
+    /// After a significant change, keep sampling until the screen is stable
+    /// and store only that frame.
+    pub enabled: bool,
+    /// Delay between samples while settling.
+    pub interval_ms: u64,
+    /// Consecutive unchanged samples needed to count as settled.
+    pub stable_frames: u32,
+    /// Give up waiting after this long and store the latest sample.
+    pub timeout_ms: u64,
+}
+
+#[derive(Debug, Clone)]
+pub struct CapturePolicyConfig {
+    /// `exponential`, `burst`, `focused` or `keyframe`.
+    pub name: String,
+    /// Shortest delay `burst` reaches while changes keep coming.
+    pub burst_interval_ms: u64,
+    /// How long `focused` keeps a changed background window's previous result.
+    pub background_interval_secs: u64,
+    /// `keyframe` stores a frame at least this often, changed or not.
+    pub keyframe_minutes: u64,
+}
+
+#[derive(Debug, Clone)]
+pub struct PrivacyConfig {
+    /// Skip windows matching the built-in or configured privacy patterns.
+    pub enabled: bool,
+    /// Pause capture entirely while a matching window has focus.
+    pub pause_on_focus: bool,
+    /// Extra app names treated as private (case-insensitive, whole words).
+    pub apps: Vec<String>,
+    /// Extra window-title keywords treated as private (case-insensitive, whole words).
+    pub titles: Vec<String>,
 }
 
 impl AppConfig {
     pub fn from_env() -> Result<Self> {
         // 1) Load structured config file (memri-config.toml) into env (only missing keys).
-        file_loader::load_file_config_into_env().ok();
+        let file_sections = file_loader::load_file_config_into_env().ok().flatten();
+        let (file_detection, file_redaction, file_schedule, file_images, file_preprocess) =
+            match file_sections {
+                Some(sections) => (
+                    Some(sections.detection),
+                    Some(sections.redaction),
+                    Some(sections.schedule),
+                    Some(sections.images),
+                    Some(sections.preprocess),
+                ),
+                None => (None, None, None, None, None),
+            };
         // 2) Load simple note file (env.note or memri.env.note) as a last resort.
         load_note_env();
 
         let monitor_id = read_env_u32("MEMRI_MONITOR_ID", 0)?;
         let monitor_ids = read_env_list_u32("MEMRI_MONITOR_IDS");
+        let capture_all_monitors = read_env_bool("MEMRI_CAPTURE_ALL_MONITORS", false)?;
+        let monitor_poll_secs = read_env_u64("MEMRI_MONITOR_POLL_SECS", DEFAULT_MONITOR_POLL_SECS)?;
         let capture_interval_ms = read_env_u64("MEMRI_CAPTURE_INTERVAL_MS", 2000)?;
         // Default to a 4x backoff window unless explicitly overridden.
         let capture_max_interval_ms =
@@ -58,10 +214,146 @@ impl AppConfig {
         let max_captures = read_env_u64("MEMRI_MAX_CAPTURES", 5_000)?;
         let image_dir =
             env::var("MEMRI_IMAGE_DIR").unwrap_or_else(|_| DEFAULT_IMAGE_DIR.to_string());
+        let capture_source = env::var("MEMRI_CAPTURE_SOURCE")
+            .map(|v| v.to_lowercase())
+            .unwrap_or_else(|_| DEFAULT_CAPTURE_SOURCE.to_string());
+        let replay_path = env::var("MEMRI_REPLAY_PATH")
+            .ok()
+            .filter(|v| !v.trim().is_empty());
+        let replay_speed =
+            env::var("MEMRI_REPLAY_SPEED").unwrap_or_else(|_| DEFAULT_REPLAY_SPEED.to_string());
+        let dedupe_window_minutes =
+            read_env_u64("MEMRI_DEDUPE_WINDOW_MINUTES", DEFAULT_DEDUPE_WINDOW_MINUTES)?;
+        let dedupe_max_distance =
+            read_env_u32("MEMRI_DEDUPE_MAX_DISTANCE", DEFAULT_DEDUPE_MAX_DISTANCE)?;
+        let detection_defaults = DetectionThresholds {
+            histogram_threshold: read_env_f32(
+                "MEMRI_DETECTION_HISTOGRAM_THRESHOLD",
+                DEFAULT_HISTOGRAM_THRESHOLD,
+            )?,
+            ssim_threshold: read_env_f32("MEMRI_DETECTION_SSIM_THRESHOLD", DEFAULT_SSIM_THRESHOLD)?,
+            ssim_sample_size: read_env_u32(
+                "MEMRI_DETECTION_SSIM_SAMPLE_SIZE",
+                DEFAULT_SSIM_SAMPLE_SIZE,
+            )?,
+            min_changed_area: read_env_u64(
+                "MEMRI_DETECTION_MIN_CHANGED_AREA",
+                DEFAULT_MIN_CHANGED_AREA,
+            )?,
+            tile_pixel_delta: read_env_u32(
+                "MEMRI_DETECTION_TILE_PIXEL_DELTA",
+                DEFAULT_TILE_PIXEL_DELTA as u32,
+            )?
+            .min(u8::MAX as u32) as u8,
+            tile_change_fraction: read_env_f32(
+                "MEMRI_DETECTION_TILE_CHANGE_FRACTION",
+                DEFAULT_TILE_CHANGE_FRACTION,
+            )?,
+        };
+        let detection = DetectionConfig {
+            defaults: detection_defaults,
+            profiles: detection::profiles_from_section(file_detection, detection_defaults),
+        };
+        let privacy = PrivacyConfig {
+            enabled: read_env_bool("MEMRI_PRIVACY_ENABLED", true)?,
+            pause_on_focus: read_env_bool("MEMRI_PRIVACY_PAUSE_ON_FOCUS", false)?,
+            apps: read_env_list("MEMRI_PRIVACY_APPS", ""),
+            titles: read_env_list("MEMRI_PRIVACY_TITLES", ""),
+        };
+        let redaction = RedactionConfig {
+            enabled: read_env_bool("MEMRI_REDACTION_ENABLED", true)?,
+            builtin_patterns: read_env_bool("MEMRI_REDACTION_BUILTINS", true)?,
+            ..RedactionConfig::default()
+        }
+        .with_section(file_redaction);
+        let ocr = OcrConfig {
+            workers: read_env_usize("MEMRI_OCR_WORKERS", DEFAULT_OCR_WORKERS)?.max(1),
+            queue_capacity: read_env_usize("MEMRI_OCR_QUEUE_CAPACITY", DEFAULT_OCR_QUEUE_CAPACITY)?
+                .max(1),
+            backpressure: env::var("MEMRI_OCR_BACKPRESSURE")
+                .map(|v| v.to_lowercase())
+                .unwrap_or_else(|_| DEFAULT_OCR_BACKPRESSURE.to_string()),
+            engine: env::var("MEMRI_OCR_ENGINE")
+                .map(|v| v.to_lowercase())
+                .unwrap_or_else(|_| DEFAULT_OCR_ENGINE.to_string()),
+            engines: read_env_list("MEMRI_OCR_ENGINES", DEFAULT_OCR_ENGINES)
+                .into_iter()
+                .map(|v| v.to_lowercase())
+                .collect(),
+            strategy: env::var("MEMRI_OCR_STRATEGY")
+                .map(|v| v.to_lowercase())
+                .unwrap_or_else(|_| DEFAULT_OCR_STRATEGY.to_string()),
+            min_confidence: read_env_f32("MEMRI_OCR_MIN_CONFIDENCE", DEFAULT_OCR_MIN_CONFIDENCE)?
+                .clamp(0.0, 1.0),
+            cache_capacity: read_env_usize("MEMRI_OCR_CACHE_CAPACITY", DEFAULT_OCR_CACHE_CAPACITY)?,
+            cache_persistent: read_env_bool("MEMRI_OCR_CACHE_PERSISTENT", false)?,
+            cache_max_entries: read_env_u64(
+                "MEMRI_OCR_CACHE_MAX_ENTRIES",
+                DEFAULT_OCR_CACHE_MAX_ENTRIES,
+            )?,
+            tesseract_data_path: env::var("MEMRI_TESSERACT_DATA_PATH")
+                .ok()
+                .filter(|v| !v.trim().is_empty()),
+        };
+        let image_defaults = ImageEncoding {
+            format: env::var("MEMRI_IMAGE_FORMAT")
+                .map(|v| v.to_lowercase())
+                .unwrap_or_else(|_| DEFAULT_IMAGE_FORMAT.to_string()),
+            quality: read_env_u32("MEMRI_IMAGE_QUALITY", u32::from(DEFAULT_IMAGE_QUALITY))?
+                .clamp(1, 100) as u8,
+            max_width: read_env_u32("MEMRI_IMAGE_MAX_WIDTH", 0)?,
+            max_height: read_env_u32("MEMRI_IMAGE_MAX_HEIGHT", 0)?,
+        };
+        let images = ImageConfig {
+            profiles: images::profiles_from_section(file_images, &image_defaults),
+            defaults: image_defaults,
+        };
+        let preprocess_defaults = PreprocessSteps {
+            grayscale: read_env_bool("MEMRI_PREPROCESS_GRAYSCALE", false)?,
+            invert_dark: read_env_bool("MEMRI_PREPROCESS_INVERT_DARK", false)?,
+            normalize_contrast: read_env_bool("MEMRI_PREPROCESS_CONTRAST", false)?,
+            upscale: read_env_f32("MEMRI_PREPROCESS_UPSCALE", DEFAULT_PREPROCESS_UPSCALE)?
+                .clamp(1.0, 4.0),
+            binarize: read_env_bool("MEMRI_PREPROCESS_BINARIZE", false)?,
+            tile_height: read_env_u32(
+                "MEMRI_PREPROCESS_TILE_HEIGHT",
+                DEFAULT_PREPROCESS_TILE_HEIGHT,
+            )?,
+        };
+        let preprocess = PreprocessConfig {
+            profiles: preprocess::profiles_from_section(file_preprocess, &preprocess_defaults),
+            defaults: preprocess_defaults,
+        };
+        let settle = SettleConfig {
+            enabled: read_env_bool("MEMRI_SETTLE_ENABLED", false)?,
+            interval_ms: read_env_u64("MEMRI_SETTLE_INTERVAL_MS", DEFAULT_SETTLE_INTERVAL_MS)?
+                .max(10),
+            stable_frames: read_env_u32("MEMRI_SETTLE_STABLE_FRAMES", DEFAULT_SETTLE_STABLE_FRAMES)?
+                .max(1),
+            timeout_ms: read_env_u64("MEMRI_SETTLE_TIMEOUT_MS", DEFAULT_SETTLE_TIMEOUT_MS)?,
+        };
+        let policy = CapturePolicyConfig {
+            name: env::var("MEMRI_CAPTURE_POLICY")
+                .map(|v| v.to_lowercase())
+                .unwrap_or_else(|_| DEFAULT_CAPTURE_POLICY.to_string()),
+            burst_interval_ms: read_env_u64("MEMRI_BURST_INTERVAL_MS", DEFAULT_BURST_INTERVAL_MS)?,
+            background_interval_secs: read_env_u64(
+                "MEMRI_BACKGROUND_INTERVAL_SECS",
+                DEFAULT_BACKGROUND_INTERVAL_SECS,
+            )?,
+            keyframe_minutes: read_env_u64("MEMRI_KEYFRAME_MINUTES", DEFAULT_KEYFRAME_MINUTES)?,
+        };
+        let schedule = ScheduleConfig {
+            enabled: read_env_bool("MEMRI_SCHEDULE_ENABLED", true)?,
+            ..ScheduleConfig::default()
+        }
+        .with_section(file_schedule);
 
         Ok(Self {
             monitor_id,
             monitor_ids,
+            capture_all_monitors,
+            monitor_poll_secs,
             capture_interval_ms,
             capture_max_interval_ms,
             capture_unfocused_windows,
@@ -72,6 +364,20 @@ impl AppConfig {
             retention_days,
             max_captures,
             image_dir,
+            capture_source,
+            replay_path,
+            replay_speed,
+            dedupe_window_minutes,
+            dedupe_max_distance,
+            detection,
+            redaction,
+            privacy,
+            schedule,
+            ocr,
+            images,
+            preprocess,
+            settle,
+            policy,
         })
     }
 }
@@ -124,6 +430,24 @@ fn read_env_u64(key: &str, default: u64) -> Result<u64> {
     }
 }
 
+fn read_env_usize(key: &str, default: usize) -> Result<usize> {
+    match env::var(key) {
+        Ok(val) => val
+            .parse::<usize>()
+            .with_context(|| format!("Failed to parse {key} as usize")),
+        Err(_) => Ok(default),
+    }
+}
+
+fn read_env_f32(key: &str, default: f32) -> Result<f32> {
+    match env::var(key) {
+        Ok(val) => val
+            .parse::<f32>()
+            .with_context(|| format!("Failed to parse {key} as f32")),
+        Err(_) => Ok(default),
+    }
+}
+
 fn read_env_bool(key: &str, default: bool) -> Result<bool> {
     match env::var(key) {
         Ok(val) => match val.to_lowercase().as_str() {
@@ -0,0 +1,119 @@
+//! Image clean-up applied to windows before OCR, globally and per app.
+
+use serde::{Deserialize, Serialize};
+
+use crate::file_loader::{PreprocessProfileSection, PreprocessSection};
+
+pub const DEFAULT_PREPROCESS_UPSCALE: f32 = 1.0;
+pub const DEFAULT_PREPROCESS_TILE_HEIGHT: u32 = 0;
+
+/// Which preprocessing steps run, in the order they are applied.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PreprocessSteps {
+    /// Drop colour, keeping luminance.
+    pub grayscale: bool,
+    /// Invert light-on-dark windows (dark themes) to dark-on-light.
+    pub invert_dark: bool,
+    /// Stretch the luminance range so faint text gets full contrast.
+    pub normalize_contrast: bool,
+    /// Scale factor for small text; 1 leaves the size alone.
+    pub upscale: f32,
+    /// Reduce to black and white with an automatic (Otsu) threshold.
+    pub binarize: bool,
+    /// Windows taller than this, after upscaling, are recognized in
+    /// overlapping bands of this height (0 = never split).
+    pub tile_height: u32,
+}
+
+impl Default for PreprocessSteps {
+    fn default() -> Self {
+        Self {
+            grayscale: false,
+            invert_dark: false,
+            normalize_contrast: false,
+            upscale: DEFAULT_PREPROCESS_UPSCALE,
+            binarize: false,
+            tile_height: DEFAULT_PREPROCESS_TILE_HEIGHT,
+        }
+    }
+}
+
+/// Steps used for windows of a matching app or title.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PreprocessProfile {
+    pub name: String,
+    /// Case-insensitive substrings matched against the window's app name.
+    #[serde(default)]
+    pub match_apps: Vec<String>,
+    /// Case-insensitive substrings matched against the window title.
+    #[serde(default)]
+    pub match_titles: Vec<String>,
+    pub steps: PreprocessSteps,
+}
+
+impl PreprocessProfile {
+    pub fn matches(&self, app_name: &str, window_name: &str) -> bool {
+        let app_name = app_name.to_lowercase();
+        let window_name = window_name.to_lowercase();
+        self.match_apps
+            .iter()
+            .any(|pattern| app_name.contains(&pattern.to_lowercase()))
+            || self
+                .match_titles
+                .iter()
+                .any(|pattern| window_name.contains(&pattern.to_lowercase()))
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct PreprocessConfig {
+    /// Steps used when no profile matches.
+    pub defaults: PreprocessSteps,
+    /// Profiles checked in order; the first match wins.
+    #[serde(default)]
+    pub profiles: Vec<PreprocessProfile>,
+}
+
+impl PreprocessConfig {
+    pub fn steps_for(&self, app_name: &str, window_name: &str) -> &PreprocessSteps {
+        self.profiles
+            .iter()
+            .find(|profile| profile.matches(app_name, window_name))
+            .map(|profile| &profile.steps)
+            .unwrap_or(&self.defaults)
+    }
+}
+
+pub(crate) fn profiles_from_section(
+    section: Option<PreprocessSection>,
+    defaults: &PreprocessSteps,
+) -> Vec<PreprocessProfile> {
+    section
+        .map(|section| section.profiles)
+        .unwrap_or_default()
+        .into_iter()
+        .map(|profile| profile_from_section(profile, defaults))
+        .collect()
+}
+
+/// Fields missing from a profile fall back to the global settings.
+fn profile_from_section(
+    section: PreprocessProfileSection,
+    defaults: &PreprocessSteps,
+) -> PreprocessProfile {
+    PreprocessProfile {
+        name: section.name,
+        match_apps: section.match_apps,
+        match_titles: section.match_titles,
+        steps: PreprocessSteps {
+            grayscale: section.grayscale.unwrap_or(defaults.grayscale),
+            invert_dark: section.invert_dark.unwrap_or(defaults.invert_dark),
+            normalize_contrast: section
+                .normalize_contrast
+                .unwrap_or(defaults.normalize_contrast),
+            upscale: section.upscale.unwrap_or(defaults.upscale).clamp(1.0, 4.0),
+            binarize: section.binarize.unwrap_or(defaults.binarize),
+            tile_height: section.tile_height.unwrap_or(defaults.tile_height),
+        },
+    }
+}
@@ -0,0 +1,48 @@
+//! Redaction settings applied to window images and OCR text before they are stored.
+
+use serde::{Deserialize, Serialize};
+
+use crate::file_loader::RedactionSection;
+
+/// Screen-space rectangle that is always blacked out, optionally only for one app.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MaskRect {
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+    /// Case-insensitive app name; masks without one apply to every window.
+    #[serde(default)]
+    pub app: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RedactionConfig {
+    pub enabled: bool,
+    /// Include the built-in detectors (card numbers, API keys, password bullets).
+    pub builtin_patterns: bool,
+    /// Extra regexes matched against each OCR line.
+    pub patterns: Vec<String>,
+    pub masks: Vec<MaskRect>,
+}
+
+impl Default for RedactionConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            builtin_patterns: true,
+            patterns: Vec::new(),
+            masks: Vec::new(),
+        }
+    }
+}
+
+impl RedactionConfig {
+    pub(crate) fn with_section(mut self, section: Option<RedactionSection>) -> Self {
+        if let Some(section) = section {
+            self.patterns = section.patterns;
+            self.masks = section.masks;
+        }
+        self
+    }
+}
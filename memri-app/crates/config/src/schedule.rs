@@ -0,0 +1,42 @@
+//! Time-of-day windows outside of which capture is paused.
+
+use serde::{Deserialize, Serialize};
+
+use crate::file_loader::ScheduleSection;
+
+/// Weekday/time range during which capture runs, in local time.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ScheduleWindow {
+    /// `mon`..`sun`, ranges like `mon-fri`, `weekdays`, `weekends` or `daily`;
+    /// empty means every day.
+    #[serde(default)]
+    pub days: Vec<String>,
+    /// `HH:MM`; an `end` at or before `start` runs past midnight, `24:00` ends the day.
+    pub start: String,
+    pub end: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ScheduleConfig {
+    /// Restrict capture to `windows`; without windows capture runs around the clock.
+    pub enabled: bool,
+    pub windows: Vec<ScheduleWindow>,
+}
+
+impl Default for ScheduleConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            windows: Vec::new(),
+        }
+    }
+}
+
+impl ScheduleConfig {
+    pub(crate) fn with_section(mut self, section: Option<ScheduleSection>) -> Self {
+        if let Some(section) = section {
+            self.windows = section.windows;
+        }
+        self
+    }
+}
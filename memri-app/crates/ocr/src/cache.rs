@@ -0,0 +1,334 @@
+//! Caching decorator for OCR engines.
+//!
+//! Results are keyed by the engine, the requested languages and a SHA-256 of
+//! the image, so an unchanged window is only recognized once. Recent results
+//! live in an in-memory LRU; an optional persistent tier keeps them across
+//! restarts.
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+use anyhow::Result;
+use async_trait::async_trait;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use tracing::{debug, warn};
+
+use crate::{OcrContext, OcrEngine, OcrPayload, RgbaFrame};
+
+/// Persistent tier of `CachedOcr`; payloads are handed over as JSON.
+#[async_trait]
+pub trait OcrCacheStore: Send + Sync {
+    async fn load(&self, key: &str) -> Result<Option<String>>;
+
+    async fn store(&self, key: &str, payload_json: &str) -> Result<()>;
+
+    /// Whether `payload` may be written to this tier at all. Rejected
+    /// results are still cached in memory.
+    fn accepts(&self, _payload: &OcrPayload) -> bool {
+        true
+    }
+}
+
+/// Counters of a `CachedOcr`, served by the backend for monitoring.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct OcrCacheStats {
+    pub memory_hits: u64,
+    pub persistent_hits: u64,
+    pub misses: u64,
+    /// Results held in memory, out of `capacity`.
+    pub entries: usize,
+    pub capacity: usize,
+    pub persistent: bool,
+}
+
+/// Wraps an engine and answers repeated images from the cache. Only
+/// successful results are cached; failures are retried next time.
+pub struct CachedOcr {
+    inner: Arc<dyn OcrEngine>,
+    memory: Mutex<Lru>,
+    store: Option<Arc<dyn OcrCacheStore>>,
+    memory_hits: AtomicU64,
+    persistent_hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+impl CachedOcr {
+    /// `capacity` results are kept in memory (0 disables the memory tier);
+    /// `store`, when given, is consulted on a memory miss.
+    pub fn new(
+        inner: Arc<dyn OcrEngine>,
+        capacity: usize,
+        store: Option<Arc<dyn OcrCacheStore>>,
+    ) -> Self {
+        Self {
+            inner,
+            memory: Mutex::new(Lru::new(capacity)),
+            store,
+            memory_hits: AtomicU64::new(0),
+            persistent_hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        }
+    }
+
+    pub fn stats(&self) -> OcrCacheStats {
+        let (entries, capacity) = {
+            let memory = self.memory.lock().unwrap();
+            (memory.entries.len(), memory.capacity)
+        };
+        OcrCacheStats {
+            memory_hits: self.memory_hits.load(Ordering::Relaxed),
+            persistent_hits: self.persistent_hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            entries,
+            capacity,
+            persistent: self.store.is_some(),
+        }
+    }
+
+    /// `engine/languages/sha256`, where `engine` is the inner engine's
+    /// `cache_id`. Languages are sorted so their order in the
+    /// config doesn't split the cache.
+    fn key(&self, image_hash: &str, context: &OcrContext) -> String {
+        let mut languages: Vec<String> = context
+            .languages
+            .iter()
+            .map(|language| language.trim().to_lowercase())
+            .collect();
+        languages.sort();
+        languages.dedup();
+        format!(
+            "{}/{}/{image_hash}",
+            self.inner.cache_id(),
+            languages.join("+")
+        )
+    }
+
+    async fn lookup(&self, key: &str) -> Option<OcrPayload> {
+        let hit = self.memory.lock().unwrap().get(key);
+        if let Some(payload) = hit {
+            self.memory_hits.fetch_add(1, Ordering::Relaxed);
+            return Some(payload);
+        }
+        let store = self.store.as_ref()?;
+        let json = match store.load(key).await {
+            Ok(json) => json?,
+            Err(err) => {
+                warn!("failed to read the OCR cache: {err}");
+                return None;
+            }
+        };
+        // Entries from an older payload format count as misses.
+        let payload: OcrPayload = serde_json::from_str(&json).ok()?;
+        self.persistent_hits.fetch_add(1, Ordering::Relaxed);
+        self.memory
+            .lock()
+            .unwrap()
+            .insert(key.to_string(), payload.clone());
+        Some(payload)
+    }
+
+    async fn remember(&self, key: String, payload: &OcrPayload) {
+        if let Some(store) = self.store.as_ref().filter(|store| store.accepts(payload)) {
+            match serde_json::to_string(payload) {
+                Ok(json) => {
+                    if let Err(err) = store.store(&key, &json).await {
+                        warn!("failed to write the OCR cache: {err}");
+                    }
+                }
+                Err(err) => warn!("failed to serialize OCR result for the cache: {err}"),
+            }
+        }
+        self.memory.lock().unwrap().insert(key, payload.clone());
+    }
+
+    async fn cached(
+        &self,
+        key: String,
+        recognize: impl std::future::Future<Output = Result<OcrPayload>>,
+    ) -> Result<OcrPayload> {
+        if let Some(payload) = self.lookup(&key).await {
+            debug!(key = %key, "OCR cache hit");
+            return Ok(payload);
+        }
+        self.misses.fetch_add(1, Ordering::Relaxed);
+        let payload = recognize.await?;
+        self.remember(key, &payload).await;
+        Ok(payload)
+    }
+}
+
+#[async_trait]
+impl OcrEngine for CachedOcr {
+    async fn recognize(&self, image_bytes: &[u8], context: &OcrContext) -> Result<OcrPayload> {
+        let key = self.key(&format!("{:x}", Sha256::digest(image_bytes)), context);
+        self.cached(key, self.inner.recognize(image_bytes, context))
+            .await
+    }
+
+    async fn recognize_rgba(
+        &self,
+        frame: RgbaFrame<'_>,
+        context: &OcrContext,
+    ) -> Result<OcrPayload> {
+        // The size is hashed too: the same bytes reshaped are a different image.
+        let mut hasher = Sha256::new();
+        hasher.update(frame.width.to_le_bytes());
+        hasher.update(frame.height.to_le_bytes());
+        hasher.update(frame.pixels);
+        let key = self.key(&format!("rgba-{:x}", hasher.finalize()), context);
+        self.cached(key, self.inner.recognize_rgba(frame, context))
+            .await
+    }
+
+    fn name(&self) -> &'static str {
+        self.inner.name()
+    }
+
+    fn cache_id(&self) -> String {
+        self.inner.cache_id()
+    }
+}
+
+/// Least-recently-used map. Eviction scans for the oldest entry, which is
+/// cheap next to OCR at the capacities used here.
+struct Lru {
+    capacity: usize,
+    tick: u64,
+    entries: HashMap<String, (u64, OcrPayload)>,
+}
+
+impl Lru {
+    fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            tick: 0,
+            entries: HashMap::new(),
+        }
+    }
+
+    fn get(&mut self, key: &str) -> Option<OcrPayload> {
+        self.tick += 1;
+        let (used, payload) = self.entries.get_mut(key)?;
+        *used = self.tick;
+        Some(payload.clone())
+    }
+
+    fn insert(&mut self, key: String, payload: OcrPayload) {
+        if self.capacity == 0 {
+            return;
+        }
+        self.tick += 1;
+        if !self.entries.contains_key(&key) && self.entries.len() >= self.capacity {
+            let oldest = self
+                .entries
+                .iter()
+                .min_by_key(|(_, (used, _))| *used)
+                .map(|(key, _)| key.clone());
+            if let Some(oldest) = oldest {
+                self.entries.remove(&oldest);
+            }
+        }
+        self.entries.insert(key, (self.tick, payload));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{CompositeOcr, CompositeStrategy};
+
+    fn payload(text: &str) -> OcrPayload {
+        OcrPayload {
+            text: text.to_string(),
+            confidence: None,
+            layout: Default::default(),
+        }
+    }
+
+    fn cached_text(lru: &mut Lru, key: &str) -> Option<String> {
+        lru.get(key).map(|payload| payload.text)
+    }
+
+    #[test]
+    fn evicts_the_least_recently_used_entry() {
+        let mut lru = Lru::new(2);
+        lru.insert("a".to_string(), payload("first"));
+        lru.insert("b".to_string(), payload("second"));
+        // Reading `a` makes `b` the oldest.
+        assert_eq!(cached_text(&mut lru, "a").as_deref(), Some("first"));
+        lru.insert("c".to_string(), payload("third"));
+
+        assert_eq!(lru.entries.len(), 2);
+        assert_eq!(cached_text(&mut lru, "b"), None);
+        assert_eq!(cached_text(&mut lru, "a").as_deref(), Some("first"));
+        assert_eq!(cached_text(&mut lru, "c").as_deref(), Some("third"));
+    }
+
+    #[test]
+    fn replacing_a_key_evicts_nothing() {
+        let mut lru = Lru::new(2);
+        lru.insert("a".to_string(), payload("first"));
+        lru.insert("b".to_string(), payload("second"));
+        lru.insert("a".to_string(), payload("updated"));
+
+        assert_eq!(cached_text(&mut lru, "a").as_deref(), Some("updated"));
+        assert_eq!(cached_text(&mut lru, "b").as_deref(), Some("second"));
+    }
+
+    /// Returns its own name as the text.
+    struct Named(&'static str);
+
+    #[async_trait]
+    impl OcrEngine for Named {
+        async fn recognize(
+            &self,
+            _image_bytes: &[u8],
+            _context: &OcrContext,
+        ) -> Result<OcrPayload> {
+            Ok(payload(self.0))
+        }
+
+        fn name(&self) -> &'static str {
+            self.0
+        }
+    }
+
+    fn composite_key(engines: &[&'static str], strategy: CompositeStrategy, min: f32) -> String {
+        let engines = engines
+            .iter()
+            .map(|name| Arc::new(Named(name)) as Arc<dyn OcrEngine>)
+            .collect();
+        let composite = CompositeOcr::new(engines, strategy, min).unwrap();
+        let context = OcrContext {
+            window_name: String::new(),
+            app_name: String::new(),
+            is_focused: false,
+            languages: vec!["eng".to_string()],
+        };
+        CachedOcr::new(Arc::new(composite), 1, None).key("hash", &context)
+    }
+
+    #[test]
+    fn composite_keys_depend_on_engines_strategy_and_confidence() {
+        let key = composite_key(&["a", "b"], CompositeStrategy::Fallback, 0.5);
+        assert_eq!(key, "composite(a+b,Fallback,0.5)/eng/hash");
+        for other in [
+            composite_key(&["b", "a"], CompositeStrategy::Fallback, 0.5),
+            composite_key(&["a"], CompositeStrategy::Fallback, 0.5),
+            composite_key(&["a", "b"], CompositeStrategy::Race, 0.5),
+            composite_key(&["a", "b"], CompositeStrategy::Fallback, 0.8),
+        ] {
+            assert_ne!(key, other);
+        }
+    }
+
+    #[test]
+    fn zero_capacity_keeps_nothing() {
+        let mut lru = Lru::new(0);
+        lru.insert("a".to_string(), payload("first"));
+        assert!(lru.entries.is_empty());
+        assert_eq!(cached_text(&mut lru, "a"), None);
+    }
+}
@@ -0,0 +1,258 @@
+use std::cmp::Ordering;
+use std::str::FromStr;
+use std::sync::Arc;
+
+use anyhow::{anyhow, bail, Result};
+use async_trait::async_trait;
+use tokio::task::JoinSet;
+use tracing::debug;
+
+use crate::{OcrContext, OcrEngine, OcrPayload, RgbaFrame};
+
+/// How `CompositeOcr` combines its engines.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompositeStrategy {
+    /// Run the engines one after another and stop at the first good result.
+    Fallback,
+    /// Run all engines at once and take the first good result to arrive.
+    Race,
+    /// Run all engines at once and take the most confident result.
+    BestConfidence,
+}
+
+impl FromStr for CompositeStrategy {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self> {
+        match value.trim().to_lowercase().replace('-', "_").as_str() {
+            "fallback" => Ok(Self::Fallback),
+            "race" => Ok(Self::Race),
+            "best_confidence" => Ok(Self::BestConfidence),
+            other => Err(anyhow!(
+                "unknown composite OCR strategy {other:?} (expected fallback, race or best_confidence)"
+            )),
+        }
+    }
+}
+
+/// Runs several engines on the same image. A result is good when it has text
+/// and its confidence, if the engine reports one, reaches `min_confidence`.
+/// When no result is good, the best one is returned anyway; when every
+/// engine fails, the last error is.
+pub struct CompositeOcr {
+    engines: Vec<Arc<dyn OcrEngine>>,
+    strategy: CompositeStrategy,
+    min_confidence: f32,
+}
+
+/// The image handed to every engine, owned so it can move into tasks.
+#[derive(Clone)]
+enum OwnedImage {
+    Encoded(Arc<Vec<u8>>),
+    Rgba {
+        width: u32,
+        height: u32,
+        pixels: Arc<Vec<u8>>,
+    },
+}
+
+impl OwnedImage {
+    async fn recognize(&self, engine: &dyn OcrEngine, context: &OcrContext) -> Result<OcrPayload> {
+        match self {
+            Self::Encoded(bytes) => engine.recognize(bytes, context).await,
+            Self::Rgba {
+                width,
+                height,
+                pixels,
+            } => {
+                let frame = RgbaFrame {
+                    width: *width,
+                    height: *height,
+                    pixels,
+                };
+                engine.recognize_rgba(frame, context).await
+            }
+        }
+    }
+}
+
+impl CompositeOcr {
+    /// Engines are listed in order of preference: `fallback` tries them in
+    /// this order and ties are broken in favour of the earlier one.
+    pub fn new(
+        engines: Vec<Arc<dyn OcrEngine>>,
+        strategy: CompositeStrategy,
+        min_confidence: f32,
+    ) -> Result<Self> {
+        if engines.is_empty() {
+            bail!("composite OCR needs at least one engine");
+        }
+        Ok(Self {
+            engines,
+            strategy,
+            min_confidence,
+        })
+    }
+
+    fn is_good(&self, payload: &OcrPayload) -> bool {
+        !payload.text.trim().is_empty()
+            && payload
+                .confidence
+                .is_none_or(|confidence| confidence >= self.min_confidence)
+    }
+
+    /// Whether `candidate` ranks above `current`: text beats no text, then
+    /// the higher confidence when both engines report one. An engine without
+    /// scores is neither better nor worse than one with them, so the rest
+    /// goes to the engine listed first.
+    fn outranks(candidate: &(usize, OcrPayload), current: &(usize, OcrPayload)) -> bool {
+        let has_text = |payload: &OcrPayload| !payload.text.trim().is_empty();
+        let by_confidence = match (candidate.1.confidence, current.1.confidence) {
+            (Some(candidate), Some(current)) => {
+                candidate.partial_cmp(&current).unwrap_or(Ordering::Equal)
+            }
+            _ => Ordering::Equal,
+        };
+        has_text(&candidate.1)
+            .cmp(&has_text(&current.1))
+            .then(by_confidence)
+            .then(current.0.cmp(&candidate.0))
+            .is_gt()
+    }
+
+    async fn run(&self, image: OwnedImage, context: &OcrContext) -> Result<OcrPayload> {
+        match self.strategy {
+            CompositeStrategy::Fallback => self.fallback(image, context).await,
+            CompositeStrategy::Race | CompositeStrategy::BestConfidence => {
+                self.concurrent(image, context).await
+            }
+        }
+    }
+
+    async fn fallback(&self, image: OwnedImage, context: &OcrContext) -> Result<OcrPayload> {
+        let mut best: Option<(usize, OcrPayload)> = None;
+        let mut last_error = None;
+        for (index, engine) in self.engines.iter().enumerate() {
+            match image.recognize(engine.as_ref(), context).await {
+                Ok(payload) if self.is_good(&payload) => return Ok(payload),
+                Ok(payload) => {
+                    debug!(
+                        engine = engine.name(),
+                        confidence = payload.confidence,
+                        "OCR result not good enough; trying the next engine"
+                    );
+                    best = Self::better(best, (index, payload));
+                }
+                Err(err) => {
+                    debug!(engine = engine.name(), "OCR engine failed: {err}");
+                    last_error = Some(err);
+                }
+            }
+        }
+        Self::finish(best, last_error)
+    }
+
+    /// Every engine runs as its own task so blocking engines overlap. `race`
+    /// returns the first good result and abandons the rest; unfinished
+    /// blocking engines still run to completion in the background.
+    async fn concurrent(&self, image: OwnedImage, context: &OcrContext) -> Result<OcrPayload> {
+        let mut tasks = JoinSet::new();
+        for (index, engine) in self.engines.iter().enumerate() {
+            let engine = engine.clone();
+            let image = image.clone();
+            let context = context.clone();
+            tasks.spawn(async move {
+                let result = image.recognize(engine.as_ref(), &context).await;
+                (index, engine.name(), result)
+            });
+        }
+
+        let mut best: Option<(usize, OcrPayload)> = None;
+        let mut last_error = None;
+        while let Some(joined) = tasks.join_next().await {
+            let (index, name, result) = match joined {
+                Ok(finished) => finished,
+                Err(err) => {
+                    last_error = Some(anyhow!("OCR task failed: {err}"));
+                    continue;
+                }
+            };
+            match result {
+                Ok(payload) => {
+                    if self.strategy == CompositeStrategy::Race && self.is_good(&payload) {
+                        debug!(engine = name, "OCR race won");
+                        return Ok(payload);
+                    }
+                    best = Self::better(best, (index, payload));
+                }
+                Err(err) => {
+                    debug!(engine = name, "OCR engine failed: {err}");
+                    last_error = Some(err);
+                }
+            }
+        }
+        Self::finish(best, last_error)
+    }
+
+    /// Keep whichever result ranks higher.
+    fn better(
+        current: Option<(usize, OcrPayload)>,
+        candidate: (usize, OcrPayload),
+    ) -> Option<(usize, OcrPayload)> {
+        match current {
+            Some(current) if !Self::outranks(&candidate, &current) => Some(current),
+            _ => Some(candidate),
+        }
+    }
+
+    fn finish(
+        best: Option<(usize, OcrPayload)>,
+        last_error: Option<anyhow::Error>,
+    ) -> Result<OcrPayload> {
+        match (best, last_error) {
+            (Some((_, payload)), _) => Ok(payload),
+            (None, Some(err)) => Err(err),
+            (None, None) => Err(anyhow!("no OCR engine produced a result")),
+        }
+    }
+}
+
+#[async_trait]
+impl OcrEngine for CompositeOcr {
+    async fn recognize(&self, image_bytes: &[u8], context: &OcrContext) -> Result<OcrPayload> {
+        self.run(OwnedImage::Encoded(Arc::new(image_bytes.to_vec())), context)
+            .await
+    }
+
+    async fn recognize_rgba(
+        &self,
+        frame: RgbaFrame<'_>,
+        context: &OcrContext,
+    ) -> Result<OcrPayload> {
+        let image = OwnedImage::Rgba {
+            width: frame.width,
+            height: frame.height,
+            pixels: Arc::new(frame.pixels.to_vec()),
+        };
+        self.run(image, context).await
+    }
+
+    fn name(&self) -> &'static str {
+        "composite"
+    }
+
+    /// `composite(engines,strategy,min_confidence)`, with each engine's own id.
+    fn cache_id(&self) -> String {
+        let engines: Vec<String> = self
+            .engines
+            .iter()
+            .map(|engine| engine.cache_id())
+            .collect();
+        format!(
+            "composite({},{:?},{})",
+            engines.join("+"),
+            self.strategy,
+            self.min_confidence
+        )
+    }
+}
@@ -0,0 +1,156 @@
+use serde::{Deserialize, Serialize};
+
+/// Rectangle in the pixels of the recognized image.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OcrRect {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl OcrRect {
+    /// Smallest rectangle covering every one of `rects`; empty input gives a
+    /// zero-sized rectangle at the origin.
+    pub fn union<'a>(rects: impl IntoIterator<Item = &'a OcrRect>) -> Self {
+        let mut rects = rects.into_iter().filter(|rect| rect.width > 0 && rect.height > 0);
+        let Some(first) = rects.next() else {
+            return Self::default();
+        };
+        let (mut left, mut top) = (first.x, first.y);
+        let (mut right, mut bottom) = (first.x + first.width, first.y + first.height);
+        for rect in rects {
+            left = left.min(rect.x);
+            top = top.min(rect.y);
+            right = right.max(rect.x + rect.width);
+            bottom = bottom.max(rect.y + rect.height);
+        }
+        Self {
+            x: left,
+            y: top,
+            width: right - left,
+            height: bottom - top,
+        }
+    }
+}
+
+/// A single recognized word.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OcrWord {
+    pub text: String,
+    pub bounds: OcrRect,
+    /// Recognition confidence from 0 to 1, when the engine reports one.
+    pub confidence: Option<f32>,
+}
+
+/// Words on one line, left to right.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OcrLine {
+    pub text: String,
+    pub bounds: OcrRect,
+    pub confidence: Option<f32>,
+    pub words: Vec<OcrWord>,
+}
+
+impl OcrLine {
+    /// Build a line from its words; text, bounds and confidence are derived.
+    pub fn new(words: Vec<OcrWord>) -> Self {
+        Self {
+            text: words
+                .iter()
+                .map(|word| word.text.as_str())
+                .collect::<Vec<_>>()
+                .join(" "),
+            bounds: OcrRect::union(words.iter().map(|word| &word.bounds)),
+            confidence: mean_confidence(&words),
+            words,
+        }
+    }
+}
+
+/// A paragraph or column of text: lines, top to bottom.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OcrBlock {
+    pub bounds: OcrRect,
+    pub confidence: Option<f32>,
+    pub lines: Vec<OcrLine>,
+}
+
+impl OcrBlock {
+    /// Build a block from its lines; bounds and confidence are derived.
+    pub fn new(lines: Vec<OcrLine>) -> Self {
+        Self {
+            bounds: OcrRect::union(lines.iter().map(|line| &line.bounds)),
+            confidence: mean_confidence(lines.iter().flat_map(|line| line.words.iter())),
+            lines,
+        }
+    }
+}
+
+/// Where text sits in a recognized image. Blocks, lines and words are each
+/// listed in reading order.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct OcrLayout {
+    /// Engine that produced the layout.
+    pub engine: String,
+    /// Language the engine recognized, as the engine names it.
+    pub language: Option<String>,
+    /// Size of the recognized image, which all boxes refer to.
+    pub width: u32,
+    pub height: u32,
+    pub blocks: Vec<OcrBlock>,
+    /// Preprocessing applied to the image before recognition, in order
+    /// (`grayscale`, `invert`, `upscale:2`, `tiles:3`, ...).
+    #[serde(default)]
+    pub preprocessing: Vec<String>,
+}
+
+impl OcrLayout {
+    /// Every line in reading order.
+    pub fn lines(&self) -> impl Iterator<Item = &OcrLine> {
+        self.blocks.iter().flat_map(|block| block.lines.iter())
+    }
+
+    /// Every word in reading order.
+    pub fn words(&self) -> impl Iterator<Item = &OcrWord> {
+        self.lines().flat_map(|line| line.words.iter())
+    }
+
+    /// Mean word confidence, weighted by word length.
+    pub fn confidence(&self) -> Option<f32> {
+        mean_confidence(self.words())
+    }
+
+    /// Words containing any whitespace-separated term of `query`, ignoring
+    /// case; used to highlight search hits on the image.
+    pub fn matching_words(&self, query: &str) -> Vec<&OcrWord> {
+        let terms: Vec<String> = query
+            .split_whitespace()
+            .map(|term| term.to_lowercase())
+            .collect();
+        if terms.is_empty() {
+            return Vec::new();
+        }
+        self.words()
+            .filter(|word| {
+                let text = word.text.to_lowercase();
+                terms.iter().any(|term| text.contains(term.as_str()))
+            })
+            .collect()
+    }
+}
+
+/// Average word confidence, weighted by word length so stray one-letter
+/// guesses count for less.
+fn mean_confidence<'a>(words: impl IntoIterator<Item = &'a OcrWord>) -> Option<f32> {
+    let (sum, weight) = words
+        .into_iter()
+        .filter_map(|word| {
+            let weight = word.text.chars().count() as f32;
+            word.confidence.map(|confidence| (confidence * weight, weight))
+        })
+        .fold((0.0, 0.0), |(sum, total), (value, weight)| {
+            (sum + value, total + weight)
+        });
+    (weight > 0.0).then(|| sum / weight)
+}
@@ -1,17 +1,40 @@
 //! OCR abstraction layer powered by Windows-native APIs first.
 //!
 //! The Windows implementation uses `Windows.Media.Ocr` to perform on-device OCR.
+//! Elsewhere, `TesseractOcr` (behind the `tesseract` feature) uses libtesseract.
 
-use anyhow::Result;
+use std::sync::Arc;
+
+use anyhow::{anyhow, bail, Result};
 use async_trait::async_trait;
+use image::{codecs::png::PngEncoder, ColorType, ImageEncoder};
+use serde::{Deserialize, Serialize};
+#[cfg(target_os = "windows")]
 use tracing::debug;
+#[cfg(not(any(target_os = "windows", feature = "tesseract")))]
+use tracing::warn;
 
-/// OCR text output including optional structured details.
-#[derive(Debug, Clone)]
+mod cache;
+mod composite;
+mod layout;
+#[cfg(feature = "tesseract")]
+mod tesseract_ocr;
+
+pub use cache::{CachedOcr, OcrCacheStats, OcrCacheStore};
+pub use composite::{CompositeOcr, CompositeStrategy};
+pub use layout::{OcrBlock, OcrLayout, OcrLine, OcrRect, OcrWord};
+#[cfg(feature = "tesseract")]
+pub use tesseract_ocr::TesseractOcr;
+
+/// OCR text output with the layout it was read from.
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct OcrPayload {
     pub text: String,
+    /// Mean word confidence from 0 to 1; `None` when the engine has no scores.
     pub confidence: Option<f32>,
-    pub json: Option<String>,
+    /// Blocks, lines and words with their boxes; no blocks when the engine
+    /// can't locate text.
+    pub layout: OcrLayout,
 }
 
 /// Metadata about the window/surface being processed.
@@ -23,53 +46,153 @@ pub struct OcrContext {
     pub languages: Vec<String>,
 }
 
+/// A decoded image: tightly packed 8-bit RGBA rows, `width * height * 4` bytes.
+#[derive(Debug, Clone, Copy)]
+pub struct RgbaFrame<'a> {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: &'a [u8],
+}
+
+impl RgbaFrame<'_> {
+    /// Encode as PNG for engines that only accept image files.
+    pub fn to_png(&self) -> Result<Vec<u8>> {
+        if self.pixels.len() != self.width as usize * self.height as usize * 4 {
+            return Err(anyhow!(
+                "RGBA buffer of {} bytes does not match {}x{}",
+                self.pixels.len(),
+                self.width,
+                self.height
+            ));
+        }
+        let mut buffer = Vec::new();
+        PngEncoder::new(&mut buffer).write_image(
+            self.pixels,
+            self.width,
+            self.height,
+            ColorType::Rgba8,
+        )?;
+        Ok(buffer)
+    }
+}
+
 /// Trait that all OCR engines must implement.
 #[async_trait]
 pub trait OcrEngine: Send + Sync {
+    /// Recognize text in an encoded image (PNG, JPEG, ...).
     async fn recognize(&self, image_bytes: &[u8], context: &OcrContext) -> Result<OcrPayload>;
 
+    /// Recognize text in raw pixels. The default encodes a PNG and calls
+    /// `recognize`; engines that can read pixels directly should override it.
+    async fn recognize_rgba(
+        &self,
+        frame: RgbaFrame<'_>,
+        context: &OcrContext,
+    ) -> Result<OcrPayload> {
+        let png = frame.to_png()?;
+        self.recognize(&png, context).await
+    }
+
     fn name(&self) -> &'static str;
+
+    /// Identifies the engine and every setting that changes its output, so
+    /// cached results are only reused for the same configuration.
+    fn cache_id(&self) -> String {
+        self.name().to_string()
+    }
 }
 
-/// Placeholder Windows OCR implementation.
+/// Build the engine named in the config: `windows`, `tesseract`, or `auto`,
+/// which picks Windows OCR on Windows and Tesseract elsewhere when compiled in.
+pub fn engine_from_name(
+    name: &str,
+    tesseract_data_path: Option<&str>,
+) -> Result<Arc<dyn OcrEngine>> {
+    let _ = tesseract_data_path;
+    match name.trim().to_lowercase().as_str() {
+        "windows" => Ok(Arc::new(WindowsOcr)),
+        #[cfg(feature = "tesseract")]
+        "tesseract" => Ok(Arc::new(TesseractOcr::new(
+            tesseract_data_path.map(str::to_string),
+        ))),
+        #[cfg(not(feature = "tesseract"))]
+        "tesseract" => bail!("OCR engine \"tesseract\" needs the `tesseract` cargo feature"),
+        #[cfg(target_os = "windows")]
+        "auto" => Ok(Arc::new(WindowsOcr)),
+        #[cfg(all(not(target_os = "windows"), feature = "tesseract"))]
+        "auto" => Ok(Arc::new(TesseractOcr::new(
+            tesseract_data_path.map(str::to_string),
+        ))),
+        #[cfg(not(any(target_os = "windows", feature = "tesseract")))]
+        "auto" => {
+            warn!("no OCR engine available on this platform; build with the `tesseract` feature");
+            Ok(Arc::new(WindowsOcr))
+        }
+        other => bail!("unknown OCR engine {other:?} (expected windows, tesseract or auto)"),
+    }
+}
+
+/// Windows OCR (`Windows.Media.Ocr`). On other platforms every call fails, so
+/// windows are marked as failed instead of storing made-up text.
 pub struct WindowsOcr;
 
+/// Identifier of Windows OCR in stored layouts, `ocr_engine` and cache keys.
+const WINDOWS_OCR_ENGINE: &str = "windows.media.ocr";
+
 #[async_trait]
 impl OcrEngine for WindowsOcr {
     async fn recognize(&self, image_bytes: &[u8], context: &OcrContext) -> Result<OcrPayload> {
         #[cfg(target_os = "windows")]
         {
-            let (text, json) = ocr_windows(image_bytes, context).await?;
+            let (text, layout) = ocr_windows(image_bytes, context).await?;
             Ok(OcrPayload {
                 text,
                 confidence: None,
-                json: Some(json),
+                layout,
             })
         }
 
         #[cfg(not(target_os = "windows"))]
         {
+            let _ = (image_bytes, context);
+            Err(anyhow!("Windows OCR is not available on this platform"))
+        }
+    }
+
+    async fn recognize_rgba(
+        &self,
+        frame: RgbaFrame<'_>,
+        context: &OcrContext,
+    ) -> Result<OcrPayload> {
+        #[cfg(target_os = "windows")]
+        {
+            let (text, layout) = ocr_windows_rgba(frame, context).await?;
             Ok(OcrPayload {
-                text: format!("[stub ocr for {}]", context.window_name),
+                text,
                 confidence: None,
-                json: None,
+                layout,
             })
         }
+
+        #[cfg(not(target_os = "windows"))]
+        {
+            let _ = (frame, context);
+            Err(anyhow!("Windows OCR is not available on this platform"))
+        }
     }
 
     fn name(&self) -> &'static str {
-        "windows-ocr"
+        WINDOWS_OCR_ENGINE
     }
 }
 
 #[cfg(target_os = "windows")]
-async fn ocr_windows(image_bytes: &[u8], context: &OcrContext) -> Result<(String, String)> {
-    use anyhow::Context as _;
+async fn ocr_windows(
+    image_bytes: &[u8],
+    context: &OcrContext,
+) -> Result<(String, OcrLayout)> {
     use windows::{
-        core::HSTRING,
-        Globalization::Language,
         Graphics::Imaging::{BitmapDecoder, BitmapPixelFormat, SoftwareBitmap},
-        Media::Ocr::OcrEngine,
         Storage::Streams::{DataWriter, InMemoryRandomAccessStream},
     };
 
@@ -87,6 +210,42 @@ async fn ocr_windows(image_bytes: &[u8], context: &OcrContext) -> Result<(String
     // Ensure format is supported by OCR (BGRA8).
     let bitmap = SoftwareBitmap::Convert(&bitmap, BitmapPixelFormat::Bgra8)?;
 
+    ocr_windows_bitmap(&bitmap, context)
+}
+
+/// Build the bitmap straight from pixels, skipping the encode/decode round trip.
+#[cfg(target_os = "windows")]
+async fn ocr_windows_rgba(
+    frame: RgbaFrame<'_>,
+    context: &OcrContext,
+) -> Result<(String, OcrLayout)> {
+    use windows::{
+        Graphics::Imaging::{BitmapPixelFormat, SoftwareBitmap},
+        Storage::Streams::DataWriter,
+    };
+
+    let writer = DataWriter::new()?;
+    writer.WriteBytes(frame.pixels)?;
+    let buffer = writer.DetachBuffer()?;
+    let bitmap = SoftwareBitmap::CreateCopyFromBuffer(
+        &buffer,
+        BitmapPixelFormat::Rgba8,
+        frame.width as i32,
+        frame.height as i32,
+    )?;
+    let bitmap = SoftwareBitmap::Convert(&bitmap, BitmapPixelFormat::Bgra8)?;
+
+    ocr_windows_bitmap(&bitmap, context)
+}
+
+#[cfg(target_os = "windows")]
+fn ocr_windows_bitmap(
+    bitmap: &windows::Graphics::Imaging::SoftwareBitmap,
+    context: &OcrContext,
+) -> Result<(String, OcrLayout)> {
+    use anyhow::Context as _;
+    use windows::{core::HSTRING, Globalization::Language, Media::Ocr::OcrEngine};
+
     let engine = if let Some(lang) = context
         .languages
         .iter()
@@ -98,21 +257,48 @@ async fn ocr_windows(image_bytes: &[u8], context: &OcrContext) -> Result<(String
     };
 
     let result = engine
-        .RecognizeAsync(&bitmap)?
+        .RecognizeAsync(bitmap)?
         .GetResults()
         .context("Windows OCR recognize failed")?;
 
     let text = result.Text()?.to_string_lossy();
-    let json = format!(
-        r#"{{"engine":"{}","window":"{}","app":"{}","lang":"{}"}}"#,
-        "windows.media.ocr",
-        context.window_name,
-        context.app_name,
-        engine
-            .RecognizerLanguage()?
-            .LanguageTag()?
-            .to_string_lossy()
-    );
+
+    // Windows OCR reports lines only, so they all go into one block.
+    let mut lines = Vec::new();
+    for line in result.Lines()? {
+        let mut words = Vec::new();
+        for word in line.Words()? {
+            let rect = word.BoundingRect()?;
+            words.push(OcrWord {
+                text: word.Text()?.to_string_lossy(),
+                bounds: OcrRect {
+                    x: rect.X.max(0.0) as u32,
+                    y: rect.Y.max(0.0) as u32,
+                    width: rect.Width.max(0.0).ceil() as u32,
+                    height: rect.Height.max(0.0).ceil() as u32,
+                },
+                confidence: None,
+            });
+        }
+        lines.push(OcrLine::new(words));
+    }
+    let layout = OcrLayout {
+        engine: WINDOWS_OCR_ENGINE.to_string(),
+        language: Some(
+            engine
+                .RecognizerLanguage()?
+                .LanguageTag()?
+                .to_string_lossy(),
+        ),
+        width: bitmap.PixelWidth()?.max(0) as u32,
+        height: bitmap.PixelHeight()?.max(0) as u32,
+        blocks: if lines.is_empty() {
+            Vec::new()
+        } else {
+            vec![OcrBlock::new(lines)]
+        },
+        preprocessing: Vec::new(),
+    };
 
     debug!(
         window = %context.window_name,
@@ -121,5 +307,5 @@ async fn ocr_windows(image_bytes: &[u8], context: &OcrContext) -> Result<(String
         "windows ocr completed"
     );
 
-    Ok((text, json))
+    Ok((text, layout))
 }
@@ -0,0 +1,354 @@
+//! Tesseract OCR through libtesseract, for platforms without Windows OCR.
+
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use anyhow::{anyhow, Context, Result};
+use async_trait::async_trait;
+use tesseract::Tesseract;
+use tokio::task;
+use tracing::debug;
+
+use crate::{
+    OcrBlock, OcrContext, OcrEngine, OcrLayout, OcrLine, OcrPayload, OcrRect, OcrWord, RgbaFrame,
+};
+
+/// Identifier of this engine in stored layouts, `ocr_engine` and cache keys.
+const ENGINE_NAME: &str = "tesseract";
+/// Used when none of the requested languages has traineddata installed.
+const FALLBACK_LANGUAGE: &str = "eng";
+
+/// Idle recognizers by language spec.
+type RecognizerPool = Arc<Mutex<HashMap<String, Vec<Tesseract>>>>;
+
+/// OCR engine backed by libtesseract. Loading traineddata takes longer than
+/// recognizing a window, so recognizers are kept for reuse; each image checks
+/// one out, so concurrent workers never share one.
+pub struct TesseractOcr {
+    data_path: Option<String>,
+    idle: RecognizerPool,
+}
+
+impl TesseractOcr {
+    /// `data_path` is the directory holding `*.traineddata`; `None` leaves the
+    /// choice to Tesseract (`TESSDATA_PREFIX`).
+    pub fn new(data_path: Option<String>) -> Self {
+        Self {
+            data_path,
+            idle: Arc::default(),
+        }
+    }
+
+    /// Tesseract language spec (`eng+deu`) for the requested languages. When
+    /// the data directory is known, languages without traineddata are left out.
+    fn language_spec(&self, languages: &[String]) -> String {
+        let mut codes: Vec<String> = Vec::new();
+        for language in languages {
+            let code = traineddata_name(language);
+            if codes.contains(&code) {
+                continue;
+            }
+            let installed = self.data_path.as_deref().is_none_or(|dir| {
+                Path::new(dir)
+                    .join(format!("{code}.traineddata"))
+                    .exists()
+            });
+            if installed {
+                codes.push(code);
+            } else {
+                debug!(language, code, "no tesseract traineddata; skipping language");
+            }
+        }
+        if codes.is_empty() {
+            FALLBACK_LANGUAGE.to_string()
+        } else {
+            codes.join("+")
+        }
+    }
+
+    /// Recognize on a blocking thread with an idle recognizer for the
+    /// languages, loading one if none is free. A recognizer that fails is
+    /// dropped rather than returned to the pool.
+    async fn run(
+        &self,
+        context: &OcrContext,
+        load: impl FnOnce(Tesseract) -> Result<Tesseract> + Send + 'static,
+    ) -> Result<OcrPayload> {
+        let languages = self.language_spec(&context.languages);
+        let data_path = self.data_path.clone();
+        let idle = self.idle.clone();
+        let spec = languages.clone();
+        let (text, tsv) = task::spawn_blocking(move || {
+            let reused = idle.lock().unwrap().get_mut(&spec).and_then(Vec::pop);
+            let engine = match reused {
+                Some(engine) => engine,
+                None => Tesseract::new(data_path.as_deref(), Some(spec.as_str()))
+                    .with_context(|| format!("failed to load tesseract languages {spec}"))?,
+            };
+            let mut engine = load(engine)?
+                .recognize()
+                .context("tesseract recognize failed")?;
+            let text = engine.get_text().context("failed to read tesseract text")?;
+            let tsv = engine
+                .get_tsv_text(0)
+                .context("failed to read tesseract word boxes")?;
+            idle.lock().unwrap().entry(spec).or_default().push(engine);
+            Ok::<_, anyhow::Error>((text, tsv))
+        })
+        .await
+        .context("tesseract task failed")??;
+
+        let mut layout = parse_tsv(&tsv);
+        layout.language = Some(languages);
+        let confidence = layout.confidence();
+
+        debug!(
+            window = %context.window_name,
+            app = %context.app_name,
+            lang = ?layout.language,
+            blocks = layout.blocks.len(),
+            confidence,
+            "tesseract ocr completed"
+        );
+
+        Ok(OcrPayload {
+            text: text.trim_end().to_string(),
+            confidence,
+            layout,
+        })
+    }
+}
+
+#[async_trait]
+impl OcrEngine for TesseractOcr {
+    async fn recognize(&self, image_bytes: &[u8], context: &OcrContext) -> Result<OcrPayload> {
+        let image_bytes = image_bytes.to_vec();
+        self.run(context, move |engine| {
+            engine
+                .set_image_from_mem(&image_bytes)
+                .context("tesseract could not decode the image")
+        })
+        .await
+    }
+
+    async fn recognize_rgba(
+        &self,
+        frame: RgbaFrame<'_>,
+        context: &OcrContext,
+    ) -> Result<OcrPayload> {
+        if frame.pixels.len() != frame.width as usize * frame.height as usize * 4 {
+            return Err(anyhow!(
+                "RGBA buffer of {} bytes does not match {}x{}",
+                frame.pixels.len(),
+                frame.width,
+                frame.height
+            ));
+        }
+        let (width, height) = (frame.width as i32, frame.height as i32);
+        let pixels = frame.pixels.to_vec();
+        self.run(context, move |engine| {
+            engine
+                .set_frame(&pixels, width, height, 4, width * 4)
+                .context("tesseract rejected the frame")
+        })
+        .await
+    }
+
+    fn name(&self) -> &'static str {
+        ENGINE_NAME
+    }
+}
+
+/// Map a language tag (`en`, `de-DE`, `zh-Hant`) to the name of its
+/// Tesseract traineddata. Unknown tags are passed through lowercased, so
+/// Tesseract codes like `eng` or `chi_sim` work as-is.
+fn traineddata_name(language: &str) -> String {
+    let tag = language.trim().to_lowercase().replace('_', "-");
+    let code = match tag.as_str() {
+        "zh-tw" | "zh-hk" | "zh-hant" => "chi_tra",
+        "chi-sim" => "chi_sim",
+        "chi-tra" => "chi_tra",
+        _ => match tag.split('-').next().unwrap_or_default() {
+            "en" => "eng",
+            "de" => "deu",
+            "fr" => "fra",
+            "es" => "spa",
+            "it" => "ita",
+            "pt" => "por",
+            "nl" => "nld",
+            "sv" => "swe",
+            "da" => "dan",
+            "no" | "nb" => "nor",
+            "fi" => "fin",
+            "pl" => "pol",
+            "cs" => "ces",
+            "sk" => "slk",
+            "hu" => "hun",
+            "ro" => "ron",
+            "tr" => "tur",
+            "el" => "ell",
+            "ru" => "rus",
+            "uk" => "ukr",
+            "ar" => "ara",
+            "he" => "heb",
+            "hi" => "hin",
+            "th" => "tha",
+            "vi" => "vie",
+            "id" => "ind",
+            "ja" => "jpn",
+            "ko" => "kor",
+            "zh" => "chi_sim",
+            _ => return tag,
+        },
+    };
+    code.to_string()
+}
+
+/// Layout from Tesseract's TSV output: `level page block par line word left
+/// top width height conf text`. The level 1 row gives the page size and level
+/// 5 rows are words, with `conf` from 0 to 100.
+fn parse_tsv(tsv: &str) -> OcrLayout {
+    let mut layout = OcrLayout {
+        engine: ENGINE_NAME.to_string(),
+        ..OcrLayout::default()
+    };
+    // Words of the current line, lines of the current block.
+    let mut words: Vec<OcrWord> = Vec::new();
+    let mut lines: Vec<OcrLine> = Vec::new();
+    let mut current_block: Option<&str> = None;
+    let mut current_line: Option<(&str, &str)> = None;
+
+    for row in tsv.lines() {
+        let fields: Vec<&str> = row.splitn(12, '\t').collect();
+        if fields.len() < 11 {
+            continue;
+        }
+        let pixels = |index: usize| fields[index].trim().parse::<i64>().unwrap_or(0).max(0) as u32;
+        match fields[0] {
+            "1" => {
+                layout.width = pixels(8);
+                layout.height = pixels(9);
+                continue;
+            }
+            "5" => {}
+            _ => continue,
+        }
+        let text = fields.get(11).map_or("", |text| text.trim());
+        let Ok(confidence) = fields[10].trim().parse::<f32>() else {
+            continue;
+        };
+        if text.is_empty() || confidence < 0.0 {
+            continue;
+        }
+
+        let block = fields[2];
+        let line = (fields[3], fields[4]);
+        if current_block.is_some_and(|current| current != block) {
+            close_line(&mut words, &mut lines);
+            close_block(&mut lines, &mut layout.blocks);
+        } else if current_line.is_some_and(|current| current != line) {
+            close_line(&mut words, &mut lines);
+        }
+        current_block = Some(block);
+        current_line = Some(line);
+
+        words.push(OcrWord {
+            text: text.to_string(),
+            bounds: OcrRect {
+                x: pixels(6),
+                y: pixels(7),
+                width: pixels(8),
+                height: pixels(9),
+            },
+            confidence: Some((confidence / 100.0).clamp(0.0, 1.0)),
+        });
+    }
+    close_line(&mut words, &mut lines);
+    close_block(&mut lines, &mut layout.blocks);
+    layout
+}
+
+fn close_line(words: &mut Vec<OcrWord>, lines: &mut Vec<OcrLine>) {
+    if !words.is_empty() {
+        lines.push(OcrLine::new(std::mem::take(words)));
+    }
+}
+
+fn close_block(lines: &mut Vec<OcrLine>, blocks: &mut Vec<OcrBlock>) {
+    if !lines.is_empty() {
+        blocks.push(OcrBlock::new(std::mem::take(lines)));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_TSV: &str = "\
+level\tpage_num\tblock_num\tpar_num\tline_num\tword_num\tleft\ttop\twidth\theight\tconf\ttext
+1\t1\t0\t0\t0\t0\t0\t0\t640\t480\t-1\t
+2\t1\t1\t0\t0\t0\t10\t10\t200\t40\t-1\t
+3\t1\t1\t1\t0\t0\t10\t10\t200\t40\t-1\t
+4\t1\t1\t1\t1\t0\t10\t10\t140\t20\t-1\t
+5\t1\t1\t1\t1\t1\t10\t10\t60\t20\t96.5\tHello
+5\t1\t1\t1\t1\t2\t80\t10\t70\t20\t91\tworld
+4\t1\t1\t1\t2\t0\t10\t30\t100\t20\t-1\t
+5\t1\t1\t1\t2\t1\t10\t30\t100\t20\t88\tSecond
+2\t1\t2\t0\t0\t0\t300\t200\t50\t20\t-1\t
+5\t1\t2\t1\t1\t1\t300\t200\t10\t20\t-1\t 
+5\t1\t2\t1\t1\t2\t310\t200\t40\t20\t75\tBlock
+";
+
+    #[test]
+    fn parse_tsv_groups_words_into_lines_and_blocks() {
+        let layout = parse_tsv(SAMPLE_TSV);
+
+        assert_eq!(layout.engine, "tesseract");
+        assert_eq!((layout.width, layout.height), (640, 480));
+        assert_eq!(layout.blocks.len(), 2);
+        let lines: Vec<&str> = layout.lines().map(|line| line.text.as_str()).collect();
+        assert_eq!(lines, ["Hello world", "Second", "Block"]);
+
+        let hello = &layout.blocks[0].lines[0].words[0];
+        assert_eq!(
+            hello.bounds,
+            OcrRect {
+                x: 10,
+                y: 10,
+                width: 60,
+                height: 20
+            }
+        );
+        assert!((hello.confidence.unwrap() - 0.965).abs() < 1e-6);
+    }
+
+    #[test]
+    fn parse_tsv_skips_empty_and_unrecognized_words() {
+        let layout = parse_tsv(SAMPLE_TSV);
+
+        // The blank word with confidence -1 in the second block is dropped.
+        assert_eq!(layout.blocks[1].lines[0].words.len(), 1);
+        assert!(parse_tsv("").blocks.is_empty());
+        assert!(parse_tsv("5\t1\t1\t1\t1\t1\t0\t0\t5\t5\tn/a\tword")
+            .blocks
+            .is_empty());
+    }
+
+    #[test]
+    fn traineddata_name_maps_language_tags() {
+        assert_eq!(traineddata_name("en"), "eng");
+        assert_eq!(traineddata_name("de-DE"), "deu");
+        assert_eq!(traineddata_name("pt_BR"), "por");
+        assert_eq!(traineddata_name("zh"), "chi_sim");
+        assert_eq!(traineddata_name("zh-Hant"), "chi_tra");
+        assert_eq!(traineddata_name("zh_TW"), "chi_tra");
+    }
+
+    #[test]
+    fn traineddata_name_passes_tesseract_codes_through() {
+        assert_eq!(traineddata_name("eng"), "eng");
+        assert_eq!(traineddata_name(" CHI_SIM "), "chi_sim");
+        assert_eq!(traineddata_name("frk"), "frk");
+    }
+}
@@ -0,0 +1,617 @@
+//! Content-addressed image blobs under `image_dir`.
+//!
+//! Each stored image is named after the SHA-256 of its bytes and sharded by
+//! the first two byte pairs of the hash (`ab/cd/abcd….webp`), so identical
+//! window images share one file. `image_blobs.ref_count` counts the
+//! `captured_windows` rows pointing at a blob and is kept up to date by
+//! triggers; a blob is deleted once nothing references it.
+//!
+//! A blob written for a window is pinned until the window row points at it
+//! (`release`), so garbage collection can't delete a file that was just
+//! found on disk and is about to be referenced again.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, MutexGuard};
+
+use anyhow::{Context, Result};
+use sha2::{Digest, Sha256};
+use sqlx::{FromRow, Pool, Sqlite};
+use tracing::{debug, info, warn};
+
+/// Hashes of written blobs not yet referenced, with how many writers are
+/// waiting on each. Its lock also serialises writers against collection.
+static PINNED: Mutex<BTreeMap<String, usize>> = Mutex::new(BTreeMap::new());
+
+/// Makes temporary file names unique between concurrent writers.
+static TEMP_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/// Lowercase hex SHA-256 of `bytes`.
+pub fn content_hash(bytes: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(bytes))
+}
+
+/// Where a blob with `hash` and file `extension` lives under `image_dir`.
+pub fn blob_path(image_dir: &Path, hash: &str, extension: &str) -> PathBuf {
+    let shard = |range: std::ops::Range<usize>| hash.get(range).unwrap_or("00").to_string();
+    image_dir
+        .join(shard(0..2))
+        .join(shard(2..4))
+        .join(format!("{hash}.{extension}"))
+}
+
+/// Write `bytes`, whose content hash is `hash`, to `path` unless a blob is
+/// already there, and pin the blob until `release`. Nothing is pinned when
+/// the write fails.
+pub fn write_blob(path: &Path, hash: &str, bytes: &[u8]) -> Result<()> {
+    let mut pinned = pinned();
+    if !path.exists() {
+        write_new(path, bytes)?;
+    }
+    *pinned.entry(hash.to_string()).or_default() += 1;
+    Ok(())
+}
+
+/// The data goes to a temporary file first so a crash never leaves a
+/// truncated blob behind.
+fn write_new(path: &Path, bytes: &[u8]) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).with_context(|| format!("failed to create {parent:?}"))?;
+    }
+    let temp = path.with_extension(format!(
+        "{}-{}.tmp",
+        process::id(),
+        TEMP_COUNTER.fetch_add(1, Ordering::Relaxed)
+    ));
+    fs::write(&temp, bytes).with_context(|| format!("failed to write {temp:?}"))?;
+    match fs::rename(&temp, path) {
+        Ok(()) => Ok(()),
+        // Another process stored the same content first.
+        Err(_) if path.exists() => {
+            let _ = fs::remove_file(&temp);
+            Ok(())
+        }
+        Err(err) => {
+            let _ = fs::remove_file(&temp);
+            Err(err).with_context(|| format!("failed to move {temp:?} into place"))
+        }
+    }
+}
+
+fn pinned() -> MutexGuard<'static, BTreeMap<String, usize>> {
+    PINNED
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// Unpin a blob from `write_blob` once its window row references it, or
+/// won't.
+pub(crate) fn release(hash: &str) {
+    let mut pinned = pinned();
+    if let Some(count) = pinned.get_mut(hash) {
+        *count -= 1;
+        if *count == 0 {
+            pinned.remove(hash);
+        }
+    }
+}
+
+pub(crate) async fn create_schema(pool: &Pool<Sqlite>) -> Result<()> {
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS image_blobs (
+            hash TEXT PRIMARY KEY,
+            path TEXT NOT NULL,
+            format TEXT,
+            bytes INTEGER,
+            ref_count INTEGER NOT NULL DEFAULT 0,
+            created_at_ms INTEGER NOT NULL DEFAULT (strftime('%s','now') * 1000)
+        );
+        "#,
+    )
+    .execute(pool)
+    .await?;
+
+    // Cascading deletes from `captures` fire these too, so retention pruning
+    // releases references without extra bookkeeping.
+    sqlx::query(
+        r#"
+        CREATE TRIGGER IF NOT EXISTS image_blobs_ref_insert
+        AFTER INSERT ON captured_windows
+        WHEN NEW.image_hash IS NOT NULL
+        BEGIN
+            UPDATE image_blobs SET ref_count = ref_count + 1 WHERE hash = NEW.image_hash;
+        END;
+        "#,
+    )
+    .execute(pool)
+    .await?;
+
+    sqlx::query(
+        r#"
+        CREATE TRIGGER IF NOT EXISTS image_blobs_ref_delete
+        AFTER DELETE ON captured_windows
+        WHEN OLD.image_hash IS NOT NULL
+        BEGIN
+            UPDATE image_blobs SET ref_count = ref_count - 1 WHERE hash = OLD.image_hash;
+        END;
+        "#,
+    )
+    .execute(pool)
+    .await?;
+
+    sqlx::query(
+        r#"
+        CREATE TRIGGER IF NOT EXISTS image_blobs_ref_update
+        AFTER UPDATE OF image_hash ON captured_windows
+        WHEN OLD.image_hash IS NOT NEW.image_hash
+        BEGIN
+            UPDATE image_blobs SET ref_count = ref_count - 1 WHERE hash = OLD.image_hash;
+            UPDATE image_blobs SET ref_count = ref_count + 1 WHERE hash = NEW.image_hash;
+        END;
+        "#,
+    )
+    .execute(pool)
+    .await?;
+
+    sqlx::query(
+        "CREATE INDEX IF NOT EXISTS idx_windows_image_hash ON captured_windows(image_hash)",
+    )
+    .execute(pool)
+    .await?;
+    sqlx::query("CREATE INDEX IF NOT EXISTS idx_image_blobs_ref_count ON image_blobs(ref_count)")
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+/// Record a blob so windows can reference it; a blob already known keeps its
+/// original row.
+pub(crate) async fn register(
+    conn: &mut sqlx::SqliteConnection,
+    hash: &str,
+    path: &str,
+    format: Option<&str>,
+    bytes: Option<i64>,
+) -> Result<()> {
+    sqlx::query(
+        r#"
+        INSERT INTO image_blobs (hash, path, format, bytes) VALUES (?, ?, ?, ?)
+        ON CONFLICT(hash) DO NOTHING
+        "#,
+    )
+    .bind(hash)
+    .bind(path)
+    .bind(format)
+    .bind(bytes)
+    .execute(conn)
+    .await?;
+    Ok(())
+}
+
+#[derive(FromRow)]
+struct BlobRow {
+    hash: String,
+    path: String,
+}
+
+/// Delete blobs that no window references any more, along with their files
+/// and any shard directories left empty. Returns how many were removed.
+pub(crate) async fn collect_garbage(pool: &Pool<Sqlite>) -> Result<usize> {
+    let unreferenced: Vec<BlobRow> =
+        sqlx::query_as("SELECT hash, path FROM image_blobs WHERE ref_count <= 0")
+            .fetch_all(pool)
+            .await?;
+
+    let mut removed = 0;
+    for blob in unreferenced {
+        let path = Path::new(&blob.path);
+        {
+            // Held until the file is gone, so no writer finds it meanwhile.
+            let pinned = pinned();
+            if pinned.contains_key(&blob.hash) {
+                continue;
+            }
+            match fs::remove_file(path) {
+                Ok(()) => {}
+                Err(err) if err.kind() == ErrorKind::NotFound => {}
+                Err(err) => {
+                    warn!(path = blob.path, "failed to delete image blob: {err}");
+                    continue;
+                }
+            }
+            // Shard directories are only removed when empty; errors just mean
+            // another blob still lives there.
+            for dir in path.ancestors().skip(1).take(2) {
+                if fs::remove_dir(dir).is_err() {
+                    break;
+                }
+            }
+        }
+        sqlx::query("DELETE FROM image_blobs WHERE hash = ? AND ref_count <= 0")
+            .bind(&blob.hash)
+            .execute(pool)
+            .await?;
+        removed += 1;
+    }
+    if removed > 0 {
+        debug!(removed, "deleted unreferenced image blobs");
+    }
+    Ok(removed)
+}
+
+#[derive(FromRow)]
+struct LegacyImageRow {
+    image_path: String,
+    image_format: Option<String>,
+}
+
+/// Copy images written before content addressing (`frame_{ts}_{frame}_{idx}`)
+/// into the blob store and point their rows at the blob, then delete legacy
+/// files no row references. Identical legacy files collapse into one blob.
+/// Rows whose file is missing are left as is.
+pub(crate) async fn migrate_legacy_images(pool: &Pool<Sqlite>, image_dir: &Path) -> Result<()> {
+    let rows: Vec<LegacyImageRow> = sqlx::query_as(
+        r#"
+        SELECT image_path, MAX(image_format) AS image_format
+        FROM captured_windows
+        WHERE image_path IS NOT NULL AND image_hash IS NULL
+        GROUP BY image_path
+        "#,
+    )
+    .fetch_all(pool)
+    .await?;
+    if !rows.is_empty() {
+        move_legacy_images(pool, image_dir, rows).await?;
+    }
+    remove_unreferenced_legacy_images(pool, image_dir).await
+}
+
+/// Each file is copied into place and its rows repointed before the original
+/// is removed, so an interrupted run leaves every row with a readable image.
+/// Originals left behind are unreferenced and go in the next cleanup.
+async fn move_legacy_images(
+    pool: &Pool<Sqlite>,
+    image_dir: &Path,
+    rows: Vec<LegacyImageRow>,
+) -> Result<()> {
+    info!(
+        files = rows.len(),
+        "moving window images into the content-addressed store"
+    );
+    let mut moved_to: HashMap<String, PathBuf> = HashMap::new();
+    let mut migrated = 0;
+    for row in rows {
+        let old_path = PathBuf::from(&row.image_path);
+        let bytes = match fs::read(&old_path) {
+            Ok(bytes) => bytes,
+            Err(err) => {
+                warn!(path = row.image_path, "cannot migrate window image: {err}");
+                continue;
+            }
+        };
+        let hash = content_hash(&bytes);
+        let extension = old_path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .unwrap_or("png")
+            .to_lowercase();
+        let new_path = moved_to
+            .entry(hash.clone())
+            .or_insert_with(|| blob_path(image_dir, &hash, &extension))
+            .clone();
+
+        write_blob(&new_path, &hash, &bytes)?;
+        let repointed = repoint_legacy_rows(pool, &row, &hash, &new_path, bytes.len()).await;
+        release(&hash);
+        repointed?;
+
+        match fs::remove_file(&old_path) {
+            Ok(()) => {}
+            Err(err) if err.kind() == ErrorKind::NotFound => {}
+            Err(err) => warn!(
+                path = row.image_path,
+                "failed to remove migrated image: {err}"
+            ),
+        }
+        migrated += 1;
+    }
+    info!(
+        migrated,
+        blobs = moved_to.len(),
+        "window image migration finished"
+    );
+    Ok(())
+}
+
+async fn repoint_legacy_rows(
+    pool: &Pool<Sqlite>,
+    row: &LegacyImageRow,
+    hash: &str,
+    new_path: &Path,
+    bytes: usize,
+) -> Result<()> {
+    let new_path = new_path.to_string_lossy().to_string();
+    let mut tx = pool.begin().await?;
+    register(
+        &mut tx,
+        hash,
+        &new_path,
+        row.image_format.as_deref(),
+        Some(bytes as i64),
+    )
+    .await?;
+    sqlx::query(
+        "UPDATE captured_windows SET image_path = ?, image_hash = ? WHERE image_path = ? AND image_hash IS NULL",
+    )
+    .bind(&new_path)
+    .bind(hash)
+    .bind(&row.image_path)
+    .execute(&mut *tx)
+    .await?;
+    tx.commit().await?;
+    Ok(())
+}
+
+/// Delete `frame_*` files in `image_dir` that no window row points at, such
+/// as images whose rows were pruned before they could be migrated.
+async fn remove_unreferenced_legacy_images(pool: &Pool<Sqlite>, image_dir: &Path) -> Result<()> {
+    let entries = match fs::read_dir(image_dir) {
+        Ok(entries) => entries,
+        Err(err) if err.kind() == ErrorKind::NotFound => return Ok(()),
+        Err(err) => return Err(err).with_context(|| format!("failed to list {image_dir:?}")),
+    };
+    let referenced: HashSet<String> = sqlx::query_scalar::<_, String>(
+        "SELECT DISTINCT image_path FROM captured_windows WHERE image_path IS NOT NULL AND image_hash IS NULL",
+    )
+    .fetch_all(pool)
+    .await?
+    .iter()
+    .filter_map(|path| Path::new(path).file_name())
+    .map(|name| name.to_string_lossy().to_string())
+    .collect();
+
+    let mut removed = 0;
+    for entry in entries.flatten() {
+        let name = entry.file_name().to_string_lossy().to_string();
+        let is_file = entry.file_type().is_ok_and(|kind| kind.is_file());
+        if !is_file || !name.starts_with("frame_") || referenced.contains(&name) {
+            continue;
+        }
+        match fs::remove_file(entry.path()) {
+            Ok(()) => removed += 1,
+            Err(err) => warn!(file = name, "failed to delete legacy window image: {err}"),
+        }
+    }
+    if removed > 0 {
+        info!(removed, "deleted unreferenced legacy window images");
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::SqliteSink;
+
+    /// A fresh directory and database for one test.
+    async fn setup(name: &str) -> (PathBuf, SqliteSink) {
+        let dir = std::env::temp_dir().join(format!("memri-blobs-{}-{name}", process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let url = format!("sqlite://{}?mode=rwc", dir.join("memri.db").display());
+        let sink = SqliteSink::connect(&url).await.unwrap();
+        (dir, sink)
+    }
+
+    async fn insert_capture(pool: &Pool<Sqlite>) -> i64 {
+        sqlx::query("INSERT INTO captures (frame_number, timestamp_ms) VALUES (1, 1000)")
+            .execute(pool)
+            .await
+            .unwrap()
+            .last_insert_rowid()
+    }
+
+    async fn insert_window(
+        pool: &Pool<Sqlite>,
+        capture_id: i64,
+        image_path: &str,
+        image_hash: Option<&str>,
+    ) -> i64 {
+        sqlx::query(
+            "INSERT INTO captured_windows (capture_id, image_path, image_hash) VALUES (?, ?, ?)",
+        )
+        .bind(capture_id)
+        .bind(image_path)
+        .bind(image_hash)
+        .execute(pool)
+        .await
+        .unwrap()
+        .last_insert_rowid()
+    }
+
+    async fn add_blob(pool: &Pool<Sqlite>, dir: &Path, bytes: &[u8]) -> (String, PathBuf) {
+        let hash = content_hash(bytes);
+        let path = blob_path(dir, &hash, "png");
+        write_blob(&path, &hash, bytes).unwrap();
+        let mut conn = pool.acquire().await.unwrap();
+        register(&mut conn, &hash, &path.to_string_lossy(), Some("png"), None)
+            .await
+            .unwrap();
+        (hash, path)
+    }
+
+    async fn ref_count(pool: &Pool<Sqlite>, hash: &str) -> Option<i64> {
+        sqlx::query_scalar("SELECT ref_count FROM image_blobs WHERE hash = ?")
+            .bind(hash)
+            .fetch_optional(pool)
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn ref_count_follows_window_rows() {
+        let (dir, sink) = setup("ref-count").await;
+        let pool = &sink.pool;
+        let (first, _) = add_blob(pool, &dir, b"ref-count first").await;
+        let (second, _) = add_blob(pool, &dir, b"ref-count second").await;
+        release(&first);
+        release(&second);
+
+        let capture_id = insert_capture(pool).await;
+        let window_id = insert_window(pool, capture_id, "a.png", Some(&first)).await;
+        insert_window(pool, capture_id, "a.png", Some(&first)).await;
+        insert_window(pool, capture_id, "legacy.png", None).await;
+        assert_eq!(ref_count(pool, &first).await, Some(2));
+        assert_eq!(ref_count(pool, &second).await, Some(0));
+
+        sqlx::query("UPDATE captured_windows SET image_hash = ? WHERE id = ?")
+            .bind(&second)
+            .bind(window_id)
+            .execute(pool)
+            .await
+            .unwrap();
+        assert_eq!(ref_count(pool, &first).await, Some(1));
+        assert_eq!(ref_count(pool, &second).await, Some(1));
+
+        // Deleting the capture cascades to its windows.
+        sqlx::query("DELETE FROM captures WHERE id = ?")
+            .bind(capture_id)
+            .execute(pool)
+            .await
+            .unwrap();
+        assert_eq!(ref_count(pool, &first).await, Some(0));
+        assert_eq!(ref_count(pool, &second).await, Some(0));
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn garbage_collection_keeps_pinned_and_referenced_blobs() {
+        let (dir, sink) = setup("gc").await;
+        let pool = &sink.pool;
+        let (referenced, referenced_path) = add_blob(pool, &dir, b"gc referenced").await;
+        let (pinned_hash, pinned_path) = add_blob(pool, &dir, b"gc pinned").await;
+        let (unused, unused_path) = add_blob(pool, &dir, b"gc unused").await;
+        let capture_id = insert_capture(pool).await;
+        insert_window(pool, capture_id, "r.png", Some(&referenced)).await;
+        release(&referenced);
+        release(&unused);
+
+        assert_eq!(collect_garbage(pool).await.unwrap(), 1);
+        assert!(referenced_path.exists());
+        assert!(pinned_path.exists());
+        assert!(!unused_path.exists());
+        // Its shard directories went with it.
+        assert!(!unused_path.parent().unwrap().exists());
+        assert_eq!(ref_count(pool, &unused).await, None);
+
+        // Once released, an unreferenced blob goes on the next run.
+        release(&pinned_hash);
+        assert_eq!(collect_garbage(pool).await.unwrap(), 1);
+        assert!(!pinned_path.exists());
+        assert!(referenced_path.exists());
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn failed_writes_pin_nothing() {
+        let dir = std::env::temp_dir().join(format!("memri-blobs-{}-failed", process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        // A file where the shard directory should be.
+        fs::write(dir.join("blocker"), b"").unwrap();
+        let bytes = b"failed write";
+        let hash = content_hash(bytes);
+        let path = dir.join("blocker").join("blob.png");
+
+        assert!(write_blob(&path, &hash, bytes).is_err());
+        assert!(!pinned().contains_key(&hash));
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn legacy_images_move_into_shared_blobs() {
+        let (dir, sink) = setup("legacy").await;
+        let pool = &sink.pool;
+        let image_dir = dir.join("images");
+        fs::create_dir_all(&image_dir).unwrap();
+        let legacy = |name: &str, bytes: &[u8]| {
+            let path = image_dir.join(name);
+            fs::write(&path, bytes).unwrap();
+            path.to_string_lossy().to_string()
+        };
+        let duplicate_a = legacy("frame_1_1_0.png", b"legacy same");
+        let duplicate_b = legacy("frame_2_2_0.png", b"legacy same");
+        let other = legacy("frame_3_3_0.png", b"legacy other");
+        let orphan = legacy("frame_4_4_0.png", b"legacy orphan");
+        let capture_id = insert_capture(pool).await;
+        for path in [&duplicate_a, &duplicate_a, &duplicate_b, &other] {
+            insert_window(pool, capture_id, path, None).await;
+        }
+        insert_window(pool, capture_id, "/missing/frame_5_5_0.png", None).await;
+
+        migrate_legacy_images(pool, &image_dir).await.unwrap();
+
+        let same = content_hash(b"legacy same");
+        let same_path = blob_path(&image_dir, &same, "png");
+        assert_eq!(fs::read(&same_path).unwrap(), b"legacy same");
+        assert_eq!(ref_count(pool, &same).await, Some(3));
+        assert_eq!(
+            ref_count(pool, &content_hash(b"legacy other")).await,
+            Some(1)
+        );
+        let blobs: i64 = sqlx::query_scalar("SELECT COUNT(1) FROM image_blobs")
+            .fetch_one(pool)
+            .await
+            .unwrap();
+        assert_eq!(blobs, 2);
+
+        let rows: Vec<(String, Option<String>)> =
+            sqlx::query_as("SELECT image_path, image_hash FROM captured_windows ORDER BY id")
+                .fetch_all(pool)
+                .await
+                .unwrap();
+        for (path, hash) in &rows[..3] {
+            assert_eq!(path, &same_path.to_string_lossy());
+            assert_eq!(hash.as_deref(), Some(same.as_str()));
+        }
+        // A row whose file is gone keeps its old path.
+        assert_eq!(rows[4], ("/missing/frame_5_5_0.png".to_string(), None));
+
+        for path in [&duplicate_a, &duplicate_b, &other, &orphan] {
+            assert!(!Path::new(path).exists(), "{path} left behind");
+        }
+        assert!(!pinned().contains_key(&same));
+
+        // Running again changes nothing.
+        migrate_legacy_images(pool, &image_dir).await.unwrap();
+        assert_eq!(ref_count(pool, &same).await, Some(3));
+        assert!(same_path.exists());
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn originals_left_by_an_interrupted_migration_are_cleaned_up() {
+        let (dir, sink) = setup("interrupted").await;
+        let pool = &sink.pool;
+        let image_dir = dir.join("images");
+        fs::create_dir_all(&image_dir).unwrap();
+        // The row was repointed, but the process stopped before the original
+        // was removed.
+        let (hash, path) = add_blob(pool, &image_dir, b"interrupted").await;
+        release(&hash);
+        let capture_id = insert_capture(pool).await;
+        insert_window(pool, capture_id, &path.to_string_lossy(), Some(&hash)).await;
+        let original = image_dir.join("frame_1_1_0.png");
+        fs::write(&original, b"interrupted").unwrap();
+
+        migrate_legacy_images(pool, &image_dir).await.unwrap();
+        assert!(!original.exists());
+        assert!(path.exists());
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -2,27 +2,53 @@
 //!
 //! Uses sqlx for async database access with Tokio.
 
+mod blobs;
+mod ocr_cache;
+
 use anyhow::Result;
 use async_trait::async_trait;
 use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
 use memri_config::AppConfig;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use sqlx::{
     sqlite::{SqlitePoolOptions, SqliteQueryResult},
     FromRow, Pool, QueryBuilder, Sqlite,
 };
 use std::collections::{BTreeMap, HashMap};
 use std::fs;
+use std::path::Path;
 use std::time::{Duration, SystemTime, UNIX_EPOCH};
 use tokio::sync::RwLock;
 use tracing::{info, warn};
 
+pub use blobs::{blob_path, content_hash, write_blob};
+
+/// Axis-aligned rectangle in screen pixel coordinates.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ScreenRect {
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+}
+
 /// Incoming capture batch containing summary information.
 #[derive(Debug, Clone, Serialize)]
 pub struct CaptureBatch {
     pub frame_number: u64,
     pub timestamp_ms: i64,
+    pub monitor_id: u32,
+    /// Physical display the frame came from; empty names are stored as `None`.
+    pub monitor_name: Option<String>,
+    pub monitor_width: u32,
+    pub monitor_height: u32,
+    /// Monitor areas that changed since the previous stored frame.
+    pub changed_regions: Vec<ScreenRect>,
     pub windows: Vec<CapturedWindowRecord>,
+    /// User note attached to a manually requested capture.
+    pub note: Option<String>,
+    /// Frames sampled and discarded while waiting for the screen to settle.
+    pub settle_skipped_frames: u32,
 }
 
 #[derive(Debug, Clone, Serialize)]
@@ -31,10 +57,117 @@ pub struct CapturedWindowRecord {
     pub app_name: String,
     pub text: String,
     pub confidence: Option<f32>,
+    /// Serialized OCR layout (blocks, lines and words with their boxes);
+    /// windows stored before layouts were recorded hold a short summary.
     pub ocr_json: Option<String>,
+    /// OCR engine that produced `text`; inside a composite engine, the one
+    /// whose result was kept.
+    pub ocr_engine: Option<String>,
     pub image_base64: Option<String>,
     pub image_path: Option<String>,
     pub browser_url: Option<String>,
+    /// Row id of an earlier window whose OCR and image were reused because
+    /// the window had not changed.
+    pub reused_window_id: Option<i64>,
+    /// 64-bit perceptual (difference) hash of the window image, stored as its
+    /// two's-complement bit pattern.
+    pub phash: Option<i64>,
+    /// Whether `text`, `image_path` and the OCR fields have been filled in yet.
+    pub ocr_status: OcrStatus,
+    /// Encoding of the stored image (`webp`, `jpeg`, `png` or `avif`) and its
+    /// size on disk; unknown for images written before this was recorded.
+    pub image_format: Option<String>,
+    pub image_bytes: Option<i64>,
+    /// SHA-256 of the stored image bytes, naming its blob in `image_dir`.
+    pub image_hash: Option<String>,
+    /// Owning process and where the window sat on screen when captured; all
+    /// unknown for windows stored before this was recorded.
+    pub process_id: Option<i32>,
+    pub executable_path: Option<String>,
+    /// Position and size on the virtual screen.
+    pub bounds: Option<ScreenRect>,
+    /// Stacking position; higher values are closer to the front.
+    pub z_order: Option<i32>,
+    /// Monitor the window was assigned to.
+    pub monitor_id: Option<u32>,
+    pub is_focused: Option<bool>,
+}
+
+/// Progress of a window's OCR, which finishes after the capture is stored.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OcrStatus {
+    /// Queued; text and image are filled in when a worker finishes.
+    Pending,
+    #[default]
+    Done,
+    /// OCR failed; the image is stored but there is no text.
+    Failed,
+    /// Discarded by queue backpressure before OCR ran.
+    Dropped,
+}
+
+impl OcrStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Pending => "pending",
+            Self::Done => "done",
+            Self::Failed => "failed",
+            Self::Dropped => "dropped",
+        }
+    }
+
+    /// Rows written before OCR ran asynchronously have no status and count as done.
+    fn from_column(value: Option<&str>) -> Self {
+        match value {
+            Some("pending") => Self::Pending,
+            Some("failed") => Self::Failed,
+            Some("dropped") => Self::Dropped,
+            _ => Self::Done,
+        }
+    }
+}
+
+/// OCR output written back to a window that was stored as pending.
+#[derive(Debug, Clone)]
+pub struct WindowOcrUpdate {
+    pub status: OcrStatus,
+    pub text: String,
+    pub confidence: Option<f32>,
+    pub ocr_json: Option<String>,
+    pub ocr_engine: Option<String>,
+    pub image_path: Option<String>,
+    pub image_format: Option<String>,
+    pub image_bytes: Option<i64>,
+    pub image_hash: Option<String>,
+}
+
+/// OCR layout stored for one window of a capture.
+#[derive(Debug, Clone, FromRow)]
+pub struct WindowLayout {
+    pub capture_id: i64,
+    pub window_name: Option<String>,
+    pub app_name: Option<String>,
+    pub image_hash: Option<String>,
+    pub ocr_json: String,
+}
+
+/// Recently stored window with its perceptual hash, used to warm the
+/// long-range dedupe index.
+#[derive(Debug, Clone)]
+pub struct HashedWindow {
+    pub window_id: i64,
+    pub timestamp_ms: i64,
+    pub phash: i64,
+    pub record: CapturedWindowRecord,
+}
+
+/// Row ids assigned to a persisted batch; `window_ids` follows the order of
+/// `CaptureBatch::windows`.
+#[derive(Debug, Clone, Default)]
+pub struct PersistedBatch {
+    pub capture_id: i64,
+    pub window_ids: Vec<i64>,
 }
 
 /// Capture with inlined windows, convenient for API responses.
@@ -43,7 +176,41 @@ pub struct CaptureWithWindows {
     pub capture_id: i64,
     pub frame_number: i64,
     pub timestamp_ms: i64,
+    /// Monitor the frame was taken from; unknown for captures stored before
+    /// monitors were recorded.
+    pub monitor_id: Option<u32>,
+    pub monitor_name: Option<String>,
+    pub monitor_width: Option<u32>,
+    pub monitor_height: Option<u32>,
+    pub changed_regions: Vec<ScreenRect>,
     pub windows: Vec<CapturedWindowRecord>,
+    pub note: Option<String>,
+    /// Frames discarded while the screen settled; `None` for captures stored
+    /// before this was recorded.
+    pub settle_skipped_frames: Option<u32>,
+}
+
+/// Stretch of time during which one window kept focus.
+#[derive(Debug, Clone, Serialize)]
+pub struct FocusSession {
+    /// Row id once the session has been stored.
+    pub id: Option<i64>,
+    pub app_name: String,
+    pub window_name: String,
+    pub monitor_id: Option<u32>,
+    pub start_ms: i64,
+    pub end_ms: i64,
+}
+
+/// Focused time summed over a range, per app or per app and window title.
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct UsageTotal {
+    pub app_name: String,
+    /// Set for per-title totals.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub window_name: Option<String>,
+    pub total_ms: i64,
+    pub sessions: i64,
 }
 
 /// Simple chat record model.
@@ -57,7 +224,31 @@ pub struct ChatMessage {
 
 #[async_trait]
 pub trait CaptureSink: Send + Sync {
-    async fn persist_batch(&self, batch: CaptureBatch) -> Result<()>;
+    async fn persist_batch(&self, batch: CaptureBatch) -> Result<PersistedBatch>;
+
+    /// Original (non-reused) windows stored at or after `since_ms` that carry
+    /// a perceptual hash. Sinks without history return nothing.
+    async fn recent_window_hashes(&self, _since_ms: i64) -> Result<Vec<HashedWindow>> {
+        Ok(Vec::new())
+    }
+
+    /// Highest frame number stored so far, so numbering continues across
+    /// restarts. Sinks without history return `None`.
+    async fn last_frame_number(&self) -> Result<Option<u64>> {
+        Ok(None)
+    }
+
+    /// Fill in the OCR results of a pending window and of every window that
+    /// reused it. Sinks that don't store windows ignore this.
+    async fn update_window_ocr(&self, _window_id: i64, _update: WindowOcrUpdate) -> Result<()> {
+        Ok(())
+    }
+
+    /// Insert a focus session (when `id` is `None`) or move the end of a
+    /// stored one, returning the row id. Sinks without history return `None`.
+    async fn save_focus_session(&self, _session: &FocusSession) -> Result<Option<i64>> {
+        Ok(None)
+    }
 }
 
 /// Concrete SQLite-backed sink.
@@ -95,6 +286,11 @@ impl SqliteSink {
         } else {
             Some(config.max_captures)
         };
+        if let Err(err) =
+            blobs::migrate_legacy_images(&sink.pool, Path::new(&config.image_dir)).await
+        {
+            warn!("window image migration failed: {err}");
+        }
         Ok(sink)
     }
 
@@ -105,7 +301,14 @@ impl SqliteSink {
             CREATE TABLE IF NOT EXISTS captures (
                 id INTEGER PRIMARY KEY AUTOINCREMENT,
                 frame_number INTEGER NOT NULL,
-                timestamp_ms INTEGER NOT NULL
+                timestamp_ms INTEGER NOT NULL,
+                changed_regions TEXT,
+                note TEXT,
+                monitor_id INTEGER,
+                monitor_name TEXT,
+                monitor_width INTEGER,
+                monitor_height INTEGER,
+                settle_skipped_frames INTEGER
             );
             "#,
         )
@@ -122,9 +325,25 @@ impl SqliteSink {
                 text TEXT,
                 confidence REAL,
                 ocr_json TEXT,
+                ocr_engine TEXT,
                 image_base64 TEXT,
                 image_path TEXT,
-                browser_url TEXT
+                browser_url TEXT,
+                reused_window_id INTEGER,
+                phash INTEGER,
+                ocr_status TEXT,
+                image_format TEXT,
+                image_bytes INTEGER,
+                image_hash TEXT,
+                process_id INTEGER,
+                executable_path TEXT,
+                window_x INTEGER,
+                window_y INTEGER,
+                window_width INTEGER,
+                window_height INTEGER,
+                z_order INTEGER,
+                monitor_id INTEGER,
+                is_focused INTEGER
             );
             "#,
         )
@@ -138,6 +357,82 @@ impl SqliteSink {
         let _ = sqlx::query("ALTER TABLE captured_windows ADD COLUMN image_path TEXT")
             .execute(&self.pool)
             .await;
+        let _ = sqlx::query("ALTER TABLE captures ADD COLUMN changed_regions TEXT")
+            .execute(&self.pool)
+            .await;
+        let _ = sqlx::query("ALTER TABLE captured_windows ADD COLUMN reused_window_id INTEGER")
+            .execute(&self.pool)
+            .await;
+        let _ = sqlx::query("ALTER TABLE captured_windows ADD COLUMN phash INTEGER")
+            .execute(&self.pool)
+            .await;
+        let _ = sqlx::query("ALTER TABLE captures ADD COLUMN note TEXT")
+            .execute(&self.pool)
+            .await;
+        let _ = sqlx::query("ALTER TABLE captures ADD COLUMN monitor_id INTEGER")
+            .execute(&self.pool)
+            .await;
+        let _ = sqlx::query("ALTER TABLE captures ADD COLUMN monitor_name TEXT")
+            .execute(&self.pool)
+            .await;
+        let _ = sqlx::query("ALTER TABLE captures ADD COLUMN monitor_width INTEGER")
+            .execute(&self.pool)
+            .await;
+        let _ = sqlx::query("ALTER TABLE captures ADD COLUMN monitor_height INTEGER")
+            .execute(&self.pool)
+            .await;
+        let _ = sqlx::query("ALTER TABLE captures ADD COLUMN settle_skipped_frames INTEGER")
+            .execute(&self.pool)
+            .await;
+        let _ = sqlx::query("ALTER TABLE captured_windows ADD COLUMN ocr_status TEXT")
+            .execute(&self.pool)
+            .await;
+        let _ = sqlx::query("ALTER TABLE captured_windows ADD COLUMN image_format TEXT")
+            .execute(&self.pool)
+            .await;
+        let _ = sqlx::query("ALTER TABLE captured_windows ADD COLUMN image_bytes INTEGER")
+            .execute(&self.pool)
+            .await;
+        let _ = sqlx::query("ALTER TABLE captured_windows ADD COLUMN image_hash TEXT")
+            .execute(&self.pool)
+            .await;
+        let _ = sqlx::query("ALTER TABLE captured_windows ADD COLUMN ocr_engine TEXT")
+            .execute(&self.pool)
+            .await;
+        for column in [
+            "process_id INTEGER",
+            "executable_path TEXT",
+            "window_x INTEGER",
+            "window_y INTEGER",
+            "window_width INTEGER",
+            "window_height INTEGER",
+            "z_order INTEGER",
+            "monitor_id INTEGER",
+            "is_focused INTEGER",
+        ] {
+            let _ = sqlx::query(&format!("ALTER TABLE captured_windows ADD COLUMN {column}"))
+                .execute(&self.pool)
+                .await;
+        }
+
+        // Reference-counted image blobs; needs `captured_windows.image_hash`.
+        blobs::create_schema(&self.pool).await?;
+        ocr_cache::create_schema(&self.pool).await?;
+
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS focus_sessions (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                app_name TEXT NOT NULL,
+                window_name TEXT NOT NULL,
+                monitor_id INTEGER,
+                start_ms INTEGER NOT NULL,
+                end_ms INTEGER NOT NULL
+            );
+            "#,
+        )
+        .execute(&self.pool)
+        .await?;
 
         // Chat history storage.
         sqlx::query(
@@ -172,6 +467,15 @@ impl SqliteSink {
         .execute(&self.pool)
         .await?;
 
+        sqlx::query(
+            r#"
+            CREATE INDEX IF NOT EXISTS idx_focus_sessions_range
+            ON focus_sessions(start_ms, end_ms);
+            "#,
+        )
+        .execute(&self.pool)
+        .await?;
+
         sqlx::query(
             r#"
             CREATE INDEX IF NOT EXISTS idx_chat_created_at
@@ -187,23 +491,37 @@ impl SqliteSink {
 
 #[async_trait]
 impl CaptureSink for SqliteSink {
-    async fn persist_batch(&self, batch: CaptureBatch) -> Result<()> {
+    async fn persist_batch(&self, batch: CaptureBatch) -> Result<PersistedBatch> {
         let mut conn = self.pool.acquire().await?;
 
-        let insert_result: SqliteQueryResult =
-            sqlx::query("INSERT INTO captures (frame_number, timestamp_ms) VALUES (?, ?)")
-                .bind(batch.frame_number as i64)
-                .bind(batch.timestamp_ms)
-                .execute(&mut *conn)
-                .await?;
+        let changed_regions = serde_json::to_string(&batch.changed_regions)?;
+        let insert_result: SqliteQueryResult = sqlx::query(
+            r#"INSERT INTO captures (
+                frame_number, timestamp_ms, monitor_id, monitor_name, monitor_width, monitor_height, changed_regions, note,
+                settle_skipped_frames
+            ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)"#,
+        )
+        .bind(batch.frame_number as i64)
+        .bind(batch.timestamp_ms)
+        .bind(batch.monitor_id as i64)
+        .bind(&batch.monitor_name)
+        .bind(batch.monitor_width as i64)
+        .bind(batch.monitor_height as i64)
+        .bind(changed_regions)
+        .bind(&batch.note)
+        .bind(batch.settle_skipped_frames as i64)
+        .execute(&mut *conn)
+        .await?;
 
         let capture_id = insert_result.last_insert_rowid();
 
+        let mut window_ids = Vec::with_capacity(batch.windows.len());
         for window in batch.windows.iter() {
-            sqlx::query(
+            let window_result = sqlx::query(
                 r#"INSERT INTO captured_windows (
-                    capture_id, window_name, app_name, text, confidence, ocr_json, image_base64, image_path, browser_url
-                ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)"#,
+                    capture_id, window_name, app_name, text, confidence, ocr_json, image_base64, image_path, browser_url, reused_window_id, phash, ocr_status, image_format, image_bytes, image_hash,
+                    process_id, executable_path, window_x, window_y, window_width, window_height, z_order, monitor_id, is_focused, ocr_engine
+                ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)"#,
             )
             .bind(capture_id)
             .bind(window.window_name.clone())
@@ -214,8 +532,25 @@ impl CaptureSink for SqliteSink {
             .bind(window.image_base64.clone())
             .bind(window.image_path.clone())
             .bind(window.browser_url.clone())
+            .bind(window.reused_window_id)
+            .bind(window.phash)
+            .bind(window.ocr_status.as_str())
+            .bind(window.image_format.clone())
+            .bind(window.image_bytes)
+            .bind(window.image_hash.clone())
+            .bind(window.process_id)
+            .bind(window.executable_path.clone())
+            .bind(window.bounds.map(|bounds| bounds.x))
+            .bind(window.bounds.map(|bounds| bounds.y))
+            .bind(window.bounds.map(|bounds| bounds.width as i64))
+            .bind(window.bounds.map(|bounds| bounds.height as i64))
+            .bind(window.z_order)
+            .bind(window.monitor_id.map(|id| id as i64))
+            .bind(window.is_focused)
+            .bind(window.ocr_engine.clone())
             .execute(&mut *conn)
             .await?;
+            window_ids.push(window_result.last_insert_rowid());
         }
 
         if let Err(err) = self.prune().await {
@@ -224,10 +559,89 @@ impl CaptureSink for SqliteSink {
 
         info!(
             frame = batch.frame_number,
+            monitor = batch.monitor_id,
             windows = batch.windows.len(),
             "persisted capture"
         );
-        Ok(())
+        Ok(PersistedBatch {
+            capture_id,
+            window_ids,
+        })
+    }
+
+    async fn recent_window_hashes(&self, since_ms: i64) -> Result<Vec<HashedWindow>> {
+        let rows: Vec<HashedWindowRow> = sqlx::query_as(
+            r#"
+            SELECT cw.id, c.timestamp_ms, cw.capture_id, cw.window_name, cw.app_name, cw.text,
+                   cw.confidence, cw.ocr_json, NULL as image_base64, cw.image_path, cw.browser_url,
+                   cw.reused_window_id, cw.phash, cw.ocr_status, cw.image_format, cw.image_bytes,
+                   cw.image_hash, cw.process_id, cw.executable_path, cw.window_x, cw.window_y,
+                   cw.window_width, cw.window_height, cw.z_order, cw.monitor_id, cw.is_focused,
+                   cw.ocr_engine
+            FROM captured_windows cw
+            JOIN captures c ON c.id = cw.capture_id
+            WHERE c.timestamp_ms >= ?
+              AND cw.phash IS NOT NULL
+              AND cw.reused_window_id IS NULL
+              AND cw.image_path IS NOT NULL
+            ORDER BY c.timestamp_ms ASC
+            "#,
+        )
+        .bind(since_ms)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .filter_map(|row| {
+                let phash = row.window.phash?;
+                Some(HashedWindow {
+                    window_id: row.id,
+                    timestamp_ms: row.timestamp_ms,
+                    phash,
+                    record: row.window.into_record(),
+                })
+            })
+            .collect())
+    }
+
+    async fn last_frame_number(&self) -> Result<Option<u64>> {
+        let last: Option<i64> = sqlx::query_scalar("SELECT MAX(frame_number) FROM captures")
+            .fetch_one(&self.pool)
+            .await?;
+        Ok(last.map(|frame| frame.max(0) as u64))
+    }
+
+    async fn update_window_ocr(&self, window_id: i64, update: WindowOcrUpdate) -> Result<()> {
+        let result = self.write_window_ocr(window_id, &update).await;
+        // Referenced now, or never will be: either way GC may have the blob.
+        if let Some(hash) = &update.image_hash {
+            blobs::release(hash);
+        }
+        result
+    }
+
+    async fn save_focus_session(&self, session: &FocusSession) -> Result<Option<i64>> {
+        if let Some(id) = session.id {
+            sqlx::query("UPDATE focus_sessions SET end_ms = ? WHERE id = ?")
+                .bind(session.end_ms)
+                .bind(id)
+                .execute(&self.pool)
+                .await?;
+            return Ok(Some(id));
+        }
+        let result = sqlx::query(
+            r#"INSERT INTO focus_sessions (app_name, window_name, monitor_id, start_ms, end_ms)
+            VALUES (?, ?, ?, ?, ?)"#,
+        )
+        .bind(&session.app_name)
+        .bind(&session.window_name)
+        .bind(session.monitor_id.map(|id| id as i64))
+        .bind(session.start_ms)
+        .bind(session.end_ms)
+        .execute(&self.pool)
+        .await?;
+        Ok(Some(result.last_insert_rowid()))
     }
 }
 
@@ -252,7 +666,8 @@ impl SqliteSink {
 
         let capture_rows: Vec<CaptureRow> = sqlx::query_as(
             r#"
-            SELECT id, frame_number, timestamp_ms
+            SELECT id, frame_number, timestamp_ms, monitor_id, monitor_name, monitor_width, monitor_height,
+                   changed_regions, note, settle_skipped_frames
             FROM captures
             ORDER BY timestamp_ms DESC
             LIMIT ?
@@ -268,17 +683,7 @@ impl SqliteSink {
 
         let mut captures: BTreeMap<i64, CaptureWithWindows> = capture_rows
             .into_iter()
-            .map(|row| {
-                (
-                    row.id,
-                    CaptureWithWindows {
-                        capture_id: row.id,
-                        frame_number: row.frame_number,
-                        timestamp_ms: row.timestamp_ms,
-                        windows: Vec::new(),
-                    },
-                )
-            })
+            .map(|row| (row.id, row.into_capture()))
             .collect();
 
         let ids: Vec<i64> = captures.keys().copied().collect();
@@ -286,25 +691,14 @@ impl SqliteSink {
 
         for row in window_rows {
             if let Some(capture) = captures.get_mut(&row.capture_id) {
+                let mut record = row.into_record();
                 // Load image from disk if image_path exists but no base64
-                let image_base64 = if row.image_base64.is_some() {
-                    row.image_base64
-                } else if let Some(ref path) = row.image_path {
-                    load_image_as_base64(path)
-                } else {
-                    None
-                };
-
-                capture.windows.push(CapturedWindowRecord {
-                    window_name: row.window_name.unwrap_or_default(),
-                    app_name: row.app_name.unwrap_or_default(),
-                    text: row.text.unwrap_or_default(),
-                    confidence: row.confidence,
-                    ocr_json: row.ocr_json,
-                    image_base64,
-                    image_path: row.image_path,
-                    browser_url: row.browser_url,
-                });
+                if record.image_base64.is_none() {
+                    record.image_base64 =
+                        record.image_path.as_deref().and_then(load_image_as_base64);
+                }
+
+                capture.windows.push(record);
             }
         }
 
@@ -322,7 +716,8 @@ impl SqliteSink {
 
         let capture_rows: Vec<CaptureRow> = sqlx::query_as(
             r#"
-            SELECT id, frame_number, timestamp_ms
+            SELECT id, frame_number, timestamp_ms, monitor_id, monitor_name, monitor_width, monitor_height,
+                   changed_regions, note, settle_skipped_frames
             FROM captures
             ORDER BY timestamp_ms DESC
             LIMIT ?
@@ -338,17 +733,7 @@ impl SqliteSink {
 
         let mut captures: BTreeMap<i64, CaptureWithWindows> = capture_rows
             .into_iter()
-            .map(|row| {
-                (
-                    row.id,
-                    CaptureWithWindows {
-                        capture_id: row.id,
-                        frame_number: row.frame_number,
-                        timestamp_ms: row.timestamp_ms,
-                        windows: Vec::new(),
-                    },
-                )
-            })
+            .map(|row| (row.id, row.into_capture()))
             .collect();
 
         let ids: Vec<i64> = captures.keys().copied().collect();
@@ -357,16 +742,8 @@ impl SqliteSink {
 
         for row in window_rows {
             if let Some(capture) = captures.get_mut(&row.capture_id) {
-                capture.windows.push(CapturedWindowRecord {
-                    window_name: row.window_name.unwrap_or_default(),
-                    app_name: row.app_name.unwrap_or_default(),
-                    text: row.text.unwrap_or_default(),
-                    confidence: row.confidence,
-                    ocr_json: row.ocr_json,
-                    image_base64: None, // Don't load images
-                    image_path: row.image_path,
-                    browser_url: row.browser_url,
-                });
+                // Metadata rows never select image data.
+                capture.windows.push(row.into_record());
             }
         }
 
@@ -405,6 +782,39 @@ impl SqliteSink {
         Ok(images)
     }
 
+    /// Fetch the OCR layouts stored for the windows of specific capture IDs.
+    pub async fn fetch_layouts_for_captures(&self, ids: &[i64]) -> Result<Vec<WindowLayout>> {
+        if ids.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let mut builder = QueryBuilder::new(
+            "SELECT capture_id, window_name, app_name, image_hash, ocr_json FROM captured_windows WHERE ocr_json IS NOT NULL AND capture_id IN (",
+        );
+        let mut separated = builder.separated(", ");
+        for id in ids {
+            separated.push_bind(id);
+        }
+        builder.push(") ORDER BY capture_id, id");
+
+        let rows = builder
+            .build_query_as::<WindowLayout>()
+            .fetch_all(&self.pool)
+            .await?;
+        Ok(rows)
+    }
+
+    /// Cached OCR result (serialized payload) stored under `key`, if any.
+    pub async fn load_cached_ocr(&self, key: &str) -> Result<Option<String>> {
+        ocr_cache::load(&self.pool, key, current_time_ms() as i64).await
+    }
+
+    /// Cache an OCR result under `key`, keeping at most `max_entries` rows
+    /// (0 for no limit) by evicting the least recently used.
+    pub async fn store_cached_ocr(&self, key: &str, payload: &str, max_entries: u64) -> Result<()> {
+        ocr_cache::store(&self.pool, key, payload, current_time_ms() as i64, max_entries).await
+    }
+
     /// Search captures by text content and/or time range.
     /// Returns captures where OCR text, window name, app name, or browser URL matches ANY of the query terms.
     pub async fn search_captures(
@@ -444,7 +854,9 @@ impl SqliteSink {
         
         let sql = format!(
             r#"
-            SELECT DISTINCT c.id, c.frame_number, c.timestamp_ms
+            SELECT DISTINCT c.id, c.frame_number, c.timestamp_ms, c.monitor_id, c.monitor_name,
+                   c.monitor_width, c.monitor_height, c.changed_regions, c.note,
+                   c.settle_skipped_frames
             FROM captures c
             JOIN captured_windows cw ON cw.capture_id = c.id
             WHERE ({}){}
@@ -485,37 +897,81 @@ impl SqliteSink {
 
         let mut by_capture: BTreeMap<i64, CaptureWithWindows> = capture_rows
             .into_iter()
-            .map(|c| {
-                (
-                    c.id,
-                    CaptureWithWindows {
-                        capture_id: c.id,
-                        frame_number: c.frame_number,
-                        timestamp_ms: c.timestamp_ms,
-                        windows: vec![],
-                    },
-                )
-            })
+            .map(|c| (c.id, c.into_capture()))
             .collect();
 
         for wr in window_rows {
             if let Some(capture) = by_capture.get_mut(&wr.capture_id) {
-                capture.windows.push(CapturedWindowRecord {
-                    window_name: wr.window_name.unwrap_or_default(),
-                    app_name: wr.app_name.unwrap_or_default(),
-                    text: wr.text.unwrap_or_default(),
-                    confidence: wr.confidence,
-                    ocr_json: wr.ocr_json,
-                    image_base64: None,
-                    image_path: wr.image_path,
-                    browser_url: wr.browser_url,
-                });
+                capture.windows.push(wr.into_record());
             }
         }
 
         Ok(by_capture.into_values().collect())
     }
 
+    /// Focused time per app between `start_ms` and `end_ms`, longest first.
+    /// Sessions crossing the range edges only count the part inside it.
+    pub async fn app_usage(
+        &self,
+        start_ms: i64,
+        end_ms: i64,
+        limit: i64,
+    ) -> Result<Vec<UsageTotal>> {
+        let rows = sqlx::query_as(
+            r#"
+            SELECT app_name, NULL AS window_name,
+                   SUM(MIN(end_ms, ?) - MAX(start_ms, ?)) AS total_ms,
+                   COUNT(1) AS sessions
+            FROM focus_sessions
+            WHERE end_ms > ? AND start_ms < ?
+            GROUP BY app_name
+            ORDER BY total_ms DESC
+            LIMIT ?
+            "#,
+        )
+        .bind(end_ms)
+        .bind(start_ms)
+        .bind(start_ms)
+        .bind(end_ms)
+        .bind(limit.max(0))
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(rows)
+    }
+
+    /// Focused time per app and window title between `start_ms` and
+    /// `end_ms`, optionally for one app only, longest first.
+    pub async fn title_usage(
+        &self,
+        start_ms: i64,
+        end_ms: i64,
+        app_name: Option<&str>,
+        limit: i64,
+    ) -> Result<Vec<UsageTotal>> {
+        let rows = sqlx::query_as(
+            r#"
+            SELECT app_name, window_name,
+                   SUM(MIN(end_ms, ?) - MAX(start_ms, ?)) AS total_ms,
+                   COUNT(1) AS sessions
+            FROM focus_sessions
+            WHERE end_ms > ? AND start_ms < ? AND (? IS NULL OR app_name = ?)
+            GROUP BY app_name, window_name
+            ORDER BY total_ms DESC
+            LIMIT ?
+            "#,
+        )
+        .bind(end_ms)
+        .bind(start_ms)
+        .bind(start_ms)
+        .bind(end_ms)
+        .bind(app_name)
+        .bind(app_name)
+        .bind(limit.max(0))
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(rows)
+    }
+
     /// Fetch recent chat messages ordered newest first.
     pub async fn fetch_chat_messages(&self, limit: i64) -> Result<Vec<ChatMessage>> {
         let limited = limit.max(0);
@@ -550,6 +1006,67 @@ struct CapturedWindowRow {
     image_base64: Option<String>,
     image_path: Option<String>,
     browser_url: Option<String>,
+    reused_window_id: Option<i64>,
+    phash: Option<i64>,
+    ocr_status: Option<String>,
+    image_format: Option<String>,
+    image_bytes: Option<i64>,
+    image_hash: Option<String>,
+    process_id: Option<i32>,
+    executable_path: Option<String>,
+    window_x: Option<i32>,
+    window_y: Option<i32>,
+    window_width: Option<i64>,
+    window_height: Option<i64>,
+    z_order: Option<i32>,
+    monitor_id: Option<i64>,
+    is_focused: Option<bool>,
+    ocr_engine: Option<String>,
+}
+
+impl CapturedWindowRow {
+    fn into_record(self) -> CapturedWindowRecord {
+        let bounds = match (self.window_x, self.window_y, self.window_width, self.window_height) {
+            (Some(x), Some(y), Some(width), Some(height)) => Some(ScreenRect {
+                x,
+                y,
+                width: width.max(0) as u32,
+                height: height.max(0) as u32,
+            }),
+            _ => None,
+        };
+        CapturedWindowRecord {
+            window_name: self.window_name.unwrap_or_default(),
+            app_name: self.app_name.unwrap_or_default(),
+            text: self.text.unwrap_or_default(),
+            confidence: self.confidence,
+            ocr_json: self.ocr_json,
+            ocr_engine: self.ocr_engine,
+            image_base64: self.image_base64,
+            image_path: self.image_path,
+            browser_url: self.browser_url,
+            reused_window_id: self.reused_window_id,
+            phash: self.phash,
+            ocr_status: OcrStatus::from_column(self.ocr_status.as_deref()),
+            image_format: self.image_format,
+            image_bytes: self.image_bytes,
+            image_hash: self.image_hash,
+            process_id: self.process_id,
+            executable_path: self.executable_path,
+            bounds,
+            z_order: self.z_order,
+            monitor_id: self.monitor_id.map(|id| id as u32),
+            is_focused: self.is_focused,
+        }
+    }
+}
+
+#[derive(FromRow)]
+struct HashedWindowRow {
+    id: i64,
+    timestamp_ms: i64,
+    #[sqlx(flatten)]
+    window: CapturedWindowRow,
 }
 
 #[derive(FromRow)]
@@ -557,6 +1074,37 @@ struct CaptureRow {
     id: i64,
     frame_number: i64,
     timestamp_ms: i64,
+    monitor_id: Option<i64>,
+    monitor_name: Option<String>,
+    monitor_width: Option<i64>,
+    monitor_height: Option<i64>,
+    changed_regions: Option<String>,
+    note: Option<String>,
+    settle_skipped_frames: Option<i64>,
+}
+
+impl CaptureRow {
+    /// Convert into an API capture with no windows attached yet.
+    fn into_capture(self) -> CaptureWithWindows {
+        let changed_regions = self
+            .changed_regions
+            .and_then(|raw| serde_json::from_str(&raw).ok())
+            .unwrap_or_default();
+
+        CaptureWithWindows {
+            capture_id: self.id,
+            frame_number: self.frame_number,
+            timestamp_ms: self.timestamp_ms,
+            monitor_id: self.monitor_id.map(|id| id as u32),
+            monitor_name: self.monitor_name,
+            monitor_width: self.monitor_width.map(|width| width as u32),
+            monitor_height: self.monitor_height.map(|height| height as u32),
+            changed_regions,
+            windows: Vec::new(),
+            note: self.note,
+            settle_skipped_frames: self.settle_skipped_frames.map(|frames| frames as u32),
+        }
+    }
 }
 
 #[derive(FromRow)]
@@ -566,6 +1114,45 @@ struct ImagePathRow {
 }
 
 impl SqliteSink {
+    async fn write_window_ocr(&self, window_id: i64, update: &WindowOcrUpdate) -> Result<()> {
+        // The blob row has to exist before windows point at it so the
+        // reference-count triggers find it.
+        let mut tx = self.pool.begin().await?;
+        if let (Some(hash), Some(path)) = (&update.image_hash, &update.image_path) {
+            blobs::register(
+                &mut tx,
+                hash,
+                path,
+                update.image_format.as_deref(),
+                update.image_bytes,
+            )
+            .await?;
+        }
+        sqlx::query(
+            r#"
+            UPDATE captured_windows
+            SET text = ?, confidence = ?, ocr_json = ?, image_path = ?, ocr_status = ?,
+                image_format = ?, image_bytes = ?, image_hash = ?, ocr_engine = ?
+            WHERE id = ? OR reused_window_id = ?
+            "#,
+        )
+        .bind(&update.text)
+        .bind(update.confidence)
+        .bind(&update.ocr_json)
+        .bind(&update.image_path)
+        .bind(update.status.as_str())
+        .bind(&update.image_format)
+        .bind(update.image_bytes)
+        .bind(&update.image_hash)
+        .bind(&update.ocr_engine)
+        .bind(window_id)
+        .bind(window_id)
+        .execute(&mut *tx)
+        .await?;
+        tx.commit().await?;
+        Ok(())
+    }
+
     async fn prune(&self) -> Result<()> {
         if let Some(days) = self.retention_days {
             let cutoff_ms = current_time_ms().saturating_sub(days.saturating_mul(86_400_000));
@@ -573,6 +1160,10 @@ impl SqliteSink {
                 .bind(cutoff_ms as i64)
                 .execute(&self.pool)
                 .await?;
+            sqlx::query("DELETE FROM focus_sessions WHERE end_ms < ?")
+                .bind(cutoff_ms as i64)
+                .execute(&self.pool)
+                .await?;
         }
 
         if let Some(max) = self.max_captures {
@@ -600,6 +1191,10 @@ impl SqliteSink {
             }
         }
 
+        // Deleted captures released their windows' image references and
+        // cached OCR results.
+        ocr_cache::prune(&self.pool).await?;
+        blobs::collect_garbage(&self.pool).await?;
         Ok(())
     }
 }
@@ -625,7 +1220,7 @@ async fn fetch_windows_for_ids(pool: &Pool<Sqlite>, ids: &[i64]) -> Result<Vec<C
     }
 
     let mut builder = QueryBuilder::new(
-        "SELECT capture_id, window_name, app_name, text, confidence, ocr_json, image_base64, image_path, browser_url FROM captured_windows WHERE capture_id IN (",
+        "SELECT capture_id, window_name, app_name, text, confidence, ocr_json, image_base64, image_path, browser_url, reused_window_id, phash, ocr_status, image_format, image_bytes, image_hash, process_id, executable_path, window_x, window_y, window_width, window_height, z_order, monitor_id, is_focused, ocr_engine FROM captured_windows WHERE capture_id IN (",
     );
     let mut separated = builder.separated(", ");
     for id in ids {
@@ -645,7 +1240,7 @@ async fn fetch_windows_metadata_for_ids(pool: &Pool<Sqlite>, ids: &[i64]) -> Res
     }
 
     let mut builder = QueryBuilder::new(
-        "SELECT capture_id, window_name, app_name, text, confidence, ocr_json, NULL as image_base64, image_path, browser_url FROM captured_windows WHERE capture_id IN (",
+        "SELECT capture_id, window_name, app_name, text, confidence, ocr_json, NULL as image_base64, image_path, browser_url, reused_window_id, phash, ocr_status, image_format, image_bytes, image_hash, process_id, executable_path, window_x, window_y, window_width, window_height, z_order, monitor_id, is_focused, ocr_engine FROM captured_windows WHERE capture_id IN (",
     );
     let mut separated = builder.separated(", ");
     for id in ids {
@@ -670,7 +1265,7 @@ impl SharedSink {
         }
     }
 
-    pub async fn persist(&self, batch: CaptureBatch) -> Result<()> {
+    pub async fn persist(&self, batch: CaptureBatch) -> Result<PersistedBatch> {
         let guard = self.inner.read().await;
         guard.persist_batch(batch).await
     }
@@ -0,0 +1,94 @@
+//! Persistent OCR results keyed by engine, languages and image hash.
+//!
+//! The backend only writes here when the persistent cache is enabled, and
+//! never writes results that redaction would have changed. `last_used_ms`
+//! orders eviction once the table grows past its limit, and rows not used
+//! since the oldest remaining capture are dropped with it.
+
+use anyhow::Result;
+use sqlx::{Pool, Sqlite};
+
+pub(crate) async fn create_schema(pool: &Pool<Sqlite>) -> Result<()> {
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS ocr_cache (
+            key TEXT PRIMARY KEY,
+            payload TEXT NOT NULL,
+            created_ms INTEGER NOT NULL,
+            last_used_ms INTEGER NOT NULL
+        );
+        "#,
+    )
+    .execute(pool)
+    .await?;
+    sqlx::query("CREATE INDEX IF NOT EXISTS ocr_cache_last_used ON ocr_cache (last_used_ms)")
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+/// The payload stored under `key`, marking it as used at `now_ms`.
+pub(crate) async fn load(pool: &Pool<Sqlite>, key: &str, now_ms: i64) -> Result<Option<String>> {
+    let payload: Option<String> =
+        sqlx::query_scalar("UPDATE ocr_cache SET last_used_ms = ? WHERE key = ? RETURNING payload")
+            .bind(now_ms)
+            .bind(key)
+            .fetch_optional(pool)
+            .await?;
+    Ok(payload)
+}
+
+/// Store `payload` under `key`, then drop the least recently used rows
+/// beyond `max_entries` (0 keeps everything).
+pub(crate) async fn store(
+    pool: &Pool<Sqlite>,
+    key: &str,
+    payload: &str,
+    now_ms: i64,
+    max_entries: u64,
+) -> Result<()> {
+    sqlx::query(
+        r#"
+        INSERT INTO ocr_cache (key, payload, created_ms, last_used_ms) VALUES (?, ?, ?, ?)
+        ON CONFLICT(key) DO UPDATE SET payload = excluded.payload, last_used_ms = excluded.last_used_ms
+        "#,
+    )
+    .bind(key)
+    .bind(payload)
+    .bind(now_ms)
+    .bind(now_ms)
+    .execute(pool)
+    .await?;
+
+    if max_entries > 0 {
+        sqlx::query(
+            r#"
+            DELETE FROM ocr_cache
+            WHERE key IN (
+                SELECT key FROM ocr_cache
+                ORDER BY last_used_ms DESC
+                LIMIT -1 OFFSET ?
+            )
+            "#,
+        )
+        .bind(max_entries as i64)
+        .execute(pool)
+        .await?;
+    }
+    Ok(())
+}
+
+/// Drop rows not used since the oldest remaining capture: the captures that
+/// used them have been deleted. With no captures left, every row goes.
+pub(crate) async fn prune(pool: &Pool<Sqlite>) -> Result<()> {
+    sqlx::query(
+        r#"
+        DELETE FROM ocr_cache
+        WHERE last_used_ms < COALESCE((SELECT MIN(timestamp_ms) FROM captures), ?)
+        "#,
+    )
+    .bind(i64::MAX)
+    .execute(pool)
+    .await?;
+    Ok(())
+}